@@ -1,49 +1,135 @@
-mod ast;
-mod tokenizer;
-mod parser;
-mod derivative;
-mod function_table;
-mod simplifier;
-mod constants;
+use std::sync::Mutex;
+use std::time::Duration;
 
-#[tauri::command]
- fn find_der(input_expr: &str,diff_var:&str) -> Result<String, String> {
-    let trimmed_expr = input_expr.trim();
-    print!("{}",trimmed_expr);
-    print!("{}",diff_var);
-  
+use derivative_calculator::angle::AngleMode;
+use derivative_calculator::context::Context;
+use derivative_calculator::limits::{self, CancelToken};
+use derivative_calculator::{ast, derivative, parser, plot, simplifier, svg, tangent, tokenizer};
 
-    if trimmed_expr.is_empty() {
-        return Err("Input expression is empty".to_string());
-    }
+/// How long one command may run before giving up.
+const TIME_LIMIT: Duration = Duration::from_secs(5);
 
-    if diff_var.len() != 1 || !diff_var.chars().all(|c| c.is_alphabetic()) {
+/// The token shared by the commands currently running, so that `cancel_der`
+/// can stop them.
+#[derive(Default)]
+struct Running(Mutex<CancelToken>);
+
+/// Fails unless `var` is a single letter, as every command expects of `diff_var`.
+fn check_var(var: &str) -> Result<(), String> {
+    if var.len() != 1 || !var.chars().all(|c| c.is_alphabetic()) {
         return Err("Variable must be a single alphabetic character".to_string());
     }
+    Ok(())
+}
+
+/// The context for one command: the angle mode, the time limit and the
+/// cancel token of the commands running now, or a new one once those have
+/// been cancelled.
+fn context(degrees: bool, running: &Running) -> Result<Context, String> {
+    let mut token = running.0.lock().map_err(|e| e.to_string())?;
+    if token.is_cancelled() {
+        *token = CancelToken::new();
+    }
+    let mut ctx = Context::default();
+    ctx.limits.time_limit = Some(TIME_LIMIT);
+    ctx.limits.cancel = token.clone();
+    if degrees {
+        ctx.angle = AngleMode::Degrees;
+    }
+    Ok(ctx)
+}
 
-    // Tokenize expression
-    let tokens = tokenizer::tokenize(trimmed_expr)
+fn parse(text: &str) -> Result<ast::Expr, String> {
+    let tokens = tokenizer::tokenize(text.trim())
         .map_err(|e| format!("Tokenizer error: {}", e))?;
+    parser::Parser::new(tokens).parse()
+        .map_err(|e| format!("Parser error: {}", e))
+}
+
+// Async, so that a slow derivative runs off the main thread and the UI stays responsive
+#[tauri::command]
+async fn find_der(
+    input_expr: String,
+    diff_var: String,
+    degrees: bool,
+    running: tauri::State<'_, Running>,
+) -> Result<String, String> {
+    if input_expr.trim().is_empty() {
+        return Err("Input expression is empty".to_string());
+    }
+    check_var(&diff_var)?;
+    let ctx = context(degrees, &running)?;
+    // Deeply nested input needs more stack than the async runtime's threads have
+    limits::on_large_stack(|| {
+        let der = derivative::derivative_with(&parse(&input_expr)?, &diff_var, &ctx)?;
+        let simp = simplifier::simplify_with(&der, &ctx)?;
+        Ok(format!("{}", simp))
+    })
+}
 
-    // Parse tokens
-    let mut parser = parser::Parser::new(tokens);
-    let expr = parser.parse()
-        .map_err(|e| format!("Parser error: {}", e))?;
+/// Plots the expression and its derivative from `from` to `to` as an SVG image.
+#[tauri::command]
+async fn plot_der(
+    input_expr: String,
+    diff_var: String,
+    from: f64,
+    to: f64,
+    degrees: bool,
+    running: tauri::State<'_, Running>,
+) -> Result<String, String> {
+    if input_expr.trim().is_empty() {
+        return Err("Input expression is empty".to_string());
+    }
+    check_var(&diff_var)?;
+    if from >= to {
+        return Err("The plot range must go from a smaller to a larger value".to_string());
+    }
+    let ctx = context(degrees, &running)?;
+    limits::on_large_stack(|| {
+        let series = plot::sample_adaptive(&parse(&input_expr)?, &diff_var, from, to, &ctx)?;
+        svg::SvgPlot::default().render(&series)
+    })
+}
 
-    // Derivative
-    let der = derivative::derivative(&expr, diff_var);
+/// The tangent and normal lines to the graph of the expression at `point`,
+/// each with its slope and intercept.
+#[tauri::command]
+async fn tangent_at(
+    input_expr: String,
+    diff_var: String,
+    point: String,
+    degrees: bool,
+    running: tauri::State<'_, Running>,
+) -> Result<String, String> {
+    if input_expr.trim().is_empty() || point.trim().is_empty() {
+        return Err("Input expression or point is empty".to_string());
+    }
+    check_var(&diff_var)?;
+    let ctx = context(degrees, &running)?;
+    limits::on_large_stack(|| {
+        let lines = tangent::tangent_lines(&parse(&input_expr)?, &diff_var, &parse(&point)?, &ctx)?;
+        let t = &lines.tangent;
+        let normal = match &lines.normal {
+            Some(n) => format!("{} (slope {}, intercept {})", n, n.slope, n.intercept),
+            None => format!("{} = {}", diff_var, lines.point.0),
+        };
+        Ok(format!("Tangent: {} (slope {}, intercept {}); Normal: {}", t, t.slope, t.intercept, normal))
+    })
+}
 
-    // Simplify
-    let simp = simplifier::simplify(&der);
-     print!("{}",simp);
-    Ok(format!("{}", simp))
+/// Stops the running commands, which then fail with "Cancelled".
+#[tauri::command]
+fn cancel_der(running: tauri::State<'_, Running>) -> Result<(), String> {
+    running.0.lock().map_err(|e| e.to_string())?.cancel();
+    Ok(())
 }
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
     tauri::Builder::default()
         .plugin(tauri_plugin_opener::init())
-        .invoke_handler(tauri::generate_handler![find_der])
+        .manage(Running::default())
+        .invoke_handler(tauri::generate_handler![find_der, plot_der, tangent_at, cancel_der])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
 }
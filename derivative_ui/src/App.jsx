@@ -11,6 +11,12 @@ const inputRef = useRef(null);
 const variableRef=useRef(null);
 
 const [variableName,setVariableName]=useState("x");
+// Whether trig functions take and give angles in degrees rather than radians
+const [degrees,setDegrees]=useState(false);
+const [plotFrom,setPlotFrom]=useState("-10");
+const [plotTo,setPlotTo]=useState("10");
+const [plotSvg,setPlotSvg]=useState("");
+const [tangentPoint,setTangentPoint]=useState("");
 
 
   const [inputext, setinputext] = useState("");
@@ -72,11 +78,14 @@ if (variableRef.current === document.activeElement) {
       const response = await invoke("find_der", {
         inputExpr: inputext,
         diffVar: variableName.trim()||"x",
+        degrees,
       });
       setoutputtext(response);
       return response;
     } catch (error) {
       console.error("Error calling find_der:", error);
+      // e.g. "Expression too large: ..." when a limit is hit
+      setoutputtext(String(error));
       return "Error";
     }
   }
@@ -84,6 +93,45 @@ if (variableRef.current === document.activeElement) {
 
 
 
+  async function getPlot() {
+    const from = Number(plotFrom), to = Number(plotTo);
+    if (plotFrom.trim() === "" || plotTo.trim() === "" || isNaN(from) || isNaN(to)) {
+      setoutputtext("Invalid plot range");
+      return;
+    }
+    try {
+      // An SVG image of the expression and its derivative, drawn in Rust
+      const svg = await invoke("plot_der", {
+        inputExpr: inputext,
+        diffVar: variableName.trim()||"x",
+        from,
+        to,
+        degrees,
+      });
+      setPlotSvg(svg);
+    } catch (error) {
+      console.error("Error calling plot_der:", error);
+      setPlotSvg("");
+      setoutputtext(String(error));
+    }
+  }
+
+  async function getTangent() {
+    try {
+      // The tangent and normal lines at the point, with their slopes and intercepts
+      const lines = await invoke("tangent_at", {
+        inputExpr: inputext,
+        diffVar: variableName.trim()||"x",
+        point: tangentPoint,
+        degrees,
+      });
+      setoutputtext(lines);
+    } catch (error) {
+      console.error("Error calling tangent_at:", error);
+      setoutputtext(String(error));
+    }
+  }
+
 const addValue = async (value) => {
   const el = inputRef.current;
   if (!el) return;
@@ -92,6 +140,8 @@ const addValue = async (value) => {
   const end = el.selectionEnd;
 
   if (value === "clear") {
+    // Also stop a derivative, plot or tangent that is still being computed
+    invoke("cancel_der").catch(() => {});
     setinputext("");
     setoutputtext("");
     return;
@@ -204,6 +254,13 @@ type="text" placeholder="Enter expression"/>
           </div>
         </section>
 
+        {plotSvg && (
+          <section
+            className="mx-8 my-4 rounded-xl overflow-hidden shadow-lg"
+            dangerouslySetInnerHTML={{ __html: plotSvg }}
+          />
+        )}
+
         <section className="px-8 flex gap-2 sm:gap-5 text-sm">
 
              <div className="  sm:relative">
@@ -230,6 +287,22 @@ type="text" placeholder="Enter expression"/>
 
 
 
+<div className="sm:relative flex gap-1">
+<button onClick={getPlot}>Plot</button>
+<input className="w-10 outline-none bg-transparent" value={plotFrom} onChange={(e)=>setPlotFrom(e.target.value)} title="Plot from"/>
+<span>..</span>
+<input className="w-10 outline-none bg-transparent" value={plotTo} onChange={(e)=>setPlotTo(e.target.value)} title="Plot to"/>
+</div>
+
+<div className="sm:relative flex gap-1">
+<button onClick={getTangent}>Tangent at</button>
+<input className="w-10 outline-none bg-transparent" value={tangentPoint} onChange={(e)=>setTangentPoint(e.target.value)} placeholder="x0" title="Point of tangency"/>
+</div>
+
+<div className="sm:relative">
+<button onClick={()=>setDegrees(!degrees)} title="Angle unit of trig functions">{degrees ? "DEG" : "RAD"}</button>
+</div>
+
 <div className="sm:relative">
 <button onClick={()=>setActiveMenu("manual")}>Manual</button>
 {active_menu=="manual" && <Manual setActiveMenu={setActiveMenu}/>
@@ -37,6 +37,7 @@ export default function Manual({setActiveMenu}) {
       <li>You can use powers on functions: <code>sin^2(x)</code> means (sin(x))²</li>
       <li>You can use parentheses for clarity: <code>sin^2(x+1)</code></li>
       <li>Supported functions: sin, cos, tan, exp, log, sinh, cosh, tanh, etc.</li>
+      <li>Use the <code>RAD</code>/<code>DEG</code> button to switch trig functions to degrees: <code>sin(30)</code> is then 0.5</li>
       <li>Supported constants: pi, π, e, euler, deg, degree</li>
     </ul>
   </div>
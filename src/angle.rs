@@ -0,0 +1,159 @@
+use std::f64::consts::PI;
+
+use crate::ast::Expr;
+use crate::eval::Function;
+use crate::simplifier::normalize;
+
+/// One degree in radians, the value of the `deg` constant.
+const DEGREE: f64 = PI / 180.0;
+
+/// The largest denominator [`snap`] tries.
+const MAX_DENOMINATOR: u32 = 360;
+
+/// The unit in which trigonometric functions take their arguments and inverse
+/// trigonometric functions give their results. Hyperbolic functions are not affected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AngleMode {
+    #[default]
+    Radians,
+    /// `sin(30)` is 1/2 and `arctan(1)` is 45.
+    Degrees,
+}
+
+impl AngleMode {
+    /// Parses a mode name as accepted by the `--angle` option.
+    pub fn parse(name: &str) -> Result<Self, String> {
+        match name {
+            "rad" | "radians" => Ok(AngleMode::Radians),
+            "deg" | "degrees" => Ok(AngleMode::Degrees),
+            _ => Err(format!("Unknown angle mode '{}' (expected rad or deg)", name)),
+        }
+    }
+
+    /// The expression in radians, the unit everything else works in: in degree
+    /// mode `sin(u)` becomes `sin(u * deg)` and `arcsin(u)` becomes `arcsin(u) / deg`.
+    /// Differentiating the result gives the `π/180` chain factor.
+    pub fn to_radians(self, expr: &Expr) -> Expr {
+        match self {
+            AngleMode::Radians => expr.clone(),
+            AngleMode::Degrees => to_radians(expr),
+        }
+    }
+
+    /// Undoes [`to_radians`](AngleMode::to_radians) on a canonical expression,
+    /// e.g. on the simplified derivative `deg * cos(deg * x)` for `cos(x)`.
+    pub fn from_radians(self, expr: &Expr) -> Expr {
+        match self {
+            AngleMode::Radians => expr.clone(),
+            AngleMode::Degrees => normalize(&snap_constant_terms(&snap_inverses(&normalize(&from_radians(expr))))),
+        }
+    }
+}
+
+fn is_circular(name: &str) -> bool {
+    use Function::*;
+    matches!(Function::from_name(name), Some(Sin | Cos | Tan | Cot | Sec | Cosec))
+}
+
+fn is_inverse_circular(name: &str) -> bool {
+    use Function::*;
+    matches!(Function::from_name(name), Some(Arcsin | Arccos | Arctan | Arccsc | Arcsec | Arccot))
+}
+
+fn to_radians(expr: &Expr) -> Expr {
+    match expr.map_children(to_radians) {
+        Expr::Func(name, arg) if is_circular(&name) => {
+            Expr::Func(name, Box::new(Expr::Product(vec![*arg, Expr::Num(DEGREE)])))
+        }
+        Expr::Func(name, arg) if is_inverse_circular(&name) => {
+            Expr::Product(vec![Expr::Num(1.0 / DEGREE), Expr::Func(name, arg)])
+        }
+        other => other,
+    }
+}
+
+fn from_radians(expr: &Expr) -> Expr {
+    match expr.map_children(from_radians) {
+        Expr::Func(name, arg) if is_circular(&name) => {
+            Expr::Func(name, Box::new(snap_numbers(&normalize(&in_degrees(&arg)))))
+        }
+        Expr::Func(name, arg) if is_inverse_circular(&name) => {
+            Expr::Product(vec![Expr::Num(DEGREE), Expr::Func(name, arg)])
+        }
+        other => other,
+    }
+}
+
+/// An angle in radians converted to degrees, term by term so that
+/// `deg * x + pi/6` becomes `x + 30` rather than `(deg * x + pi/6) / deg`.
+fn in_degrees(angle: &Expr) -> Expr {
+    match angle {
+        Expr::Sum(terms) => Expr::Sum(terms.iter().map(in_degrees).collect()),
+        Expr::Num(n) => Expr::Num(n / DEGREE),
+        other => Expr::Product(vec![Expr::Num(1.0 / DEGREE), other.clone()]),
+    }
+}
+
+/// Snaps every number to a nearby simple fraction, undoing the rounding error
+/// of converting to radians and back.
+fn snap_numbers(expr: &Expr) -> Expr {
+    match expr {
+        Expr::Num(n) => Expr::Num(snap(*n)),
+        other => other.map_children(snap_numbers),
+    }
+}
+
+/// Snaps the coefficients next to inverse trigonometric functions, where
+/// [`to_radians`] multiplied by `1/deg` and [`from_radians`] by `deg`.
+fn snap_inverses(expr: &Expr) -> Expr {
+    match expr.map_children(snap_inverses) {
+        Expr::Product(factors)
+            if factors
+                .iter()
+                .any(|f| matches!(f, Expr::Func(name, _) if is_inverse_circular(name))) =>
+        {
+            Expr::Product(
+                factors
+                    .into_iter()
+                    .map(|f| match f {
+                        Expr::Num(n) => Expr::Num(snap(n)),
+                        other => other,
+                    })
+                    .collect(),
+            )
+        }
+        other => other,
+    }
+}
+
+/// Snaps a number standing alone or as a term of a sum, where an inverse
+/// trigonometric function of a number such as `arcsin(1/2)` ends up once
+/// its value, `pi / 6` times `1/deg`, has been folded.
+fn snap_constant_terms(expr: &Expr) -> Expr {
+    match expr {
+        Expr::Num(n) => Expr::Num(snap(*n)),
+        Expr::Sum(terms) => Expr::Sum(
+            terms
+                .iter()
+                .map(|t| match t {
+                    Expr::Num(n) => Expr::Num(snap(*n)),
+                    other => other.clone(),
+                })
+                .collect(),
+        ),
+        other => other.clone(),
+    }
+}
+
+/// The fraction with a denominator of at most [`MAX_DENOMINATOR`] within a
+/// few rounding errors of `x`, or `x` itself if there is none.
+fn snap(x: f64) -> f64 {
+    for d in 1..=MAX_DENOMINATOR {
+        let d = f64::from(d);
+        let n = (x * d).round();
+        if (x - n / d).abs() <= 1e-13 * x.abs() {
+            return n / d;
+        }
+    }
+    x
+}
@@ -0,0 +1,138 @@
+use std::collections::HashMap;
+
+use crate::ast::{Expr, Op};
+
+/// What is known about a single symbol.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SymbolFacts {
+    pub real: bool,
+    pub positive: bool,
+    pub nonzero: bool,
+    pub integer: bool,
+}
+
+/// Facts about symbols that let the simplifier and the derivative rules pick
+/// cleaner forms, e.g. `sqrt(x^2) = x` once `x > 0` is known.
+/// Nothing is assumed about a symbol that has no entry.
+#[derive(Debug, Clone, Default)]
+pub struct Assumptions {
+    facts: HashMap<String, SymbolFacts>,
+}
+
+impl Assumptions {
+    /// Records an assumption written as `x>0`, `x!=0` or `x:<fact>`, where
+    /// `<fact>` is one of `real`, `positive`, `nonzero` or `integer`.
+    pub fn assume(&mut self, spec: &str) -> Result<(), String> {
+        let spec: String = spec.chars().filter(|c| !c.is_whitespace()).collect();
+        let (name, fact) = if let Some(name) = spec.strip_suffix(">0") {
+            (name, "positive")
+        } else if let Some(name) = spec.strip_suffix("!=0") {
+            (name, "nonzero")
+        } else if let Some((name, fact)) = spec.split_once(':') {
+            (name, fact)
+        } else {
+            return Err(format!("Cannot read assumption '{}' (expected e.g. x>0, x!=0 or x:integer)", spec));
+        };
+        if name.is_empty() || !name.chars().all(|c| c.is_alphabetic()) {
+            return Err(format!("Assumption '{}' must name a variable", spec));
+        }
+        let facts = self.facts.entry(name.to_string()).or_default();
+        match fact {
+            "real" => facts.real = true,
+            "positive" => {
+                facts.positive = true;
+                facts.nonzero = true;
+                facts.real = true;
+            }
+            "nonzero" => facts.nonzero = true,
+            "integer" => {
+                facts.integer = true;
+                facts.real = true;
+            }
+            other => return Err(format!("Unknown fact '{}' (expected real, positive, nonzero or integer)", other)),
+        }
+        Ok(())
+    }
+
+    /// The facts recorded for a symbol.
+    pub fn facts(&self, name: &str) -> SymbolFacts {
+        self.facts.get(name).copied().unwrap_or_default()
+    }
+
+    /// Whether `expr` is known to be a real number.
+    pub fn is_real(&self, expr: &Expr) -> bool {
+        match expr {
+            Expr::Num(n) => n.is_finite(),
+            Expr::Var(v) => self.facts(v).real,
+            Expr::Neg(inner) => self.is_real(inner),
+            Expr::Sum(operands) | Expr::Product(operands) => operands.iter().all(|e| self.is_real(e)),
+            Expr::BinaryOp { op: Op::Pow, left, right } => {
+                (self.is_positive(left) && self.is_real(right)) || (self.is_real(left) && self.is_integer(right))
+            }
+            Expr::BinaryOp { op: Op::Div, left, right } => self.is_real(left) && self.is_real(right) && self.is_nonzero(right),
+            Expr::BinaryOp { left, right, .. } => self.is_real(left) && self.is_real(right),
+            Expr::Func(name, arg) => match name.as_str() {
+                "sin" | "cos" | "arctan" | "arccot" | "sinh" | "cosh" | "tanh" | "sech" | "arsinh" | "exp" | "abs" => {
+                    self.is_real(arg)
+                }
+                "sqrt" | "log" => self.is_positive(arg),
+                _ => false,
+            },
+        }
+    }
+
+    /// Whether `expr` is known to be strictly positive.
+    pub fn is_positive(&self, expr: &Expr) -> bool {
+        match expr {
+            Expr::Num(n) => *n > 0.0,
+            Expr::Var(v) => self.facts(v).positive,
+            Expr::Sum(operands) | Expr::Product(operands) => operands.iter().all(|e| self.is_positive(e)),
+            Expr::BinaryOp { op: Op::Pow, left, right } => {
+                (self.is_positive(left) && self.is_real(right))
+                    || (self.is_real(left) && self.is_nonzero(left) && is_even(right))
+            }
+            Expr::BinaryOp { op: Op::Mul | Op::Div | Op::Add, left, right } => {
+                self.is_positive(left) && self.is_positive(right)
+            }
+            Expr::Func(name, arg) => match name.as_str() {
+                "exp" | "cosh" | "sech" => self.is_real(arg),
+                "sqrt" => self.is_positive(arg),
+                "abs" => self.is_real(arg) && self.is_nonzero(arg),
+                _ => false,
+            },
+            _ => false,
+        }
+    }
+
+    /// Whether `expr` is known to be nonzero.
+    pub fn is_nonzero(&self, expr: &Expr) -> bool {
+        match expr {
+            Expr::Num(n) => *n != 0.0,
+            Expr::Var(v) => self.facts(v).nonzero,
+            Expr::Neg(inner) => self.is_nonzero(inner),
+            Expr::Product(factors) => factors.iter().all(|e| self.is_nonzero(e)),
+            Expr::BinaryOp { op: Op::Pow, left, .. } => self.is_nonzero(left),
+            Expr::Func(name, arg) => match name.as_str() {
+                "exp" | "cosh" | "sech" => self.is_real(arg),
+                "abs" | "sqrt" => self.is_nonzero(arg),
+                _ => self.is_positive(expr),
+            },
+            _ => self.is_positive(expr),
+        }
+    }
+
+    /// Whether `expr` is known to be an integer.
+    pub fn is_integer(&self, expr: &Expr) -> bool {
+        match expr {
+            Expr::Num(n) => n.fract() == 0.0,
+            Expr::Var(v) => self.facts(v).integer,
+            Expr::Neg(inner) => self.is_integer(inner),
+            Expr::Sum(operands) | Expr::Product(operands) => operands.iter().all(|e| self.is_integer(e)),
+            _ => false,
+        }
+    }
+}
+
+fn is_even(expr: &Expr) -> bool {
+    matches!(expr, Expr::Num(n) if n % 2.0 == 0.0)
+}
@@ -1,9 +1,10 @@
+use std::cmp::Ordering;
 use std::fmt::{self, Display};
-use crate::constants::value_to_constant_name;
+use crate::constants::{pi_multiple_name, value_to_constant_name};
 
 /// Represents the set of mathematical operators that can appear in an expression.
 /// Each variant is a binary operator that accepts two operands.
-#[derive(Debug, PartialEq,Clone)]
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
 pub enum Op {
     Add,    // '+'
     Sub,    // '-'
@@ -19,6 +20,8 @@ pub enum Expr {
     Num(f64),
     /// A variable, such as `x` or `y`.
     Var(String),
+    /// Unary negation, such as `-x` or `-sin(x)`.
+    Neg(Box<Expr>),
     /// A binary operation, composed of an operator and two operand expressions.
     /// For example, `x + 2` would be represented as `BinaryOp { op: Op::Add, ... }`.
     BinaryOp {
@@ -27,8 +30,11 @@ pub enum Expr {
         right: Box<Expr>,
     },
      Func(String,Box<Expr>),
-     
-
+    /// An n-ary sum of terms. The simplifier keeps the terms in canonical order.
+    Sum(Vec<Expr>),
+    /// An n-ary product of factors. The simplifier keeps the factors in canonical
+    /// order, with any numeric coefficient first.
+    Product(Vec<Expr>),
 }
 
 /// Implements the `Display` trait to define how an `Op` is converted to a string.
@@ -54,17 +60,316 @@ impl Display for Expr {
                 // Try to convert the number back to a constant name
                 if let Some(const_name) = value_to_constant_name(*n) {
                     write!(f, "{}", const_name)
+                } else if let Some(multiple) = pi_multiple_name(*n) {
+                    write!(f, "({})", multiple)
                 } else {
                     write!(f, "{}", n)
                 }
             }
             Expr::Var(s) => write!(f, "{}", s),
+            Expr::Neg(inner) => write!(f, "-{}", inner),
+            Expr::BinaryOp { op: Op::Pow, left, right } if reciprocal_power(right).is_some() => {
+                // Negative powers print as fractions: x^-2 as 1 / x^2
+                write!(f, "(1 / {})", Expr::make_pow_display(left, reciprocal_power(right).unwrap()))
+            }
+            Expr::BinaryOp { op: Op::Pow, left, right } => {
+                write!(f, "({} ^ {})", Operand(left), right)
+            }
             Expr::BinaryOp { op, left, right } => {
                 write!(f, "({} {} {})", left, op, right)
             }
             Expr::Func(name,arg) => {
-                write!(f, "{} {}", name,arg)
+                write!(f, "{} {}", name, Operand(arg))
+            }
+            Expr::Sum(terms) => {
+                write!(f, "(")?;
+                for (i, term) in terms.iter().enumerate() {
+                    match (i, negated(term)) {
+                        (0, _) => write!(f, "{}", term)?,
+                        (_, Some(positive)) => write!(f, " - {}", positive)?,
+                        (_, None) => write!(f, " + {}", term)?,
+                    }
+                }
+                write!(f, ")")
+            }
+            Expr::Product(factors) => match factors.split_first() {
+                // A leading -1 coefficient prints as a sign
+                Some((Expr::Num(c), [single])) if *c == -1.0 => write!(f, "-{}", single),
+                Some((Expr::Num(c), rest)) if *c == -1.0 => write!(f, "-{}", Expr::Product(rest.to_vec())),
+                _ => {
+                    // Factors with negative powers go below the fraction bar
+                    let mut numerator = vec![];
+                    let mut denominator = vec![];
+                    for factor in factors {
+                        match factor {
+                            Expr::BinaryOp { op: Op::Pow, left, right } if reciprocal_power(right).is_some() => {
+                                denominator.push(Expr::make_pow_display(left, reciprocal_power(right).unwrap()))
+                            }
+                            other => numerator.push(other.clone()),
+                        }
+                    }
+                    if denominator.is_empty() {
+                        return write_factors(f, factors);
+                    }
+                    match numerator.len() {
+                        0 => write!(f, "(1 / ")?,
+                        1 => write!(f, "({} / ", numerator[0])?,
+                        _ => {
+                            write!(f, "(")?;
+                            write_factors(f, &numerator)?;
+                            write!(f, " / ")?;
+                        }
+                    }
+                    match denominator.len() {
+                        1 => write!(f, "{}", denominator[0])?,
+                        _ => write_factors(f, &denominator)?,
+                    }
+                    write!(f, ")")
+                }
+            },
+        }
+    }
+}
+
+/// Displays an expression in parentheses if it prints with a leading sign, for
+/// the base of a power and the argument of a function: `(-x) ^ 2` reads
+/// differently from `-x ^ 2`, and `sin (-x)` better than `sin -x`.
+struct Operand<'a>(&'a Expr);
+
+impl Display for Operand<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let signed = match self.0 {
+            Expr::Num(n) => *n < 0.0 && value_to_constant_name(*n).is_none() && pi_multiple_name(*n).is_none(),
+            Expr::Neg(_) => true,
+            Expr::Product(factors) => matches!(factors.first(), Some(Expr::Num(c)) if *c == -1.0),
+            _ => false,
+        };
+        if signed {
+            write!(f, "({})", self.0)
+        } else {
+            write!(f, "{}", self.0)
+        }
+    }
+}
+
+fn write_factors(f: &mut fmt::Formatter<'_>, factors: &[Expr]) -> fmt::Result {
+    write!(f, "(")?;
+    for (i, factor) in factors.iter().enumerate() {
+        if i > 0 {
+            write!(f, " * ")?;
+        }
+        write!(f, "{}", factor)?;
+    }
+    write!(f, ")")
+}
+
+/// For a negative numeric exponent `-n`, returns `n`.
+fn reciprocal_power(exp: &Expr) -> Option<f64> {
+    match exp {
+        Expr::Num(n) if *n < 0.0 => Some(-n),
+        _ => None,
+    }
+}
+
+/// If `term` carries a negative sign, returns the term without it, so that sums
+/// can print `a - b` rather than `a + -b`.
+fn negated(term: &Expr) -> Option<Expr> {
+    match term {
+        Expr::Num(n) if *n < 0.0 => Some(Expr::Num(-n)),
+        Expr::Neg(inner) => Some((**inner).clone()),
+        Expr::Product(factors) => match factors.split_first() {
+            Some((Expr::Num(c), [single])) if *c == -1.0 => Some(single.clone()),
+            Some((Expr::Num(c), rest)) if *c == -1.0 => Some(Expr::Product(rest.to_vec())),
+            Some((Expr::Num(c), rest)) if *c < 0.0 => {
+                let mut factors = vec![Expr::Num(-c)];
+                factors.extend_from_slice(rest);
+                Some(Expr::Product(factors))
+            }
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+impl Expr {
+    /// The direct children of this node, in order.
+    pub fn children(&self) -> Vec<&Expr> {
+        match self {
+            Expr::Num(_) | Expr::Var(_) => vec![],
+            Expr::Neg(inner) | Expr::Func(_, inner) => vec![&**inner],
+            Expr::BinaryOp { left, right, .. } => vec![&**left, &**right],
+            Expr::Sum(operands) | Expr::Product(operands) => operands.iter().collect(),
+        }
+    }
+
+    /// Returns a copy of this node with `f` applied to each of its direct children.
+    pub fn map_children(&self, mut f: impl FnMut(&Expr) -> Expr) -> Expr {
+        match self {
+            Expr::Num(_) | Expr::Var(_) => self.clone(),
+            Expr::Neg(inner) => Expr::Neg(Box::new(f(inner))),
+            Expr::BinaryOp { op, left, right } => Expr::BinaryOp {
+                op: op.clone(),
+                left: Box::new(f(left)),
+                right: Box::new(f(right)),
+            },
+            Expr::Func(name, arg) => Expr::Func(name.clone(), Box::new(f(arg))),
+            Expr::Sum(terms) => Expr::Sum(terms.iter().map(f).collect()),
+            Expr::Product(factors) => Expr::Product(factors.iter().map(f).collect()),
+        }
+    }
+
+    /// Returns a copy of this expression with every occurrence of the variable
+    /// `var` replaced by `value`.
+    pub fn substitute(&self, var: &str, value: &Expr) -> Expr {
+        match self {
+            Expr::Var(v) if v == var => value.clone(),
+            _ => self.map_children(|child| child.substitute(var, value)),
+        }
+    }
+
+    /// The names of the variables that occur, sorted and without repeats.
+    pub fn variables(&self) -> Vec<String> {
+        fn collect(expr: &Expr, names: &mut Vec<String>) {
+            match expr {
+                Expr::Num(_) => {}
+                Expr::Var(v) => names.push(v.clone()),
+                Expr::Neg(inner) | Expr::Func(_, inner) => collect(inner, names),
+                Expr::BinaryOp { left, right, .. } => {
+                    collect(left, names);
+                    collect(right, names);
+                }
+                Expr::Sum(operands) | Expr::Product(operands) => {
+                    for operand in operands {
+                        collect(operand, names);
+                    }
+                }
+            }
+        }
+        let mut names = vec![];
+        collect(self, &mut names);
+        names.sort();
+        names.dedup();
+        names
+    }
+
+    /// The number of nodes in the expression, counting an n-ary sum or product
+    /// as n - 1 binary operators.
+    pub fn size(&self) -> usize {
+        match self {
+            Expr::Num(_) | Expr::Var(_) => 1,
+            Expr::Neg(inner) | Expr::Func(_, inner) => 1 + inner.size(),
+            Expr::BinaryOp { left, right, .. } => 1 + left.size() + right.size(),
+            Expr::Sum(operands) | Expr::Product(operands) => {
+                operands.len().saturating_sub(1) + operands.iter().map(Expr::size).sum::<usize>()
             }
         }
     }
+
+    /// Whether every number in the expression is finite, none being infinite or NaN.
+    pub fn numbers_finite(&self) -> bool {
+        match self {
+            Expr::Num(n) => n.is_finite(),
+            Expr::Var(_) => true,
+            Expr::Neg(inner) | Expr::Func(_, inner) => inner.numbers_finite(),
+            Expr::BinaryOp { left, right, .. } => left.numbers_finite() && right.numbers_finite(),
+            Expr::Sum(operands) | Expr::Product(operands) => operands.iter().all(Expr::numbers_finite),
+        }
+    }
+
+    /// Builds `base ^ exp` for display, leaving out an exponent of 1.
+    fn make_pow_display(base: &Expr, exp: f64) -> Expr {
+        if exp == 1.0 {
+            base.clone()
+        } else {
+            Expr::BinaryOp {
+                op: Op::Pow,
+                left: Box::new(base.clone()),
+                right: Box::new(Expr::Num(exp)),
+            }
+        }
+    }
+
+    /// A total order on expressions, used to sort the operands of `Sum` and
+    /// `Product` so that structurally equal expressions compare equal.
+    ///
+    /// Numbers come first, a power sorts next to its base, and sums and products
+    /// are compared from their last operand, so `x`, `2 * x` and `x ^ 2` end up
+    /// adjacent to each other.
+    pub fn canonical_cmp(&self, other: &Expr) -> Ordering {
+        use Expr::*;
+        match (self, other) {
+            (Num(a), Num(b)) => a.total_cmp(b),
+            (Num(_), _) => Ordering::Less,
+            (_, Num(_)) => Ordering::Greater,
+            (a, b) if is_ordered_kind(a) && is_ordered_kind(b) => algebraic_cmp(a, b),
+            (a, b) => fallback_rank(a).cmp(&fallback_rank(b)).then_with(|| fallback_cmp(a, b)),
+        }
+    }
+}
+
+/// Whether `e` is one of the shapes the simplifier produces (other than numbers),
+/// which are ordered relative to each other rather than by kind.
+fn is_ordered_kind(e: &Expr) -> bool {
+    matches!(
+        e,
+        Expr::Var(_) | Expr::Func(..) | Expr::Sum(_) | Expr::Product(_) | Expr::BinaryOp { op: Op::Pow, .. }
+    )
+}
+
+fn algebraic_cmp(a: &Expr, b: &Expr) -> Ordering {
+    use Expr::*;
+    match (a, b) {
+        (Var(x), Var(y)) => x.cmp(y),
+        (Product(x), Product(y)) | (Sum(x), Sum(y)) => cmp_from_last(x, y),
+        (BinaryOp { op: Op::Pow, left: bx, right: ex }, BinaryOp { op: Op::Pow, left: by, right: ey }) => {
+            bx.canonical_cmp(by).then_with(|| ex.canonical_cmp(ey))
+        }
+        (Func(fx, ax), Func(fy, ay)) => fx.cmp(fy).then_with(|| ax.canonical_cmp(ay)),
+        // Mixed kinds: compare as if the simpler side were wrapped in the other's kind
+        (Product(x), y) => cmp_from_last(x, std::slice::from_ref(y)),
+        (x, Product(y)) => cmp_from_last(std::slice::from_ref(x), y),
+        (BinaryOp { op: Op::Pow, left, right }, y) => left.canonical_cmp(y).then_with(|| right.canonical_cmp(&Num(1.0))),
+        (x, BinaryOp { op: Op::Pow, left, right }) => x.canonical_cmp(left).then_with(|| Num(1.0).canonical_cmp(right)),
+        (Sum(x), y) => cmp_from_last(x, std::slice::from_ref(y)),
+        (x, Sum(y)) => cmp_from_last(std::slice::from_ref(x), y),
+        (Var(x), Func(fy, _)) => x.cmp(fy).then(Ordering::Less),
+        (Func(fx, _), Var(y)) => fx.cmp(y).then(Ordering::Greater),
+        _ => unreachable!("algebraic_cmp called on {:?} and {:?}", a, b),
+    }
+}
+
+/// Compares two operand lists starting from their last element; when one list
+/// is a suffix of the other, the shorter one sorts first.
+fn cmp_from_last(a: &[Expr], b: &[Expr]) -> Ordering {
+    for (x, y) in a.iter().rev().zip(b.iter().rev()) {
+        let ord = x.canonical_cmp(y);
+        if ord != Ordering::Equal {
+            return ord;
+        }
+    }
+    a.len().cmp(&b.len())
+}
+
+/// Orders the shapes the simplifier never leaves behind, after everything else.
+fn fallback_rank(e: &Expr) -> u8 {
+    match e {
+        Expr::Num(_) => 0,
+        Expr::Var(_) | Expr::Func(..) | Expr::Sum(_) | Expr::Product(_) => 1,
+        Expr::BinaryOp { op: Op::Pow, .. } => 1,
+        Expr::BinaryOp { op: Op::Div, .. } => 2,
+        Expr::BinaryOp { op: Op::Mul, .. } => 3,
+        Expr::BinaryOp { op: Op::Add, .. } => 4,
+        Expr::BinaryOp { op: Op::Sub, .. } => 5,
+        Expr::Neg(_) => 6,
+    }
+}
+
+fn fallback_cmp(a: &Expr, b: &Expr) -> Ordering {
+    match (a, b) {
+        (Expr::BinaryOp { left: la, right: ra, .. }, Expr::BinaryOp { left: lb, right: rb, .. }) => {
+            la.canonical_cmp(lb).then_with(|| ra.canonical_cmp(rb))
+        }
+        (Expr::Neg(x), Expr::Neg(y)) => x.canonical_cmp(y),
+        _ => Ordering::Equal,
+    }
 }
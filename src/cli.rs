@@ -0,0 +1,308 @@
+use std::collections::HashMap;
+use std::io::{self, IsTerminal};
+use std::time::Duration;
+
+use derivative_calculator::angle::AngleMode;
+use derivative_calculator::ast::Expr;
+use derivative_calculator::complex::{self, Complex};
+use derivative_calculator::context::Context;
+use derivative_calculator::egraph::{CostFn, EGraphSettings};
+use derivative_calculator::equivalence;
+use derivative_calculator::eval::eval_constant;
+use derivative_calculator::interval::{self, Interval};
+use derivative_calculator::limits::Budget;
+use derivative_calculator::logarithm::LogMode;
+use derivative_calculator::polynomial::{self, Poly};
+use derivative_calculator::rational;
+use derivative_calculator::rewrite::RuleSet;
+use derivative_calculator::svg::SvgPlot;
+use derivative_calculator::trig::TrigMode;
+use derivative_calculator::plot::{self, Glyphs, TerminalPlot};
+use derivative_calculator::{parser, simplifier, table, tangent, tokenizer};
+
+/// How many steps `table` takes across its range when not told.
+const TABLE_STEPS: usize = 10;
+
+/// How long each derivative or simplification may run unless `--time-limit` says otherwise.
+const TIME_LIMIT: Duration = Duration::from_secs(10);
+
+/// The columns `plot` leaves for the axis labels when fitting the terminal.
+const PLOT_MARGIN: usize = 12;
+
+/// Options given on the command line.
+pub struct Options {
+    /// Simplification settings: the built-in rules followed by any rules from
+    /// `--rules <file>` and `--rule "<lhs> -> <rhs>"`, the `--trig`, `--log` and `--angle`
+    /// modes, any `--assume` facts, `--expand`, `--complex`, the `--egraph` settings and the
+    /// `--max-nodes`, `--max-depth` and `--time-limit` limits, the last [`TIME_LIMIT`] by default.
+    pub ctx: Context,
+    /// A point (`--at <value>`) at which to also evaluate the derivative.
+    pub at: Option<Expr>,
+    /// Which derivative to take (`--order <n>`), 1 for the first.
+    pub order: usize,
+    /// Whether to print the derivative with repeated subexpressions named (`--cse`).
+    pub cse: bool,
+    /// A file (`--csv <file>`) to write the `table` command's CSV to instead of printing it.
+    pub csv: Option<String>,
+    /// An SVG file (`--plot <file>`) for the `plot` command to write instead of
+    /// drawing on the terminal.
+    pub plot: Option<String>,
+    /// Whether `plot` draws with ASCII characters rather than braille (`--ascii`).
+    pub ascii: bool,
+    /// A command and its arguments, e.g. `factor "x^2 - 1"`, to run instead of
+    /// the interactive prompt.
+    pub command: Vec<String>,
+}
+
+pub fn parse_args() -> Result<Options, String> {
+    let mut ctx = Context::default();
+    ctx.limits.time_limit = Some(TIME_LIMIT);
+    let mut at = None;
+    let mut order = 1;
+    let mut cse = false;
+    let mut csv = None;
+    let mut plot = None;
+    let mut ascii = false;
+    let mut command = vec![];
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--rules" => {
+                let path = args.next().ok_or("--rules expects a file path")?;
+                ctx.rules.extend(RuleSet::load_file(&path)?);
+            }
+            "--rule" => {
+                let rule = args.next().ok_or("--rule expects a rule such as \"log(exp(a)) -> a\"")?;
+                ctx.rules.add(&rule)?;
+            }
+            "--trig" => {
+                let mode = args.next().ok_or("--trig expects off, identities, sincos or exp")?;
+                ctx.trig = TrigMode::parse(&mode)?;
+            }
+            "--log" => {
+                let mode = args.next().ok_or("--log expects off, expand or contract")?;
+                ctx.log = LogMode::parse(&mode)?;
+            }
+            "--angle" => {
+                let mode = args.next().ok_or("--angle expects rad or deg")?;
+                ctx.angle = AngleMode::parse(&mode)?;
+            }
+            "--float" => ctx.fold_floats = true,
+            "--expand" => ctx.expand = true,
+            "--complex" => ctx.complex = true,
+            "--cse" => cse = true,
+            "--ascii" => ascii = true,
+            "--order" => {
+                let n = args.next().ok_or("--order expects a number such as 2")?;
+                order = match n.parse() {
+                    Ok(n) if n >= 1 => n,
+                    _ => return Err(format!("Invalid derivative order '{}'", n)),
+                };
+            }
+            "--egraph" => {
+                let cost = args.next().ok_or("--egraph expects a cost function: nodes or ops")?;
+                ctx.egraph.get_or_insert_with(EGraphSettings::default).cost = CostFn::parse(&cost)?;
+            }
+            "--egraph-nodes" => {
+                let limit = args.next().ok_or("--egraph-nodes expects a node limit")?;
+                ctx.egraph.get_or_insert_with(EGraphSettings::default).node_limit =
+                    limit.parse().map_err(|_| format!("Invalid node limit '{}'", limit))?;
+            }
+            "--egraph-time" => {
+                let ms = args.next().ok_or("--egraph-time expects a time limit in milliseconds")?;
+                ctx.egraph.get_or_insert_with(EGraphSettings::default).time_limit =
+                    Duration::from_millis(ms.parse().map_err(|_| format!("Invalid time limit '{}'", ms))?);
+            }
+            "--max-nodes" => {
+                let limit = args.next().ok_or("--max-nodes expects a node limit")?;
+                ctx.limits.max_nodes = limit.parse().map_err(|_| format!("Invalid node limit '{}'", limit))?;
+            }
+            "--max-depth" => {
+                let limit = args.next().ok_or("--max-depth expects a depth limit")?;
+                ctx.limits.max_depth = limit.parse().map_err(|_| format!("Invalid depth limit '{}'", limit))?;
+            }
+            "--time-limit" => {
+                let ms = args.next().ok_or("--time-limit expects a time limit in milliseconds, or 0 for none")?;
+                ctx.limits.time_limit = match ms.parse().map_err(|_| format!("Invalid time limit '{}'", ms))? {
+                    0 => None,
+                    ms => Some(Duration::from_millis(ms)),
+                };
+            }
+            "--assume" => {
+                let spec = args.next().ok_or("--assume expects a fact such as x>0 or n:integer")?;
+                ctx.assumptions.assume(&spec)?;
+            }
+            "--at" => {
+                let point = args.next().ok_or("--at expects a value such as pi/6")?;
+                at = Some(parse_expr(&point)?);
+            }
+            "--csv" => csv = Some(args.next().ok_or("--csv expects a file path")?),
+            "--plot" => plot = Some(args.next().ok_or("--plot expects a file path such as out.svg")?),
+            other if other.starts_with("--") => return Err(format!("Unknown option: {}", other)),
+            _ => command.push(arg),
+        }
+    }
+    Ok(Options { ctx, at, order, cse, csv, plot, ascii, command })
+}
+
+/// Parses an expression given as a command-line argument.
+pub fn parse_expr(text: &str) -> Result<Expr, String> {
+    let tokens = tokenizer::tokenize(text)?;
+    parser::Parser::new(tokens).parse()
+}
+
+/// Parses a range such as `x=0..pi/2` into its variable and interval.
+fn parse_range(spec: &str) -> Result<(String, Interval), String> {
+    let invalid = || format!("Invalid range '{}', expected e.g. x=0..1", spec);
+    let (var, range) = spec.split_once('=').ok_or_else(invalid)?;
+    let (lo, hi) = range.split_once("..").ok_or_else(invalid)?;
+    let bound = |text: &str| eval_constant(&parse_expr(text)?).ok_or_else(invalid);
+    let (lo, hi) = (bound(lo)?, bound(hi)?);
+    if var.trim().is_empty() || lo > hi {
+        return Err(invalid());
+    }
+    Ok((var.trim().to_string(), Interval::new(lo, hi)))
+}
+
+/// Parses a variable's value such as `x=1+2i`.
+fn parse_value(spec: &str) -> Result<(String, Complex), String> {
+    let (var, value) = spec
+        .split_once('=')
+        .ok_or_else(|| format!("Invalid value '{}', expected e.g. x=1+2i", spec))?;
+    let value = complex::eval_complex(&parse_expr(value)?, &HashMap::new())?;
+    Ok((var.trim().to_string(), value))
+}
+
+/// Runs a command given on the command line and prints its result.
+pub fn run(command: &[String], options: &Options) -> Result<(), String> {
+    let (name, args) = command.split_first().ok_or("No command given")?;
+    let arg = |i: usize, what: &str| {
+        args.get(i)
+            .map(String::as_str)
+            .ok_or_else(|| format!("{} expects {}", name, what))
+    };
+    // The commands work in radians: expressions are converted on the way in,
+    // and those printed are converted back
+    let angle = options.ctx.angle;
+    let ctx = Context { angle: AngleMode::Radians, ..options.ctx.clone() };
+    let expr = |i: usize| Ok::<_, String>(angle.to_radians(&parse_expr(arg(i, "an expression")?)?));
+    let var = |i: usize| -> Result<Expr, String> { Ok(Expr::Var(arg(i, "a variable")?.to_string())) };
+    let simplify = |e: &Expr| simplifier::simplify_with(e, &ctx);
+    let shown = |e: &Expr| angle.from_radians(e);
+    match name.as_str() {
+        "expand" => println!("{}", shown(&simplify(&polynomial::expand(&expr(0)?))?)),
+        "factor" => println!("{}", shown(&polynomial::factor(&expr(0)?))),
+        "collect" => println!("{}", shown(&polynomial::collect(&expr(0)?, &var(1)?))),
+        "degree" => println!("{}", Poly::from_expr(&expr(0)?).degree(&var(1)?)),
+        "coeffs" => {
+            let var = var(1)?;
+            for (k, c) in Poly::from_expr(&expr(0)?).coefficients(&var) {
+                println!("{}^{}: {}", var, k, shown(&c.to_expr()));
+            }
+        }
+        "div" => {
+            let (q, r) = Poly::from_expr(&expr(0)?).div_rem(&Poly::from_expr(&expr(1)?), &var(2)?)?;
+            println!("quotient: {}", shown(&q.to_expr()));
+            println!("remainder: {}", shown(&r.to_expr()));
+        }
+        "gcd" => {
+            let gcd = Poly::from_expr(&expr(0)?).gcd_limited(&Poly::from_expr(&expr(1)?), &Budget::start(&ctx.limits))?;
+            println!("{}", shown(&gcd.to_expr()));
+        }
+        "together" => println!("{}", shown(&rational::together(&expr(0)?))),
+        "cancel" => println!("{}", shown(&rational::cancel(&expr(0)?))),
+        "apart" => println!("{}", shown(&rational::apart(&expr(0)?, &var(1)?)?)),
+        "check" => println!("{}", equivalence::equivalent_with(&expr(0)?, &expr(1)?, &ctx)),
+        "bound" => {
+            let f = expr(0)?;
+            arg(1, "a range such as x=0..1")?;
+            let ranges = args[1..].iter().map(|spec| parse_range(spec)).collect::<Result<Vec<_>, _>>()?;
+            let var = ranges[0].0.clone();
+            let domain = ranges.into_iter().collect();
+            println!("value: {}", interval::eval_interval(&f, &domain)?);
+            println!("derivative: {}", interval::bound_derivative(&f, &var, &domain, &ctx)?);
+        }
+        "table" => {
+            let f = expr(0)?;
+            let (var, range) = parse_range(arg(1, "a range such as x=0..1")?)?;
+            let steps = match args.get(2) {
+                Some(n) => n.parse().map_err(|_| format!("Invalid number of steps '{}'", n))?,
+                None => TABLE_STEPS,
+            };
+            let orders = match args.get(3) {
+                Some(list) => list
+                    .split(',')
+                    .map(|k| k.trim().parse().map_err(|_| format!("Invalid derivative order '{}'", k)))
+                    .collect::<Result<Vec<usize>, _>>()?,
+                None => vec![0, 1, 2],
+            };
+            let csv = table::tabulate(&f, &var, range.lo, range.hi, steps, &orders, &ctx)?.to_csv();
+            match &options.csv {
+                Some(path) => std::fs::write(path, csv).map_err(|e| format!("Cannot write {}: {}", path, e))?,
+                None => print!("{}", csv),
+            }
+        }
+        "plot" => {
+            let f = expr(0)?;
+            let (var, range) = parse_range(arg(1, "a range such as x=0..1")?)?;
+            // With --at, the tangent there is drawn too
+            let tangent = match &options.at {
+                Some(point) => {
+                    let x0 = eval_constant(point).ok_or_else(|| format!("Cannot plot a tangent at {}", point))?;
+                    plot::tangent(&f, &var, x0, range.lo, range.hi, &ctx)?
+                }
+                None => None,
+            };
+            if let Some(path) = &options.plot {
+                let mut series = plot::sample_adaptive(&f, &var, range.lo, range.hi, &ctx)?;
+                series.extend(tangent);
+                let svg = SvgPlot::default().render(&series)?;
+                std::fs::write(path, svg).map_err(|e| format!("Cannot write {}: {}", path, e))?;
+                return Ok(());
+            }
+            let mut terminal = TerminalPlot {
+                glyphs: if options.ascii { Glyphs::Ascii } else { Glyphs::Braille },
+                color: io::stdout().is_terminal() && std::env::var_os("NO_COLOR").is_none(),
+                ..TerminalPlot::default()
+            };
+            // Fit the terminal, leaving room for the axis labels
+            if let Some(columns) = std::env::var("COLUMNS").ok().and_then(|c| c.parse::<usize>().ok()) {
+                terminal.width = columns.saturating_sub(PLOT_MARGIN).max(10);
+            }
+            let dots = match terminal.glyphs {
+                Glyphs::Braille => 2 * terminal.width,
+                Glyphs::Ascii => terminal.width,
+            };
+            let mut series = plot::sample(&f, &var, range.lo, range.hi, dots, &ctx)?;
+            series.extend(tangent);
+            print!("{}", terminal.render(&series)?);
+        }
+        "tangent" => {
+            let f = expr(0)?;
+            let spec = arg(1, "a point such as x=pi/6")?;
+            let (var, x0) = spec
+                .split_once('=')
+                .ok_or_else(|| format!("Invalid point '{}', expected e.g. x=pi/6", spec))?;
+            let lines = tangent::tangent_lines(&f, var.trim(), &angle.to_radians(&parse_expr(x0)?), &ctx)?;
+            let (x, y) = lines.point;
+            println!("point: ({}, {})", x, y);
+            let t = &lines.tangent;
+            println!("tangent: y = {}", shown(&t.expr));
+            println!("  slope: {}, intercept: {}", t.slope, t.intercept);
+            match &lines.normal {
+                Some(n) => {
+                    println!("normal: y = {}", shown(&n.expr));
+                    println!("  slope: {}, intercept: {}", n.slope, n.intercept);
+                }
+                None => println!("normal: {} = {}", var.trim(), x),
+            }
+        }
+        "eval" => {
+            let f = expr(0)?;
+            let vars = args[1..].iter().map(|spec| parse_value(spec)).collect::<Result<_, _>>()?;
+            println!("{}", complex::eval_complex(&f, &vars)?);
+        }
+        other => return Err(format!("Unknown command: {}", other)),
+    }
+    Ok(())
+}
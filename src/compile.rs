@@ -0,0 +1,447 @@
+use std::collections::HashMap;
+
+use crate::ast::{Expr, Op};
+use crate::context::Context;
+use crate::dag::{Arena, ExprId, Node};
+use crate::derivative::derivative_in;
+use crate::eval::Function;
+use crate::limits::Budget;
+use crate::simplifier::simplify_in;
+
+/// How many points a batch evaluates at once. Each register holds a column of
+/// this many values, small enough for all of them to stay in cache.
+const CHUNK: usize = 256;
+
+/// Batches with fewer points than this per thread are not worth splitting up.
+const MIN_POINTS_PER_THREAD: usize = 16 * 1024;
+
+/// One step of a compiled program. Each instruction writes the register with
+/// its own index and reads only registers before it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Instr {
+    Const(f64),
+    /// The value of the variable with this index.
+    Var(usize),
+    Neg(usize),
+    Add(usize, usize),
+    Sub(usize, usize),
+    Mul(usize, usize),
+    Div(usize, usize),
+    Pow(usize, usize),
+    /// A power with a constant integer exponent, cheaper than `powf`.
+    Powi(usize, i32),
+    Call(Function, usize),
+}
+
+impl Instr {
+    /// The registers the instruction reads.
+    fn operands(self) -> [Option<usize>; 2] {
+        match self {
+            Instr::Const(_) | Instr::Var(_) => [None, None],
+            Instr::Neg(a) | Instr::Powi(a, _) | Instr::Call(_, a) => [Some(a), None],
+            Instr::Add(a, b) | Instr::Sub(a, b) | Instr::Mul(a, b) | Instr::Div(a, b) | Instr::Pow(a, b) => {
+                [Some(a), Some(b)]
+            }
+        }
+    }
+
+    /// Computes the instruction's value, reading registers with `reg`.
+    #[inline]
+    fn apply(self, values: &[f64], reg: impl Fn(usize) -> f64) -> f64 {
+        match self {
+            Instr::Const(c) => c,
+            Instr::Var(i) => values[i],
+            Instr::Neg(a) => -reg(a),
+            Instr::Add(a, b) => reg(a) + reg(b),
+            Instr::Sub(a, b) => reg(a) - reg(b),
+            Instr::Mul(a, b) => reg(a) * reg(b),
+            Instr::Div(a, b) => reg(a) / reg(b),
+            Instr::Pow(a, b) => reg(a).powf(reg(b)),
+            Instr::Powi(a, n) => reg(a).powi(n),
+            Instr::Call(f, a) => f.apply(reg(a)),
+        }
+    }
+}
+
+/// Expressions compiled to a straight-line register program, for evaluating
+/// them at many points, e.g. to plot a derivative or run Newton's method.
+///
+/// Function names are resolved when compiling, subexpressions without
+/// variables are folded to constants, and a subexpression that occurs more
+/// than once (also across the compiled expressions) is computed once.
+#[derive(Debug, Clone)]
+pub struct CompiledFn {
+    vars: Vec<String>,
+    code: Vec<Instr>,
+    /// The register holding the value of each compiled expression.
+    outputs: Vec<usize>,
+}
+
+impl CompiledFn {
+    /// Compiles `expr` as a function of `vars`, in that order. Fails if it
+    /// uses another variable or an unknown function.
+    pub fn compile(expr: &Expr, vars: &[&str]) -> Result<CompiledFn, String> {
+        CompiledFn::compile_all(std::slice::from_ref(expr), vars)
+    }
+
+    /// Compiles several expressions into one program with an output for each,
+    /// sharing the work they have in common.
+    pub fn compile_all(exprs: &[Expr], vars: &[&str]) -> Result<CompiledFn, String> {
+        let mut arena = Arena::new();
+        let ids: Vec<ExprId> = exprs.iter().map(|e| arena.from_expr(e)).collect();
+        let mut compiler = Compiler {
+            arena: &arena,
+            vars,
+            code: vec![],
+            registers: HashMap::new(),
+            constants: HashMap::new(),
+        };
+        let outputs = ids
+            .into_iter()
+            .map(|id| compiler.compile(id))
+            .collect::<Result<Vec<_>, _>>()?;
+        let (code, outputs) = remove_dead_code(compiler.code, outputs);
+        Ok(CompiledFn {
+            vars: vars.iter().map(|v| v.to_string()).collect(),
+            code,
+            outputs,
+        })
+    }
+
+    /// Compiles `expr` together with its simplified derivative with respect to
+    /// `var`, as outputs 0 and 1.
+    pub fn with_derivative(expr: &Expr, var: &str, vars: &[&str], ctx: &Context) -> Result<CompiledFn, String> {
+        CompiledFn::with_derivatives(expr, var, 1, vars, ctx)
+    }
+
+    /// Compiles `expr` together with its simplified derivatives with respect to
+    /// `var` up to `order`, output `k` being the `k`-th derivative. Angles are
+    /// taken in the unit of `ctx.angle`.
+    pub fn with_derivatives(
+        expr: &Expr,
+        var: &str,
+        order: usize,
+        vars: &[&str],
+        ctx: &Context,
+    ) -> Result<CompiledFn, String> {
+        // Each order differentiates the last, sharing the work as `--order` does
+        let budget = Budget::start(&ctx.limits);
+        let mut arena = Arena::new();
+        let mut id = arena.from_expr(&ctx.angle.to_radians(expr));
+        let mut exprs = vec![arena.to_expr_limited(id, &budget)?];
+        for _ in 0..order {
+            let derivative = derivative_in(&mut arena, id, var, ctx)?;
+            id = simplify_in(&mut arena, derivative, ctx)?;
+            exprs.push(arena.to_expr_limited(id, &budget)?);
+        }
+        CompiledFn::compile_all(&exprs, vars)
+    }
+
+    /// The variables, in the order their values are passed.
+    pub fn vars(&self) -> &[String] {
+        &self.vars
+    }
+
+    /// The number of instructions.
+    pub fn len(&self) -> usize {
+        self.code.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.code.is_empty()
+    }
+
+    /// The value of the first output, given one value per variable.
+    pub fn call(&self, values: &[f64]) -> f64 {
+        let mut registers = Vec::with_capacity(self.code.len());
+        self.run(values, &mut registers);
+        registers[self.outputs[0]]
+    }
+
+    /// The values of all outputs, given one value per variable.
+    pub fn call_all(&self, values: &[f64]) -> Vec<f64> {
+        let mut out = vec![0.0; self.outputs.len()];
+        self.call_with(values, &mut Vec::with_capacity(self.code.len()), &mut out);
+        out
+    }
+
+    /// Like [`call_all`](CompiledFn::call_all), but reuses `registers` and
+    /// writes into `out`, so that calling in a loop does not allocate.
+    pub fn call_with(&self, values: &[f64], registers: &mut Vec<f64>, out: &mut [f64]) {
+        self.run(values, registers);
+        for (slot, &output) in out.iter_mut().zip(&self.outputs) {
+            *slot = registers[output];
+        }
+    }
+
+    fn run(&self, values: &[f64], registers: &mut Vec<f64>) {
+        assert!(
+            values.len() >= self.vars.len(),
+            "expected {} variable values, got {}",
+            self.vars.len(),
+            values.len()
+        );
+        registers.clear();
+        for instr in &self.code {
+            let value = instr.apply(values, |r| registers[r]);
+            registers.push(value);
+        }
+    }
+
+    /// Evaluates every output at many points. `columns` holds one slice per
+    /// variable, the i-th point being made of the i-th value of each, and
+    /// `outputs` one buffer per output that receives the values at every point.
+    pub fn eval_batch(&self, columns: &[&[f64]], outputs: &mut [&mut [f64]]) -> Result<(), String> {
+        let points = self.check_batch(columns, outputs)?;
+        let mut registers = vec![0.0; self.code.len() * CHUNK];
+        for start in (0..points).step_by(CHUNK) {
+            let end = (start + CHUNK).min(points);
+            self.run_chunk(columns, start..end, &mut registers);
+            for (buffer, &output) in outputs.iter_mut().zip(&self.outputs) {
+                buffer[start..end].copy_from_slice(&registers[output * CHUNK..][..end - start]);
+            }
+        }
+        Ok(())
+    }
+
+    /// Like [`eval_batch`](CompiledFn::eval_batch), but splits large batches
+    /// into ranges of points evaluated on separate threads.
+    pub fn eval_batch_parallel(&self, columns: &[&[f64]], outputs: &mut [&mut [f64]]) -> Result<(), String> {
+        let points = self.check_batch(columns, outputs)?;
+        let threads = std::thread::available_parallelism()
+            .map_or(1, |n| n.get())
+            .min(points / MIN_POINTS_PER_THREAD);
+        if threads <= 1 {
+            return self.eval_batch(columns, outputs);
+        }
+        let per_thread = points.div_ceil(threads);
+        // Cut every output buffer at the same points, one piece per thread
+        let mut pieces: Vec<Vec<&mut [f64]>> = (0..threads).map(|_| vec![]).collect();
+        for buffer in outputs.iter_mut() {
+            for (piece, part) in pieces.iter_mut().zip(buffer.chunks_mut(per_thread)) {
+                piece.push(part);
+            }
+        }
+        std::thread::scope(|scope| {
+            let handles: Vec<_> = pieces
+                .into_iter()
+                .enumerate()
+                .map(|(i, mut piece)| {
+                    let range = i * per_thread..((i + 1) * per_thread).min(points);
+                    let columns: Vec<&[f64]> = columns.iter().map(|c| &c[range.clone()]).collect();
+                    scope.spawn(move || self.eval_batch(&columns, &mut piece))
+                })
+                .collect();
+            handles
+                .into_iter()
+                .try_for_each(|h| h.join().unwrap_or_else(|panic| std::panic::resume_unwind(panic)))
+        })
+    }
+
+    /// Checks that a batch has a column per variable and a buffer per output,
+    /// all of the same length, and returns that length.
+    fn check_batch(&self, columns: &[&[f64]], outputs: &[&mut [f64]]) -> Result<usize, String> {
+        if columns.len() < self.vars.len() {
+            return Err(format!("Expected {} columns of variable values, got {}", self.vars.len(), columns.len()));
+        }
+        if outputs.len() > self.outputs.len() {
+            return Err(format!("Expected at most {} output buffers, got {}", self.outputs.len(), outputs.len()));
+        }
+        let points = columns
+            .first()
+            .map(|c| c.len())
+            .or_else(|| outputs.first().map(|o| o.len()))
+            .unwrap_or(0);
+        let lengths = columns.iter().map(|c| c.len()).chain(outputs.iter().map(|o| o.len()));
+        if let Some(length) = lengths.into_iter().find(|&n| n != points) {
+            return Err(format!("Batch columns and buffers differ in length ({} and {})", points, length));
+        }
+        Ok(points)
+    }
+
+    /// Runs the program over one chunk of points, one instruction at a time
+    /// across the whole chunk. The loops over a chunk are simple enough for the
+    /// compiler to vectorize.
+    fn run_chunk(&self, columns: &[&[f64]], points: std::ops::Range<usize>, registers: &mut [f64]) {
+        let n = points.len();
+        for (i, instr) in self.code.iter().enumerate() {
+            let (before, rest) = registers.split_at_mut(i * CHUNK);
+            let out = &mut rest[..n];
+            let column = |r: usize| &before[r * CHUNK..][..n];
+            match *instr {
+                Instr::Const(c) => out.fill(c),
+                Instr::Var(v) => out.copy_from_slice(&columns[v][points.clone()]),
+                Instr::Neg(a) => map(out, column(a), |x| -x),
+                Instr::Add(a, b) => zip_map(out, column(a), column(b), |x, y| x + y),
+                Instr::Sub(a, b) => zip_map(out, column(a), column(b), |x, y| x - y),
+                Instr::Mul(a, b) => zip_map(out, column(a), column(b), |x, y| x * y),
+                Instr::Div(a, b) => zip_map(out, column(a), column(b), |x, y| x / y),
+                Instr::Pow(a, b) => zip_map(out, column(a), column(b), f64::powf),
+                Instr::Powi(a, k) => map(out, column(a), |x| x.powi(k)),
+                Instr::Call(f, a) => map(out, column(a), |x| f.apply(x)),
+            }
+        }
+    }
+}
+
+fn map(out: &mut [f64], a: &[f64], f: impl Fn(f64) -> f64) {
+    for (o, &x) in out.iter_mut().zip(a) {
+        *o = f(x);
+    }
+}
+
+fn zip_map(out: &mut [f64], a: &[f64], b: &[f64], f: impl Fn(f64, f64) -> f64) {
+    for ((o, &x), &y) in out.iter_mut().zip(a).zip(b) {
+        *o = f(x, y);
+    }
+}
+
+/// `n` evenly spaced points from `start` to `end`, both included.
+pub fn grid(start: f64, end: f64, n: usize) -> Vec<f64> {
+    match n {
+        0 => vec![],
+        1 => vec![start],
+        _ => (0..n)
+            .map(|i| start + (end - start) * i as f64 / (n - 1) as f64)
+            .collect(),
+    }
+}
+
+/// Drops the instructions no output depends on, such as the operands of
+/// folded constants, and renumbers the rest.
+fn remove_dead_code(code: Vec<Instr>, outputs: Vec<usize>) -> (Vec<Instr>, Vec<usize>) {
+    let mut live = vec![false; code.len()];
+    for &output in &outputs {
+        live[output] = true;
+    }
+    for (register, instr) in code.iter().enumerate().rev() {
+        if live[register] {
+            for operand in instr.operands().into_iter().flatten() {
+                live[operand] = true;
+            }
+        }
+    }
+    let mut renumbered = vec![0; code.len()];
+    let mut kept = vec![];
+    for (register, instr) in code.into_iter().enumerate() {
+        if !live[register] {
+            continue;
+        }
+        let r = |operand: usize| renumbered[operand];
+        kept.push(match instr {
+            Instr::Const(_) | Instr::Var(_) => instr,
+            Instr::Neg(a) => Instr::Neg(r(a)),
+            Instr::Add(a, b) => Instr::Add(r(a), r(b)),
+            Instr::Sub(a, b) => Instr::Sub(r(a), r(b)),
+            Instr::Mul(a, b) => Instr::Mul(r(a), r(b)),
+            Instr::Div(a, b) => Instr::Div(r(a), r(b)),
+            Instr::Pow(a, b) => Instr::Pow(r(a), r(b)),
+            Instr::Powi(a, n) => Instr::Powi(r(a), n),
+            Instr::Call(f, a) => Instr::Call(f, r(a)),
+        });
+        renumbered[register] = kept.len() - 1;
+    }
+    let outputs = outputs.into_iter().map(|o| renumbered[o]).collect();
+    (kept, outputs)
+}
+
+struct Compiler<'a> {
+    arena: &'a Arena,
+    vars: &'a [&'a str],
+    code: Vec<Instr>,
+    /// The register already computing each node.
+    registers: HashMap<ExprId, usize>,
+    /// The register holding each constant, by its bits.
+    constants: HashMap<u64, usize>,
+}
+
+impl Compiler<'_> {
+    fn compile(&mut self, id: ExprId) -> Result<usize, String> {
+        if let Some(&register) = self.registers.get(&id) {
+            return Ok(register);
+        }
+        let register = match self.arena.node(id) {
+            Node::Num(bits) => self.constant(f64::from_bits(*bits)),
+            Node::Var(v) => {
+                let index = self
+                    .vars
+                    .iter()
+                    .position(|name| name == v)
+                    .ok_or_else(|| format!("No value given for variable '{}'", v))?;
+                self.emit(Instr::Var(index))
+            }
+            Node::Neg(inner) => {
+                let inner = self.compile(*inner)?;
+                self.emit(Instr::Neg(inner))
+            }
+            Node::BinaryOp(op, left, right) => {
+                let left = self.compile(*left)?;
+                let right = self.compile(*right)?;
+                self.binary(op, left, right)
+            }
+            Node::Func(name, arg) => {
+                let function = Function::from_name(name).ok_or_else(|| format!("Unknown function: {}", name))?;
+                let arg = self.compile(*arg)?;
+                self.emit(Instr::Call(function, arg))
+            }
+            Node::Sum(operands) => self.fold(operands, Op::Add)?,
+            Node::Product(operands) => self.fold(operands, Op::Mul)?,
+        };
+        self.registers.insert(id, register);
+        Ok(register)
+    }
+
+    /// Compiles an n-ary sum or product as a chain of binary operations.
+    fn fold(&mut self, operands: &[ExprId], op: Op) -> Result<usize, String> {
+        let mut registers = operands.iter().map(|&o| self.compile(o)).collect::<Result<Vec<_>, _>>()?.into_iter();
+        let Some(first) = registers.next() else {
+            return Ok(self.constant(if op == Op::Add { 0.0 } else { 1.0 }));
+        };
+        Ok(registers.fold(first, |acc, r| self.binary(&op, acc, r)))
+    }
+
+    fn binary(&mut self, op: &Op, left: usize, right: usize) -> usize {
+        match op {
+            Op::Add => self.emit(Instr::Add(left, right)),
+            Op::Sub => self.emit(Instr::Sub(left, right)),
+            Op::Mul => self.emit(Instr::Mul(left, right)),
+            Op::Div => self.emit(Instr::Div(left, right)),
+            Op::Pow => match self.constant_value(right) {
+                Some(n) if n.fract() == 0.0 && n.abs() <= i32::MAX as f64 => self.emit(Instr::Powi(left, n as i32)),
+                _ => self.emit(Instr::Pow(left, right)),
+            },
+        }
+    }
+
+    fn constant(&mut self, value: f64) -> usize {
+        if let Some(&register) = self.constants.get(&value.to_bits()) {
+            return register;
+        }
+        self.code.push(Instr::Const(value));
+        let register = self.code.len() - 1;
+        self.constants.insert(value.to_bits(), register);
+        register
+    }
+
+    fn constant_value(&self, register: usize) -> Option<f64> {
+        match self.code[register] {
+            Instr::Const(c) => Some(c),
+            _ => None,
+        }
+    }
+
+    /// Appends an instruction, or just its value if every operand is constant.
+    fn emit(&mut self, instr: Instr) -> usize {
+        let constant_operands = instr
+            .operands()
+            .iter()
+            .flatten()
+            .all(|&r| self.constant_value(r).is_some());
+        if constant_operands && !matches!(instr, Instr::Var(_)) {
+            let value = instr.apply(&[], |r| self.constant_value(r).unwrap_or(f64::NAN));
+            return self.constant(value);
+        }
+        self.code.push(instr);
+        self.code.len() - 1
+    }
+}
@@ -0,0 +1,312 @@
+use std::collections::HashMap;
+use std::f64::consts::{FRAC_PI_2, PI};
+use std::fmt;
+use std::ops::{Add, Div, Mul, Neg, Sub};
+
+use crate::ast::{Expr, Op};
+use crate::constants::IMAGINARY_UNIT;
+use crate::eval::Function;
+
+/// A complex number `re + im * i`.
+///
+/// Functions take their principal values. On a branch cut the value is the
+/// one approached from above for cuts along the real axis, such as the
+/// negative reals for `log` and `sqrt`, and from the right for cuts along
+/// the imaginary axis. This matches C99 and Python's `cmath`, e.g.
+/// `log(-1) = pi * i` and `arcsin(2) = pi/2 + 1.3169578969248166 * i`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Complex {
+    pub re: f64,
+    pub im: f64,
+}
+
+impl fmt::Display for Complex {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // Written like the matching expression, e.g. `pi * i` for `log(-1)`
+        write!(f, "{}", crate::simplifier::normalize(&self.to_expr()))
+    }
+}
+
+impl Complex {
+    pub const I: Complex = Complex { re: 0.0, im: 1.0 };
+
+    pub fn new(re: f64, im: f64) -> Complex {
+        Complex { re, im }
+    }
+
+    pub fn real(re: f64) -> Complex {
+        Complex { re, im: 0.0 }
+    }
+
+    /// The expression `re + im * i`.
+    pub fn to_expr(self) -> Expr {
+        Expr::Sum(vec![
+            Expr::Num(self.re),
+            Expr::Product(vec![Expr::Num(self.im), Expr::Var(IMAGINARY_UNIT.to_string())]),
+        ])
+    }
+
+    pub fn is_nan(self) -> bool {
+        self.re.is_nan() || self.im.is_nan()
+    }
+
+    /// The modulus `|z|`.
+    pub fn abs(self) -> f64 {
+        self.re.hypot(self.im)
+    }
+
+    /// The argument, in `(-π, π]`.
+    pub fn arg(self) -> f64 {
+        self.im.atan2(self.re)
+    }
+
+    pub fn recip(self) -> Complex {
+        Complex::real(1.0) / self
+    }
+
+    pub fn exp(self) -> Complex {
+        let r = self.re.exp();
+        Complex::new(r * self.im.cos(), r * self.im.sin())
+    }
+
+    /// The principal logarithm, with its branch cut along the negative real axis.
+    pub fn ln(self) -> Complex {
+        Complex::new(self.abs().ln(), self.upper().arg())
+    }
+
+    /// The number with a zero imaginary part made positive, which puts a
+    /// point on a cut along the real axis on its upper side.
+    fn upper(self) -> Complex {
+        Complex::new(self.re, self.im + 0.0)
+    }
+
+    /// The principal square root, with nonnegative real part.
+    pub fn sqrt(self) -> Complex {
+        let z = self.upper();
+        if z.re == 0.0 && z.im == 0.0 {
+            return Complex::real(0.0);
+        }
+        let r = z.abs();
+        let re = ((r + z.re) / 2.0).sqrt();
+        let im = ((r - z.re) / 2.0).sqrt().copysign(z.im);
+        Complex::new(re, im)
+    }
+
+    /// The principal value of `self ^ exponent`. Integer powers are taken by
+    /// repeated multiplication, so that e.g. `i^2` is exactly -1.
+    pub fn pow(self, exponent: Complex) -> Complex {
+        if exponent.im == 0.0 && exponent.re.fract() == 0.0 && exponent.re.abs() <= 64.0 {
+            let n = exponent.re as i32;
+            let mut result = Complex::real(1.0);
+            for _ in 0..n.unsigned_abs() {
+                result = result * self;
+            }
+            return if n < 0 { result.recip() } else { result };
+        }
+        if self.re == 0.0 && self.im == 0.0 {
+            return if exponent.re > 0.0 { Complex::real(0.0) } else { Complex::new(f64::NAN, f64::NAN) };
+        }
+        (exponent * self.ln()).exp()
+    }
+
+    pub fn sin(self) -> Complex {
+        Complex::new(self.re.sin() * self.im.cosh(), self.re.cos() * self.im.sinh())
+    }
+
+    pub fn cos(self) -> Complex {
+        Complex::new(self.re.cos() * self.im.cosh(), -(self.re.sin() * self.im.sinh()))
+    }
+
+    pub fn tan(self) -> Complex {
+        // Written with doubled angles, which does not overflow for large imaginary parts
+        let (a, b) = (2.0 * self.re, 2.0 * self.im);
+        let d = a.cos() + b.cosh();
+        Complex::new(a.sin() / d, b.sinh() / d)
+    }
+
+    pub fn sinh(self) -> Complex {
+        Complex::new(self.re.sinh() * self.im.cos(), self.re.cosh() * self.im.sin())
+    }
+
+    pub fn cosh(self) -> Complex {
+        Complex::new(self.re.cosh() * self.im.cos(), self.re.sinh() * self.im.sin())
+    }
+
+    pub fn tanh(self) -> Complex {
+        let (a, b) = (2.0 * self.re, 2.0 * self.im);
+        let d = a.cosh() + b.cos();
+        Complex::new(a.sinh() / d, b.sin() / d)
+    }
+
+    /// `-i * ln(iz + sqrt(1 - z^2))`, with cuts along the real axis outside `[-1, 1]`.
+    pub fn asin(self) -> Complex {
+        if self.im == 0.0 && self.re.abs() > 1.0 {
+            return Complex::new(FRAC_PI_2.copysign(self.re), self.re.abs().acosh());
+        }
+        let one = Complex::real(1.0);
+        let root = ((one - self) * (one + self)).sqrt();
+        -Complex::I * (Complex::I * self + root).ln()
+    }
+
+    pub fn acos(self) -> Complex {
+        Complex::real(FRAC_PI_2) - self.asin()
+    }
+
+    /// `i/2 * (ln(1 - iz) - ln(1 + iz))`, with cuts along the imaginary axis outside `[-i, i]`.
+    pub fn atan(self) -> Complex {
+        if self.re == 0.0 && self.im.abs() > 1.0 {
+            return Complex::new(FRAC_PI_2, (1.0 / self.im).atanh());
+        }
+        let one = Complex::real(1.0);
+        let iz = Complex::I * self;
+        Complex::new(0.0, 0.5) * ((one - iz).ln() - (one + iz).ln())
+    }
+
+    pub fn asinh(self) -> Complex {
+        if self.re == 0.0 && self.im.abs() > 1.0 {
+            return Complex::new(self.im.abs().acosh(), FRAC_PI_2.copysign(self.im));
+        }
+        -Complex::I * (Complex::I * self).asin()
+    }
+
+    /// `ln(z + sqrt(z + 1) * sqrt(z - 1))`, with its cut along the real axis below 1.
+    pub fn acosh(self) -> Complex {
+        if self.im == 0.0 && self.re < -1.0 {
+            return Complex::new((-self.re).acosh(), PI);
+        }
+        if self.im == 0.0 && self.re < 1.0 {
+            return Complex::new(0.0, self.re.acos());
+        }
+        let one = Complex::real(1.0);
+        (self + (self + one).sqrt() * (self - one).sqrt()).ln()
+    }
+
+    pub fn atanh(self) -> Complex {
+        if self.im == 0.0 && self.re.abs() > 1.0 {
+            return Complex::new((1.0 / self.re).atanh(), FRAC_PI_2);
+        }
+        -Complex::I * (Complex::I * self).atan()
+    }
+}
+
+impl Neg for Complex {
+    type Output = Complex;
+
+    fn neg(self) -> Complex {
+        Complex::new(-self.re, -self.im)
+    }
+}
+
+impl Add for Complex {
+    type Output = Complex;
+
+    fn add(self, other: Complex) -> Complex {
+        Complex::new(self.re + other.re, self.im + other.im)
+    }
+}
+
+impl Sub for Complex {
+    type Output = Complex;
+
+    fn sub(self, other: Complex) -> Complex {
+        Complex::new(self.re - other.re, self.im - other.im)
+    }
+}
+
+impl Mul for Complex {
+    type Output = Complex;
+
+    fn mul(self, other: Complex) -> Complex {
+        Complex::new(
+            self.re * other.re - self.im * other.im,
+            self.re * other.im + self.im * other.re,
+        )
+    }
+}
+
+impl Div for Complex {
+    type Output = Complex;
+
+    fn div(self, other: Complex) -> Complex {
+        // Smith's method, which avoids overflow in |other|^2
+        if other.re.abs() >= other.im.abs() {
+            let r = other.im / other.re;
+            let d = other.re + other.im * r;
+            Complex::new((self.re + self.im * r) / d, (self.im - self.re * r) / d)
+        } else {
+            let r = other.re / other.im;
+            let d = other.re * r + other.im;
+            Complex::new((self.re * r + self.im) / d, (self.im * r - self.re) / d)
+        }
+    }
+}
+
+/// Evaluates a built-in function at a complex number, defining the reciprocal
+/// and inverse reciprocal functions as [`apply_function`](crate::eval::apply_function) does.
+pub fn apply(function: Function, z: Complex) -> Complex {
+    match function {
+        Function::Sin => z.sin(),
+        Function::Cos => z.cos(),
+        Function::Tan => z.tan(),
+        Function::Cot => z.tan().recip(),
+        Function::Sec => z.cos().recip(),
+        Function::Cosec => z.sin().recip(),
+        Function::Arcsin => z.asin(),
+        Function::Arccos => z.acos(),
+        Function::Arctan => z.atan(),
+        Function::Arccsc => z.recip().asin(),
+        Function::Arcsec => z.recip().acos(),
+        Function::Arccot => Complex::real(FRAC_PI_2) - z.atan(),
+        Function::Sinh => z.sinh(),
+        Function::Cosh => z.cosh(),
+        Function::Tanh => z.tanh(),
+        Function::Coth => z.tanh().recip(),
+        Function::Sech => z.cosh().recip(),
+        Function::Cosech => z.sinh().recip(),
+        Function::Arsinh => z.asinh(),
+        Function::Arcosh => z.acosh(),
+        Function::Artanh => z.atanh(),
+        Function::Arcosech => z.recip().asinh(),
+        Function::Arsech => z.recip().acosh(),
+        Function::Arcoth => z.recip().atanh(),
+        Function::Log => z.ln(),
+        Function::Exp => z.exp(),
+        Function::Sqrt => z.sqrt(),
+        Function::Abs => Complex::real(z.abs()),
+    }
+}
+
+/// Evaluates an expression over the complex numbers, looking variables up in
+/// `vars`. `i` is the imaginary unit.
+pub fn eval_complex(expr: &Expr, vars: &HashMap<String, Complex>) -> Result<Complex, String> {
+    match expr {
+        Expr::Num(n) => Ok(Complex::real(*n)),
+        Expr::Var(v) if v == IMAGINARY_UNIT => Ok(Complex::I),
+        Expr::Var(v) => vars
+            .get(v)
+            .copied()
+            .ok_or_else(|| format!("No value given for variable '{}'", v)),
+        Expr::Neg(inner) => Ok(-eval_complex(inner, vars)?),
+        Expr::BinaryOp { op, left, right } => {
+            let l = eval_complex(left, vars)?;
+            let r = eval_complex(right, vars)?;
+            Ok(match op {
+                Op::Add => l + r,
+                Op::Sub => l - r,
+                Op::Mul => l * r,
+                Op::Div => l / r,
+                Op::Pow => l.pow(r),
+            })
+        }
+        Expr::Func(name, arg) => {
+            let function = Function::from_name(name).ok_or_else(|| format!("Unknown function: {}", name))?;
+            Ok(apply(function, eval_complex(arg, vars)?))
+        }
+        Expr::Sum(terms) => terms
+            .iter()
+            .try_fold(Complex::real(0.0), |acc, t| Ok(acc + eval_complex(t, vars)?)),
+        Expr::Product(factors) => factors
+            .iter()
+            .try_fold(Complex::real(1.0), |acc, f| Ok(acc * eval_complex(f, vars)?)),
+    }
+}
@@ -39,6 +39,21 @@ impl Constants {
     }
 }
 
+impl Default for Constants {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The name of the imaginary unit. It has no real value, so it is not in
+/// [`Constants`] and stays a symbol, with `i^2 = -1` applied when simplifying.
+pub const IMAGINARY_UNIT: &str = "i";
+
+/// Whether `expr` is the imaginary unit.
+pub fn is_imaginary_unit(expr: &crate::ast::Expr) -> bool {
+    matches!(expr, crate::ast::Expr::Var(name) if name == IMAGINARY_UNIT)
+}
+
 lazy_static::lazy_static! {
     pub static ref CONSTANTS: Constants = Constants::new();
 }
@@ -53,12 +68,41 @@ pub fn is_constant(name: &str) -> bool {
     CONSTANTS.is_constant(name)
 }
 
+/// Names tried, in order, when printing a number as a constant, so that each
+/// value always prints under the same name.
+const DISPLAY_NAMES: [&str; 4] = ["pi", "e", "deg", "inf"];
+
 /// Convert a decimal value back to its constant name if it matches a known constant
 pub fn value_to_constant_name(value: f64) -> Option<String> {
-    for (name, const_value) in &CONSTANTS.values {
-        if (value - const_value).abs() < f64::EPSILON {
-            return Some(name.clone());
+    DISPLAY_NAMES
+        .iter()
+        .find(|name| CONSTANTS.get(name).is_some_and(|c| (value - c).abs() < f64::EPSILON))
+        .map(|name| name.to_string())
+}
+
+/// Writes a value that is a simple rational multiple of π, such as `pi / 4`
+/// or `-2 * pi / 3`, in that form.
+pub fn pi_multiple_name(value: f64) -> Option<String> {
+    if value == 0.0 || !value.is_finite() {
+        return None;
+    }
+    for den in 1..=12 {
+        let num = value / consts::PI * den as f64;
+        // Off by a few rounding errors at most, so that e.g. 1e-13 is not `0 * pi`
+        if num.abs() > 48.0 || num.round() == 0.0 || (num - num.round()).abs() > 16.0 * f64::EPSILON * num.abs() {
+            continue;
         }
+        let num = num.round() as i64;
+        let numerator = match num {
+            1 => "pi".to_string(),
+            -1 => "-pi".to_string(),
+            n => format!("{} * pi", n),
+        };
+        return Some(if den == 1 {
+            numerator
+        } else {
+            format!("{} / {}", numerator, den)
+        });
     }
     None
-} 
\ No newline at end of file
+}
@@ -0,0 +1,53 @@
+use crate::angle::AngleMode;
+use crate::assumptions::Assumptions;
+use crate::egraph::EGraphSettings;
+use crate::limits::Limits;
+use crate::logarithm::LogMode;
+use crate::rewrite::{RuleSet, BUILTIN_RULES};
+use crate::trig::TrigMode;
+
+/// Settings that control how expressions are differentiated and simplified.
+#[derive(Debug, Clone)]
+pub struct Context {
+    /// Rewrite rules applied once an expression is in canonical form.
+    pub rules: RuleSet,
+    /// How trigonometric and hyperbolic functions are rewritten.
+    pub trig: TrigMode,
+    /// Whether logarithms are expanded, contracted or left alone.
+    pub log: LogMode,
+    /// Whether function calls on numbers without an exact value, like `sqrt 2`,
+    /// are replaced by their floating-point value.
+    pub fold_floats: bool,
+    /// Whether products and integer powers of sums are multiplied out.
+    pub expand: bool,
+    /// Whether function calls and powers on numbers without a real value,
+    /// like `sqrt(-4)`, are replaced by their complex value, e.g. `2 * i`.
+    pub complex: bool,
+    /// Whether trigonometric functions work in radians or degrees. The
+    /// arena-based functions, such as `derivative_in`, always work in radians.
+    pub angle: AngleMode,
+    /// What is known about the symbols, e.g. that `x > 0`.
+    pub assumptions: Assumptions,
+    /// When set, the result is further simplified with the (slower) e-graph simplifier.
+    pub egraph: Option<EGraphSettings>,
+    /// Bounds on the size, depth and running time of each derivative or
+    /// simplification, and a token to cancel them.
+    pub limits: Limits,
+}
+
+impl Default for Context {
+    fn default() -> Self {
+        Context {
+            rules: BUILTIN_RULES.clone(),
+            trig: TrigMode::Off,
+            log: LogMode::Off,
+            fold_floats: false,
+            expand: false,
+            complex: false,
+            angle: AngleMode::Radians,
+            assumptions: Assumptions::default(),
+            egraph: None,
+            limits: Limits::default(),
+        }
+    }
+}
@@ -0,0 +1,187 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+
+use crate::ast::Expr;
+
+/// Subexpressions smaller than this are not worth naming, e.g. `-x`.
+const MIN_SIZE: usize = 3;
+
+/// An expression with its repeated subexpressions pulled out into `let`
+/// bindings. Each binding may refer to the ones before it, and `result` may
+/// refer to all of them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cse {
+    pub bindings: Vec<(String, Expr)>,
+    pub result: Expr,
+}
+
+impl fmt::Display for Cse {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (name, value) in &self.bindings {
+            writeln!(f, "{} = {}", name, value)?;
+        }
+        write!(f, "{}", self.result)
+    }
+}
+
+impl Cse {
+    /// Substitutes the bindings back in, giving an expression equal to the original.
+    pub fn inline(&self) -> Expr {
+        self.bindings
+            .iter()
+            .rev()
+            .fold(self.result.clone(), |expr, (name, value)| expr.substitute(name, value))
+    }
+}
+
+/// A reference to an expression that can key a hash map, comparing structurally.
+/// Numbers compare by their bits, as they hash, so that NaN equals itself and
+/// 0 and -0 stay apart.
+struct Key<'a>(&'a Expr);
+
+impl PartialEq for Key<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        same_expr(self.0, other.0)
+    }
+}
+
+impl Eq for Key<'_> {}
+
+impl Hash for Key<'_> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        hash_expr(self.0, state);
+    }
+}
+
+fn hash_expr<H: Hasher>(expr: &Expr, state: &mut H) {
+    std::mem::discriminant(expr).hash(state);
+    match expr {
+        Expr::Num(n) => n.to_bits().hash(state),
+        Expr::Var(v) => v.hash(state),
+        Expr::Neg(inner) => hash_expr(inner, state),
+        Expr::BinaryOp { op, left, right } => {
+            op.hash(state);
+            hash_expr(left, state);
+            hash_expr(right, state);
+        }
+        Expr::Func(name, arg) => {
+            name.hash(state);
+            hash_expr(arg, state);
+        }
+        Expr::Sum(operands) | Expr::Product(operands) => {
+            operands.len().hash(state);
+            for operand in operands {
+                hash_expr(operand, state);
+            }
+        }
+    }
+}
+
+fn same_expr(a: &Expr, b: &Expr) -> bool {
+    match (a, b) {
+        (Expr::Num(x), Expr::Num(y)) => x.to_bits() == y.to_bits(),
+        (Expr::Var(x), Expr::Var(y)) => x == y,
+        (Expr::Neg(x), Expr::Neg(y)) => same_expr(x, y),
+        (
+            Expr::BinaryOp { op, left, right },
+            Expr::BinaryOp { op: op2, left: left2, right: right2 },
+        ) => op == op2 && same_expr(left, left2) && same_expr(right, right2),
+        (Expr::Func(f, x), Expr::Func(g, y)) => f == g && same_expr(x, y),
+        (Expr::Sum(xs), Expr::Sum(ys)) | (Expr::Product(xs), Expr::Product(ys)) => {
+            xs.len() == ys.len() && xs.iter().zip(ys).all(|(x, y)| same_expr(x, y))
+        }
+        _ => false,
+    }
+}
+
+/// Eliminates common subexpressions: every subexpression that occurs more than
+/// once is computed once into a binding `t1`, `t2`, ... (skipping names already
+/// used as variables), and its occurrences are replaced by that name.
+pub fn cse(expr: &Expr) -> Cse {
+    let mut counts = HashMap::new();
+    count(expr, &mut counts);
+
+    let used = expr.variables();
+    let mut state = State {
+        counts,
+        names: HashMap::new(),
+        bindings: vec![],
+        used,
+    };
+    let result = state.rewrite(expr);
+    Cse {
+        bindings: state.bindings,
+        result,
+    }
+}
+
+/// Counts the occurrences of each subexpression. The inside of a repeat is not
+/// counted again, so that a subexpression only occurring within one repeated
+/// subexpression is not pulled out on its own.
+fn count<'a>(expr: &'a Expr, counts: &mut HashMap<Key<'a>, usize>) {
+    if expr.size() < MIN_SIZE {
+        return;
+    }
+    let seen = counts.entry(Key(expr)).or_insert(0);
+    *seen += 1;
+    if *seen > 1 {
+        return;
+    }
+    match expr {
+        Expr::Num(_) | Expr::Var(_) => {}
+        Expr::Neg(inner) | Expr::Func(_, inner) => count(inner, counts),
+        Expr::BinaryOp { left, right, .. } => {
+            count(left, counts);
+            count(right, counts);
+        }
+        Expr::Sum(operands) | Expr::Product(operands) => {
+            for operand in operands {
+                count(operand, counts);
+            }
+        }
+    }
+}
+
+struct State<'a> {
+    counts: HashMap<Key<'a>, usize>,
+    /// The binding name given to each repeated subexpression so far.
+    names: HashMap<Key<'a>, String>,
+    bindings: Vec<(String, Expr)>,
+    /// Variable names that bindings must not shadow.
+    used: Vec<String>,
+}
+
+impl<'a> State<'a> {
+    fn rewrite(&mut self, expr: &'a Expr) -> Expr {
+        if let Some(name) = self.names.get(&Key(expr)) {
+            return Expr::Var(name.clone());
+        }
+        let rewritten = match expr {
+            Expr::Num(_) | Expr::Var(_) => return expr.clone(),
+            Expr::Neg(inner) => Expr::Neg(Box::new(self.rewrite(inner))),
+            Expr::BinaryOp { op, left, right } => Expr::BinaryOp {
+                op: op.clone(),
+                left: Box::new(self.rewrite(left)),
+                right: Box::new(self.rewrite(right)),
+            },
+            Expr::Func(name, arg) => Expr::Func(name.clone(), Box::new(self.rewrite(arg))),
+            Expr::Sum(terms) => Expr::Sum(terms.iter().map(|t| self.rewrite(t)).collect()),
+            Expr::Product(factors) => Expr::Product(factors.iter().map(|f| self.rewrite(f)).collect()),
+        };
+        if self.counts.get(&Key(expr)).copied().unwrap_or(0) < 2 {
+            return rewritten;
+        }
+        let name = self.fresh_name();
+        self.names.insert(Key(expr), name.clone());
+        self.bindings.push((name.clone(), rewritten));
+        Expr::Var(name)
+    }
+
+    fn fresh_name(&self) -> String {
+        (1..)
+            .map(|i| format!("t{}", i))
+            .find(|name| !self.used.contains(name) && self.bindings.iter().all(|(b, _)| b != name))
+            .unwrap_or_default()
+    }
+}
@@ -0,0 +1,304 @@
+use std::collections::HashMap;
+
+use crate::ast::{Expr, Op};
+use crate::limits::Budget;
+
+/// The most nodes a subexpression cut off by [`Arena::to_expr_shallow`] may
+/// have and still be expanded in full.
+const SHALLOW_NODES: usize = 16;
+
+/// A handle to an expression stored in an [`Arena`]. Since every distinct
+/// expression is stored once, two handles from the same arena are equal exactly
+/// when their expressions are.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ExprId(u32);
+
+/// One node of an interned expression; children are handles rather than boxes,
+/// so a subexpression used in many places is shared instead of copied.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Node {
+    /// A number, stored as the bits of its `f64` value.
+    Num(u64),
+    Var(String),
+    Neg(ExprId),
+    BinaryOp(Op, ExprId, ExprId),
+    Func(String, ExprId),
+    Sum(Vec<ExprId>),
+    Product(Vec<ExprId>),
+}
+
+/// Storage for hash-consed expressions, shaped as a DAG: building a node that
+/// already exists returns the existing handle. The arena also remembers the
+/// results of differentiating and simplifying its nodes, which assumes the
+/// same [`Context`](crate::context::Context) is used with it throughout.
+#[derive(Debug, Default)]
+pub struct Arena {
+    nodes: Vec<Node>,
+    ids: HashMap<Node, ExprId>,
+    /// Memoized derivatives, by node and variable.
+    pub(crate) derivatives: HashMap<(ExprId, String), ExprId>,
+    /// Memoized simplified forms.
+    pub(crate) simplified: HashMap<ExprId, ExprId>,
+}
+
+impl Arena {
+    pub fn new() -> Self {
+        Arena::default()
+    }
+
+    /// The number of distinct expressions stored.
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// Returns the handle for `node`, storing it first if it is new.
+    pub fn intern(&mut self, node: Node) -> ExprId {
+        if let Some(&id) = self.ids.get(&node) {
+            return id;
+        }
+        let id = ExprId(self.nodes.len() as u32);
+        self.nodes.push(node.clone());
+        self.ids.insert(node, id);
+        id
+    }
+
+    pub fn node(&self, id: ExprId) -> &Node {
+        &self.nodes[id.0 as usize]
+    }
+
+    pub fn num(&mut self, n: f64) -> ExprId {
+        // Adding 0.0 turns -0.0 into 0.0, so both share one node
+        self.intern(Node::Num((n + 0.0).to_bits()))
+    }
+
+    pub fn var(&mut self, name: &str) -> ExprId {
+        self.intern(Node::Var(name.to_string()))
+    }
+
+    pub fn neg(&mut self, inner: ExprId) -> ExprId {
+        self.intern(Node::Neg(inner))
+    }
+
+    pub fn binary(&mut self, op: Op, left: ExprId, right: ExprId) -> ExprId {
+        self.intern(Node::BinaryOp(op, left, right))
+    }
+
+    pub fn func(&mut self, name: &str, arg: ExprId) -> ExprId {
+        self.intern(Node::Func(name.to_string(), arg))
+    }
+
+    pub fn sum(&mut self, terms: Vec<ExprId>) -> ExprId {
+        self.intern(Node::Sum(terms))
+    }
+
+    pub fn product(&mut self, factors: Vec<ExprId>) -> ExprId {
+        self.intern(Node::Product(factors))
+    }
+
+    /// The value of a number node.
+    pub fn as_num(&self, id: ExprId) -> Option<f64> {
+        match self.node(id) {
+            Node::Num(bits) => Some(f64::from_bits(*bits)),
+            _ => None,
+        }
+    }
+
+    /// The handles a node refers to, in order.
+    pub fn children(&self, id: ExprId) -> Vec<ExprId> {
+        match self.node(id) {
+            Node::Num(_) | Node::Var(_) => Vec::new(),
+            Node::Neg(inner) | Node::Func(_, inner) => vec![*inner],
+            Node::BinaryOp(_, left, right) => vec![*left, *right],
+            Node::Sum(operands) | Node::Product(operands) => operands.clone(),
+        }
+    }
+
+    /// Stores a node like `id`, but with the given children in place of its own.
+    pub fn with_children(&mut self, id: ExprId, children: Vec<ExprId>) -> ExprId {
+        let node = match self.node(id) {
+            Node::Num(_) | Node::Var(_) => return id,
+            Node::Neg(_) => Node::Neg(children[0]),
+            Node::Func(name, _) => Node::Func(name.clone(), children[0]),
+            Node::BinaryOp(op, _, _) => Node::BinaryOp(op.clone(), children[0], children[1]),
+            Node::Sum(_) => Node::Sum(children),
+            Node::Product(_) => Node::Product(children),
+        };
+        self.intern(node)
+    }
+
+    /// Stores an expression tree, sharing its repeated subtrees.
+    pub fn from_expr(&mut self, expr: &Expr) -> ExprId {
+        self.store_shallow(expr, &HashMap::new())
+    }
+
+    /// Like [`from_expr`](Arena::from_expr), but stores each variable named in
+    /// `holes` as the node it stands for, undoing [`to_expr_shallow`](Arena::to_expr_shallow).
+    pub(crate) fn store_shallow(&mut self, expr: &Expr, holes: &HashMap<String, ExprId>) -> ExprId {
+        // Children before parents, on an explicit stack so that deep trees cannot overflow the call stack
+        let mut pending = vec![(expr, false)];
+        let mut stored = vec![];
+        while let Some((expr, ready)) = pending.pop() {
+            let children = expr.children();
+            if !ready && !children.is_empty() {
+                pending.push((expr, true));
+                pending.extend(children.into_iter().rev().map(|child| (child, false)));
+                continue;
+            }
+            let ids = stored.split_off(stored.len() - children.len());
+            let id = match expr {
+                Expr::Num(n) => self.num(*n),
+                Expr::Var(v) => holes.get(v).copied().unwrap_or_else(|| self.var(v)),
+                Expr::Neg(_) => self.neg(ids[0]),
+                Expr::BinaryOp { op, .. } => self.binary(op.clone(), ids[0], ids[1]),
+                Expr::Func(name, _) => self.func(name, ids[0]),
+                Expr::Sum(_) => self.sum(ids),
+                Expr::Product(_) => self.product(ids),
+            };
+            stored.push(id);
+        }
+        stored.pop().expect("the root is stored last")
+    }
+
+    /// Expands the top `depth` levels of a stored expression into a tree.
+    /// Below them, a subexpression of more than [`SHALLOW_NODES`] nodes is
+    /// left as a variable named `#` and a number, recorded in `holes`, so the
+    /// tree stays small however large the expression is. No input can name a
+    /// variable `#...`, so the stand-ins never clash with real variables.
+    pub(crate) fn to_expr_shallow(&self, id: ExprId, depth: usize, holes: &mut HashMap<String, ExprId>) -> Expr {
+        if depth == 0 && self.size_above(id, SHALLOW_NODES) {
+            let name = format!("#{}", id.0);
+            holes.insert(name.clone(), id);
+            return Expr::Var(name);
+        }
+        let below = depth.saturating_sub(1);
+        let mut tree = |id: &ExprId| Box::new(self.to_expr_shallow(*id, below, holes));
+        match self.node(id) {
+            Node::Num(bits) => Expr::Num(f64::from_bits(*bits)),
+            Node::Var(v) => Expr::Var(v.clone()),
+            Node::Neg(inner) => Expr::Neg(tree(inner)),
+            Node::BinaryOp(op, left, right) => Expr::BinaryOp {
+                op: op.clone(),
+                left: tree(left),
+                right: tree(right),
+            },
+            Node::Func(name, arg) => Expr::Func(name.clone(), tree(arg)),
+            Node::Sum(terms) => Expr::Sum(terms.iter().map(|t| *tree(t)).collect()),
+            Node::Product(factors) => Expr::Product(factors.iter().map(|f| *tree(f)).collect()),
+        }
+    }
+
+    /// Whether the expanded tree of `id` has more than `limit` nodes, looking
+    /// at no more than `limit` of them.
+    fn size_above(&self, id: ExprId, limit: usize) -> bool {
+        fn count(arena: &Arena, id: ExprId, left: &mut usize) -> bool {
+            if *left == 0 {
+                return false;
+            }
+            *left -= 1;
+            arena.children(id).into_iter().all(|child| count(arena, child, left))
+        }
+        let mut left = limit;
+        !count(self, id, &mut left)
+    }
+
+    /// Expands a stored expression back into a tree.
+    pub fn to_expr(&self, id: ExprId) -> Expr {
+        let uses = self.count_uses(id);
+        self.to_expr_cached(id, &uses)
+    }
+
+    /// Counts how often each node below `id` is referred to, descending into
+    /// every node once.
+    fn count_uses(&self, id: ExprId) -> HashMap<ExprId, usize> {
+        let mut uses = HashMap::new();
+        let mut pending = vec![id];
+        while let Some(id) = pending.pop() {
+            let seen = uses.entry(id).or_insert(0);
+            *seen += 1;
+            if *seen == 1 {
+                pending.extend(self.children(id));
+            }
+        }
+        uses
+    }
+
+    /// Like [`to_expr`](Arena::to_expr), but fails instead of building a tree
+    /// with more nodes than the budget allows. A DAG with heavy sharing can
+    /// stand for a tree exponentially larger than itself.
+    pub fn to_expr_limited(&self, id: ExprId, budget: &Budget) -> Result<Expr, String> {
+        budget.check_size(self.tree_size(id))?;
+        Ok(self.to_expr(id))
+    }
+
+    /// The number of nodes in the expanded tree, as [`Expr::size`] counts them.
+    fn tree_size(&self, id: ExprId) -> usize {
+        let mut sizes: HashMap<ExprId, usize> = HashMap::new();
+        let mut pending = vec![id];
+        while let Some(&top) = pending.last() {
+            if sizes.contains_key(&top) {
+                pending.pop();
+                continue;
+            }
+            let children = self.children(top);
+            let waiting: Vec<ExprId> = children.iter().filter(|c| !sizes.contains_key(c)).copied().collect();
+            if !waiting.is_empty() {
+                pending.extend(waiting);
+                continue;
+            }
+            pending.pop();
+            let operators = match self.node(top) {
+                Node::Sum(operands) | Node::Product(operands) => operands.len().saturating_sub(1),
+                _ => 1,
+            };
+            let size = children.iter().fold(operators, |acc, child| acc.saturating_add(sizes[child]));
+            sizes.insert(top, size);
+        }
+        sizes[&id]
+    }
+
+    /// Builds the tree for `id`, keeping the trees of shared nodes to copy
+    /// rather than rebuild. Unshared nodes are not kept, as copying every
+    /// subtree into the cache would take time quadratic in the depth.
+    fn to_expr_cached(&self, id: ExprId, uses: &HashMap<ExprId, usize>) -> Expr {
+        let mut cache: HashMap<ExprId, Expr> = HashMap::new();
+        // Children before parents, on an explicit stack as in `store_shallow`
+        let mut pending = vec![(id, false)];
+        let mut built = vec![];
+        while let Some((id, ready)) = pending.pop() {
+            if let Some(expr) = cache.get(&id) {
+                built.push(expr.clone());
+                continue;
+            }
+            let children = self.children(id);
+            if !ready && !children.is_empty() {
+                pending.push((id, true));
+                pending.extend(children.iter().rev().map(|&child| (child, false)));
+                continue;
+            }
+            let mut trees = built.split_off(built.len() - children.len());
+            let mut tree = |i: usize| Box::new(std::mem::replace(&mut trees[i], Expr::Num(0.0)));
+            let expr = match self.node(id) {
+                Node::Num(bits) => Expr::Num(f64::from_bits(*bits)),
+                Node::Var(v) => Expr::Var(v.clone()),
+                Node::Neg(_) => Expr::Neg(tree(0)),
+                Node::BinaryOp(op, _, _) => Expr::BinaryOp {
+                    op: op.clone(),
+                    left: tree(0),
+                    right: tree(1),
+                },
+                Node::Func(name, _) => Expr::Func(name.clone(), tree(0)),
+                Node::Sum(_) => Expr::Sum(trees),
+                Node::Product(_) => Expr::Product(trees),
+            };
+            if uses.get(&id).copied().unwrap_or(0) > 1 {
+                cache.insert(id, expr.clone());
+            }
+            built.push(expr);
+        }
+        built.pop().expect("the root is built last")
+    }
+}
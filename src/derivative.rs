@@ -1,60 +1,116 @@
+use std::collections::HashMap;
+
 use crate::ast::{Expr, Op};
-use Expr::*;
 use Op::*;
 
+use crate::constants::IMAGINARY_UNIT;
+use crate::context::Context;
+use crate::dag::{Arena, ExprId, Node};
 use crate::function_table::conversion; // function_table contains derivative formulas for built-in funcs like sin, cos, etc.
+use crate::limits::{on_large_stack, Budget};
 
 
 /// Computes the derivative of an expression with respect to the given variable.
 ///
 /// This is the main entry point for differentiation.
 /// It dispatches to different rules depending on the Expr variant.
-pub fn derivative(expr: &Expr, var: &str) -> Expr {
-    match expr {
-        Num(_) => constant_rule(), // derivative of a constant is zero
-        Var(v) => variable_rule(v, var), // derivative of variable: 1 if it matches, else 0
-        BinaryOp { op, left, right } => match op {
-            Add => add_rule(left, right, var), // sum rule
-            Sub => sub_rule(left, right, var), // difference rule
-            Mul => product_rule(left, right, var), // product rule
-            Div => quotient_rule(left, right, var), // quotient rule
-            Pow => pow_rule(left, right, var), // power rule with chain rule
-        },
-        Func(name, arg) => func_rule(name, arg, var), // chain rule for functions like sin, cos
+pub fn derivative(expr: &Expr, var: &str) -> Result<Expr, String> {
+    derivative_with(expr, var, &Context::default())
+}
+
+/// Like [`derivative`], but lets the rules for functions such as `abs` and
+/// `arcsec` use the assumptions in `ctx` to pick simpler forms. Fails with an
+/// "expression too large" error when the limits in `ctx` are exceeded.
+pub fn derivative_with(expr: &Expr, var: &str, ctx: &Context) -> Result<Expr, String> {
+    Budget::start(&ctx.limits).check_depth(expr)?;
+    on_large_stack(|| {
+        let mut arena = Arena::new();
+        let id = arena.from_expr(&ctx.angle.to_radians(expr));
+        let d = derivative_in(&mut arena, id, var, ctx)?;
+        Ok(ctx.angle.from_radians(&arena.to_expr_limited(d, &Budget::start(&ctx.limits))?))
+    })
+}
+
+/// Differentiates an expression stored in `arena`. Subexpressions are shared
+/// rather than copied, and the derivative of every node is remembered, so a
+/// subexpression occurring many times (as in high-order derivatives of
+/// products) is only differentiated once. Angles are in radians, whatever
+/// the angle mode in `ctx`.
+pub fn derivative_in(arena: &mut Arena, id: ExprId, var: &str, ctx: &Context) -> Result<ExprId, String> {
+    if var == IMAGINARY_UNIT {
+        return Err(format!("Cannot differentiate with respect to '{}', the imaginary unit", var));
     }
+    // The rules recurse once per level of nesting
+    on_large_stack(|| differentiate(arena, id, var, ctx, &Budget::start(&ctx.limits)))
+}
+
+fn differentiate(arena: &mut Arena, id: ExprId, var: &str, ctx: &Context, budget: &Budget) -> Result<ExprId, String> {
+    let key = (id, var.to_string());
+    if let Some(&d) = arena.derivatives.get(&key) {
+        return Ok(d);
+    }
+    budget.check_size(arena.len())?;
+    let d = match arena.node(id).clone() {
+        Node::Num(_) => constant_rule(arena), // derivative of a constant is zero
+        Node::Var(v) => variable_rule(arena, &v, var), // derivative of variable: 1 if it matches, else 0
+        Node::Neg(inner) => neg_rule(arena, inner, var, ctx, budget)?, // derivative of -f is -f'
+        Node::BinaryOp(op, left, right) => match op {
+            Add => add_rule(arena, left, right, var, ctx, budget)?, // sum rule
+            Sub => sub_rule(arena, left, right, var, ctx, budget)?, // difference rule
+            Mul => product_rule(arena, left, right, var, ctx, budget)?, // product rule
+            Div => quotient_rule(arena, left, right, var, ctx, budget)?, // quotient rule
+            Pow => pow_rule(arena, left, right, var, ctx, budget)?, // power rule with chain rule
+        },
+        Node::Func(name, arg) => func_rule(arena, &name, arg, var, ctx, budget)?, // chain rule for functions like sin, cos
+        Node::Sum(terms) => sum_rule(arena, &terms, var, ctx, budget)?, // n-ary sum rule
+        Node::Product(factors) => n_product_rule(arena, &factors, var, ctx, budget)?, // n-ary product rule
+    };
+    arena.derivatives.insert(key, d);
+    Ok(d)
 }
 
 /// Derivative of a constant is zero.
-fn constant_rule() -> Expr {
-    Num(0.0)
+fn constant_rule(arena: &mut Arena) -> ExprId {
+    arena.num(0.0)
 }
 
 /// Derivative of a variable:
 /// returns 1 if the variable matches the differentiation variable, else 0.
-fn variable_rule(v: &str, var: &str) -> Expr {
+fn variable_rule(arena: &mut Arena, v: &str, var: &str) -> ExprId {
     if v == var {
-        Num(1.0)
+        arena.num(1.0)
     } else {
-        Num(0.0)
+        arena.num(0.0)
     }
 }
 
+/// Negation rule: derivative of -f is -f'
+fn neg_rule(arena: &mut Arena, inner: ExprId, var: &str, ctx: &Context, budget: &Budget) -> Result<ExprId, String> {
+    let d = differentiate(arena, inner, var, ctx, budget)?;
+    Ok(arena.neg(d))
+}
+
 /// Sum rule: derivative of f + g is f' + g'
-fn add_rule(left: &Expr, right: &Expr, var: &str) -> Expr {
-    BinaryOp {
-        op: Add,
-        left: Box::new(derivative(left, var)),
-        right: Box::new(derivative(right, var)),
-    }
+fn add_rule(arena: &mut Arena, left: ExprId, right: ExprId, var: &str, ctx: &Context, budget: &Budget) -> Result<ExprId, String> {
+    let d_left = differentiate(arena, left, var, ctx, budget)?;
+    let d_right = differentiate(arena, right, var, ctx, budget)?;
+    Ok(arena.binary(Add, d_left, d_right))
 }
 
 /// Difference rule: derivative of f - g is f' - g'
-fn sub_rule(left: &Expr, right: &Expr, var: &str) -> Expr {
-    BinaryOp {
-        op: Sub,
-        left: Box::new(derivative(left, var)),
-        right: Box::new(derivative(right, var)),
-    }
+fn sub_rule(arena: &mut Arena, left: ExprId, right: ExprId, var: &str, ctx: &Context, budget: &Budget) -> Result<ExprId, String> {
+    let d_left = differentiate(arena, left, var, ctx, budget)?;
+    let d_right = differentiate(arena, right, var, ctx, budget)?;
+    Ok(arena.binary(Sub, d_left, d_right))
+}
+
+/// N-ary sum rule: derivative of f1 + f2 + ... + fn is f1' + f2' + ... + fn'
+fn sum_rule(arena: &mut Arena, terms: &[ExprId], var: &str, ctx: &Context, budget: &Budget) -> Result<ExprId, String> {
+    let terms = terms
+        .iter()
+        .map(|&t| differentiate(arena, t, var, ctx, budget))
+        .collect::<Result<_, _>>()?;
+    Ok(arena.sum(terms))
 }
 
 /// Power rule with chain rule:
@@ -63,158 +119,118 @@ fn sub_rule(left: &Expr, right: &Expr, var: &str) -> Expr {
 ///
 /// For d/dx [f(x)^n], uses chain rule:
 /// n * f(x)^(n-1) * f'(x)
-fn pow_rule(left: &Expr, right: &Expr, var: &str) -> Expr {
-    match right {
-        Expr::Num(n) => match left {
-            Expr::Var(v) if v == var => {
+fn pow_rule(arena: &mut Arena, left: ExprId, right: ExprId, var: &str, ctx: &Context, budget: &Budget) -> Result<ExprId, String> {
+    let d = match arena.as_num(right) {
+        Some(n) => {
+            let coefficient = arena.num(n);
+            let exponent = arena.num(n - 1.0);
+            let power = arena.binary(Pow, left, exponent);
+            match arena.node(left) {
                 // Simple power rule: x^n
-                Expr::BinaryOp {
-                    op: Op::Mul,
-                    left: Box::new(Expr::Num(*n)),
-                    right: Box::new(Expr::BinaryOp {
-                        op: Op::Pow,
-                        left: Box::new(Expr::Var(v.clone())),
-                        right: Box::new(Expr::Num(n - 1.0)),
-                    }),
+                Node::Var(v) if v == var => arena.binary(Mul, coefficient, power),
+                _ => {
+                    // General power rule: f(x)^n with constant n
+                    let d_left = differentiate(arena, left, var, ctx, budget)?;
+                    let chain = arena.binary(Mul, power, d_left);
+                    arena.binary(Mul, coefficient, chain)
                 }
             }
-            _ => {
-                // General power rule: f(x)^n with constant n
-                let d_left = derivative(left, var);
-                Expr::BinaryOp {
-                    op: Op::Mul,
-                    left: Box::new(Expr::Num(*n)),
-                    right: Box::new(Expr::BinaryOp {
-                        op: Op::Mul,
-                        left: Box::new(Expr::BinaryOp {
-                            op: Op::Pow,
-                            left: Box::new(left.clone()),
-                            right: Box::new(Expr::Num(n - 1.0)),
-                        }),
-                        right: Box::new(d_left),
-                    }),
-                }
-            }
-        },
+        }
         // New case: exponent is an expression, not constant
-        _ => {
+        None => {
             // Apply generalized power rule:
             // d/dx f(x)^g(x) = f(x)^g(x) * [g'(x) * ln(f(x)) + g(x) * f'(x) / f(x)]
 
-            let f = left.clone();
-            let g = right.clone();
+            let (f, g) = (left, right);
 
-            let df = derivative(left, var);
-            let dg = derivative(right, var);
+            let df = differentiate(arena, f, var, ctx, budget)?;
+            let dg = differentiate(arena, g, var, ctx, budget)?;
 
-            let ln_f = Expr::Func("ln".to_string(), Box::new(f.clone()));
+            // `log` is the natural logarithm
+            let ln_f = arena.func("log", f);
 
             // g'(x) * ln(f(x))
-            let term1 = Expr::BinaryOp {
-                op: Op::Mul,
-                left: Box::new(dg),
-                right: Box::new(ln_f),
-            };
+            let term1 = arena.binary(Mul, dg, ln_f);
 
             // f'(x) / f(x)
-            let term2 = Expr::BinaryOp {
-                op: Op::Div,
-                left: Box::new(df),
-                right: Box::new(f.clone()),
-            };
+            let term2 = arena.binary(Div, df, f);
 
             // g(x) * (f'(x) / f(x))
-            let term3 = Expr::BinaryOp {
-                op: Op::Mul,
-                left: Box::new(g.clone()),
-                right: Box::new(term2),
-            };
+            let term3 = arena.binary(Mul, g, term2);
 
             // Sum inside brackets: g'(x)*ln(f(x)) + g(x)*f'(x)/f(x)
-            let sum = Expr::BinaryOp {
-                op: Op::Add,
-                left: Box::new(term1),
-                right: Box::new(term3),
-            };
+            let sum = arena.binary(Add, term1, term3);
 
             // f(x)^g(x) * sum
-            Expr::BinaryOp {
-                op: Op::Mul,
-                left: Box::new(Expr::BinaryOp {
-                    op: Op::Pow,
-                    left: Box::new(f),
-                    right: Box::new(g),
-                }),
-                right: Box::new(sum),
-            }
+            let power = arena.binary(Pow, f, g);
+            arena.binary(Mul, power, sum)
         }
-    }
+    };
+    Ok(d)
 }
 
 
 /// Product rule: d/dx [u * v] = u' * v + u * v'
-fn product_rule(left: &Expr, right: &Expr, var: &str) -> Expr {
-    match (left, right) {
-        (Expr::Num(c), f) | (f, Expr::Num(c)) => {
+fn product_rule(arena: &mut Arena, left: ExprId, right: ExprId, var: &str, ctx: &Context, budget: &Budget) -> Result<ExprId, String> {
+    let constant = match (arena.as_num(left), arena.as_num(right)) {
+        (Some(_), _) => Some((left, right)),
+        (_, Some(_)) => Some((right, left)),
+        _ => None,
+    };
+    let d = match constant {
+        Some((c, f)) => {
             // Constant multiple rule
-            Expr::BinaryOp {
-                op: Op::Mul,
-                left: Box::new(Expr::Num(*c)),
-                right: Box::new(derivative(f, var)),
-            }
+            let d = differentiate(arena, f, var, ctx, budget)?;
+            arena.binary(Mul, c, d)
         }
-        _ => {
+        None => {
             // General product rule
-            let u_prime = derivative(left, var);
-            let v_prime = derivative(right, var);
-
-            Expr::BinaryOp {
-                op: Op::Add,
-                left: Box::new(Expr::BinaryOp {
-                    op: Op::Mul,
-                    left: Box::new(u_prime),
-                    right: Box::new(right.clone()),
-                }),
-                right: Box::new(Expr::BinaryOp {
-                    op: Op::Mul,
-                    left: Box::new(left.clone()),
-                    right: Box::new(v_prime),
-                }),
-            }
+            let u_prime = differentiate(arena, left, var, ctx, budget)?;
+            let v_prime = differentiate(arena, right, var, ctx, budget)?;
+
+            let first = arena.binary(Mul, u_prime, right);
+            let second = arena.binary(Mul, left, v_prime);
+            arena.binary(Add, first, second)
         }
+    };
+    Ok(d)
+}
+
+/// N-ary product rule: the derivative of f1 * f2 * ... * fn is the sum, over
+/// every factor, of the product with that factor replaced by its derivative.
+/// Constant factors contribute no term.
+fn n_product_rule(arena: &mut Arena, factors: &[ExprId], var: &str, ctx: &Context, budget: &Budget) -> Result<ExprId, String> {
+    let mut terms = vec![];
+    for (i, &f) in factors.iter().enumerate() {
+        if arena.as_num(f).is_some() {
+            continue;
+        }
+        // Every term repeats all the factors, so the result grows quadratically
+        budget.check_size(factors.len().saturating_mul(terms.len() + 1))?;
+        let mut term = factors.to_vec();
+        term[i] = differentiate(arena, f, var, ctx, budget)?;
+        terms.push(arena.product(term));
+    }
+    if terms.is_empty() {
+        Ok(arena.num(0.0))
+    } else {
+        Ok(arena.sum(terms))
     }
 }
 
 /// Quotient rule: d/dx [u / v] = (u' * v - u * v') / v^2
-fn quotient_rule(left: &Expr, right: &Expr, var: &str) -> Expr {
-    let u_prime = derivative(left, var);
-    let v_prime = derivative(right, var);
-
-    let numerator = BinaryOp {
-        op: Sub,
-        left: Box::new(BinaryOp {
-            op: Mul,
-            left: Box::new(u_prime),
-            right: Box::new(right.clone()),
-        }),
-        right: Box::new(BinaryOp {
-            op: Mul,
-            left: Box::new(left.clone()),
-            right: Box::new(v_prime),
-        }),
-    };
+fn quotient_rule(arena: &mut Arena, left: ExprId, right: ExprId, var: &str, ctx: &Context, budget: &Budget) -> Result<ExprId, String> {
+    let u_prime = differentiate(arena, left, var, ctx, budget)?;
+    let v_prime = differentiate(arena, right, var, ctx, budget)?;
 
-    let denominator = BinaryOp {
-        op: Pow,
-        left: Box::new(right.clone()),
-        right: Box::new(Num(2.0)),
-    };
+    let first = arena.binary(Mul, u_prime, right);
+    let second = arena.binary(Mul, left, v_prime);
+    let numerator = arena.binary(Sub, first, second);
 
-    BinaryOp {
-        op: Div,
-        left: Box::new(numerator),
-        right: Box::new(denominator),
-    }
+    let two = arena.num(2.0);
+    let denominator = arena.binary(Pow, right, two);
+
+    Ok(arena.binary(Div, numerator, denominator))
 }
 
 /// Chain rule for functions:
@@ -223,22 +239,16 @@ fn quotient_rule(left: &Expr, right: &Expr, var: &str) -> Expr {
 ///
 /// `conversion` is a helper function that returns the derivative expression
 /// of the outer function f evaluated at g(x).
-fn func_rule(name: &str, arg: &Expr, var: &str) -> Expr {
-    // Derivative of the outer function f evaluated at g(x)
-    let outer_derivative = conversion(name, arg.clone())
-        .unwrap_or_else(|e| panic!("{}", e));
+fn func_rule(arena: &mut Arena, name: &str, arg: ExprId, var: &str, ctx: &Context, budget: &Budget) -> Result<ExprId, String> {
+    // Derivative of the outer function f evaluated at g(x). Only the top of g
+    // is expanded, for the assumptions to look at; the rest stays shared
+    let mut holes = HashMap::new();
+    let outer_derivative = conversion(name, arena.to_expr_shallow(arg, 4, &mut holes), &ctx.assumptions)?;
+    let outer_derivative = arena.store_shallow(&outer_derivative, &holes);
 
     // Derivative of the inner function g(x)
-    let inner_derivative = derivative(arg, var);
+    let inner_derivative = differentiate(arena, arg, var, ctx, budget)?;
 
     // Chain rule: multiply outer derivative by inner derivative
-    BinaryOp {
-        op: Mul,
-        left: Box::new(outer_derivative),
-        right: Box::new(inner_derivative),
-    }
+    Ok(arena.binary(Mul, outer_derivative, inner_derivative))
 }
-
-
-
-
@@ -0,0 +1,594 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::time::{Duration, Instant};
+
+use crate::assumptions::Assumptions;
+use crate::ast::{Expr, Op};
+use crate::context::Context;
+use crate::limits::{on_large_stack, Budget};
+use crate::rewrite::{Bindings, Rule, RuleSet};
+use crate::simplifier::normalize;
+use crate::trig::TrigMode;
+
+/// Rules that would undo each other in the rewriting simplifier, but are safe
+/// here because the e-graph keeps both sides and only picks one at the end.
+const ALGEBRA: &str = "
+# Distributing and factoring
+a*(b + c) -> a*b + a*c
+a*b + a*c -> a*(b + c)
+a*b + a -> a*(b + 1)
+";
+
+lazy_static::lazy_static! {
+    static ref ALGEBRA_RULES: RuleSet = RuleSet::parse(ALGEBRA).expect("algebra rules must parse");
+}
+
+/// The most bindings a single pattern may produce against one node. Sums and
+/// products match in any order, so this keeps large operand lists in check.
+const MAX_MATCHES_PER_NODE: usize = 64;
+
+/// How the size of an expression is measured when picking the cheapest one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CostFn {
+    /// Every number, variable, function call and operator counts once.
+    #[default]
+    NodeCount,
+    /// Only function calls and operators count; numbers and variables are free.
+    OpCount,
+}
+
+impl CostFn {
+    /// Parses a cost function name as accepted by the `--egraph` option.
+    pub fn parse(name: &str) -> Result<Self, String> {
+        match name {
+            "nodes" => Ok(CostFn::NodeCount),
+            "ops" => Ok(CostFn::OpCount),
+            _ => Err(format!("Unknown cost function '{}' (expected nodes or ops)", name)),
+        }
+    }
+
+    fn leaf_cost(self) -> usize {
+        match self {
+            CostFn::NodeCount => 1,
+            CostFn::OpCount => 0,
+        }
+    }
+}
+
+/// Settings for the e-graph simplifier.
+#[derive(Debug, Clone)]
+pub struct EGraphSettings {
+    /// How the cheapest equivalent expression is chosen.
+    pub cost: CostFn,
+    /// Stop growing the e-graph once it holds this many nodes.
+    pub node_limit: usize,
+    /// Stop growing the e-graph after this much time, or sooner when the
+    /// context's own time limit runs out.
+    pub time_limit: Duration,
+    /// Stop after this many rounds of rewriting.
+    pub iter_limit: usize,
+}
+
+impl Default for EGraphSettings {
+    fn default() -> Self {
+        EGraphSettings {
+            cost: CostFn::NodeCount,
+            node_limit: 10_000,
+            time_limit: Duration::from_millis(500),
+            iter_limit: 20,
+        }
+    }
+}
+
+/// Index of an equivalence class in the e-graph.
+type Id = usize;
+
+/// One way of computing the value of an equivalence class, with the operands
+/// being classes rather than expressions. Only the shapes that canonical
+/// expressions use are needed.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Node {
+    Num(u64),
+    Var(String),
+    Func(String, Id),
+    /// Base and exponent.
+    Pow([Id; 2]),
+    Sum(Vec<Id>),
+    Product(Vec<Id>),
+}
+
+impl Node {
+    fn num(n: f64) -> Node {
+        // Adding 0.0 turns -0.0 into 0.0, so both land in the same class
+        Node::Num((n + 0.0).to_bits())
+    }
+
+    fn children(&self) -> &[Id] {
+        match self {
+            Node::Num(_) | Node::Var(_) => &[],
+            Node::Func(_, arg) => std::slice::from_ref(arg),
+            Node::Pow(pair) => pair,
+            Node::Sum(ids) | Node::Product(ids) => ids,
+        }
+    }
+}
+
+/// Pattern variable bindings while matching against the e-graph. A variable
+/// that captures the leftover operands of a sum or product is bound to all of them.
+#[derive(Debug, Clone)]
+enum Bound {
+    Class(Id),
+    Rest { sum: bool, ids: Vec<Id> },
+}
+
+type Matches = HashMap<String, Bound>;
+
+/// The cheapest known node of every class, together with its cost.
+type Best = HashMap<Id, (usize, Node)>;
+
+/// An e-graph: a set of equivalence classes of expressions, where each class
+/// holds every form of the same value found so far.
+#[derive(Debug, Default)]
+struct EGraph {
+    /// Union-find parent of every class id.
+    parent: Vec<Id>,
+    /// The nodes of every canonical class.
+    classes: BTreeMap<Id, Vec<Node>>,
+    /// Maps each node to the class that contains it.
+    memo: HashMap<Node, Id>,
+}
+
+impl EGraph {
+    fn find(&self, mut id: Id) -> Id {
+        while self.parent[id] != id {
+            id = self.parent[id];
+        }
+        id
+    }
+
+    /// Rewrites the operands of a node to canonical class ids, keeping sum and
+    /// product operands sorted so that reorderings of them coincide.
+    fn canonicalize(&self, node: &Node) -> Node {
+        match node {
+            Node::Num(_) | Node::Var(_) => node.clone(),
+            Node::Func(name, arg) => Node::Func(name.clone(), self.find(*arg)),
+            Node::Pow([base, exp]) => Node::Pow([self.find(*base), self.find(*exp)]),
+            Node::Sum(ids) | Node::Product(ids) => {
+                let mut ids: Vec<Id> = ids.iter().map(|&id| self.find(id)).collect();
+                ids.sort_unstable();
+                match node {
+                    Node::Sum(_) => Node::Sum(ids),
+                    _ => Node::Product(ids),
+                }
+            }
+        }
+    }
+
+    fn add(&mut self, node: Node) -> Id {
+        let node = self.canonicalize(&node);
+        if let Some(&id) = self.memo.get(&node) {
+            return self.find(id);
+        }
+        let id = self.parent.len();
+        self.parent.push(id);
+        self.memo.insert(node.clone(), id);
+        self.classes.insert(id, vec![node]);
+        id
+    }
+
+    /// Adds a canonical expression and returns its class.
+    fn add_expr(&mut self, expr: &Expr) -> Id {
+        // Children before parents, on an explicit stack as in `Arena::from_expr`
+        let mut pending = vec![(expr, false)];
+        let mut added: Vec<Id> = vec![];
+        while let Some((expr, ready)) = pending.pop() {
+            // Canonical expressions have no other shapes
+            if matches!(expr, Expr::Neg(_)) || matches!(expr, Expr::BinaryOp { op, .. } if *op != Op::Pow) {
+                let id = self.add_expr(&normalize(expr));
+                added.push(id);
+                continue;
+            }
+            let children = expr.children();
+            if !ready && !children.is_empty() {
+                pending.push((expr, true));
+                pending.extend(children.into_iter().rev().map(|child| (child, false)));
+                continue;
+            }
+            let ids = added.split_off(added.len() - children.len());
+            let node = match expr {
+                Expr::Num(n) => Node::num(*n),
+                Expr::Var(v) => Node::Var(v.clone()),
+                Expr::Func(name, _) => Node::Func(name.clone(), ids[0]),
+                Expr::BinaryOp { .. } => Node::Pow([ids[0], ids[1]]),
+                Expr::Sum(_) => Node::Sum(ids),
+                _ => Node::Product(ids),
+            };
+            added.push(self.add(node));
+        }
+        added.pop().expect("the root is added last")
+    }
+
+    /// Records that two classes are equal. Returns whether they were distinct.
+    fn union(&mut self, a: Id, b: Id) -> bool {
+        let (a, b) = (self.find(a), self.find(b));
+        if a == b {
+            return false;
+        }
+        self.parent[b] = a;
+        let nodes = self.classes.remove(&b).unwrap_or_default();
+        self.classes.entry(a).or_default().extend(nodes);
+        true
+    }
+
+    /// Restores the invariants after unions: nodes refer to canonical classes,
+    /// and classes that now contain the same node are merged.
+    fn rebuild(&mut self) {
+        loop {
+            let mut memo = HashMap::new();
+            let mut merges = vec![];
+            let ids: Vec<Id> = self.classes.keys().copied().collect();
+            for id in ids {
+                let mut nodes: Vec<Node> = self.classes[&id].iter().map(|n| self.canonicalize(n)).collect();
+                let mut seen = HashSet::new();
+                nodes.retain(|n| seen.insert(n.clone()));
+                for node in &nodes {
+                    match memo.get(node) {
+                        Some(&other) if other != id => merges.push((other, id)),
+                        Some(_) => {}
+                        None => {
+                            memo.insert(node.clone(), id);
+                        }
+                    }
+                }
+                self.classes.insert(id, nodes);
+            }
+            self.memo = memo;
+            if merges.is_empty() {
+                return;
+            }
+            for (a, b) in merges {
+                self.union(a, b);
+            }
+        }
+    }
+
+    fn node_count(&self) -> usize {
+        self.classes.values().map(Vec::len).sum()
+    }
+
+    /// Finds the cheapest node of every class under `cost`, iterating until
+    /// the costs settle since a class can depend on classes seen after it.
+    fn best(&self, cost: CostFn) -> Best {
+        let mut best = Best::new();
+        loop {
+            let mut changed = false;
+            for (&id, nodes) in &self.classes {
+                for node in nodes {
+                    let c = match self.node_cost(node, cost, &best) {
+                        Some(c) => c,
+                        None => continue,
+                    };
+                    let better = match best.get(&id) {
+                        Some((current, _)) => c < *current,
+                        None => true,
+                    };
+                    if better {
+                        best.insert(id, (c, node.clone()));
+                        changed = true;
+                    }
+                }
+            }
+            if !changed {
+                return best;
+            }
+        }
+    }
+
+    fn node_cost(&self, node: &Node, cost: CostFn, best: &Best) -> Option<usize> {
+        let own = match node {
+            Node::Num(_) | Node::Var(_) => cost.leaf_cost(),
+            Node::Func(..) | Node::Pow(_) => 1,
+            // An n-ary sum or product is n - 1 binary operators
+            Node::Sum(ids) | Node::Product(ids) => ids.len().saturating_sub(1),
+        };
+        node.children()
+            .iter()
+            .try_fold(own, |total, child| Some(total + best.get(&self.find(*child))?.0))
+    }
+
+    /// The cheapest expression in a class.
+    fn extract(&self, id: Id, best: &Best) -> Expr {
+        self.node_expr(&best[&self.find(id)].1, best)
+    }
+
+    /// A node as an expression, with its operands replaced by their cheapest forms.
+    fn node_expr(&self, node: &Node, best: &Best) -> Expr {
+        let operands = node.children().iter().map(|&id| self.extract(id, best)).collect();
+        with_operands(node, operands)
+    }
+
+    /// Matches a pattern against any node of a class.
+    fn ematch(&self, pattern: &Expr, id: Id, binds: &Matches) -> Vec<Matches> {
+        let id = self.find(id);
+        match pattern {
+            Expr::Var(name) => match binds.get(name) {
+                Some(Bound::Class(bound)) if self.find(*bound) == id => vec![binds.clone()],
+                Some(_) => vec![],
+                None => {
+                    let mut binds = binds.clone();
+                    binds.insert(name.clone(), Bound::Class(id));
+                    vec![binds]
+                }
+            },
+            _ => self.classes[&id]
+                .iter()
+                .flat_map(|node| self.ematch_node(pattern, node, binds))
+                .collect(),
+        }
+    }
+
+    /// Matches a pattern against one node, mirroring the tree matcher in `rewrite`.
+    fn ematch_node(&self, pattern: &Expr, node: &Node, binds: &Matches) -> Vec<Matches> {
+        match (pattern, node) {
+            (Expr::Num(a), Node::Num(_)) if Node::num(*a) == *node => vec![binds.clone()],
+            (Expr::Func(pn, pa), Node::Func(name, arg)) if pn == name => self.ematch(pa, *arg, binds),
+            (Expr::BinaryOp { op: Op::Pow, left, right }, Node::Pow([base, exp])) => self
+                .ematch(left, *base, binds)
+                .iter()
+                .flat_map(|b| self.ematch(right, *exp, b))
+                .collect(),
+            (Expr::Sum(pats), Node::Sum(ids)) | (Expr::Product(pats), Node::Product(ids)) => {
+                let sum = matches!(node, Node::Sum(_));
+                if pats.len() == ids.len() {
+                    return self.match_operands(pats, ids, binds).into_iter().map(|(b, _)| b).collect();
+                }
+                if pats.len() > ids.len() {
+                    return vec![];
+                }
+                // A free pattern variable captures all the leftover operands
+                let rest_var = match pats.iter().rposition(|p| matches!(p, Expr::Var(v) if !binds.contains_key(v))) {
+                    Some(i) => i,
+                    None => return vec![],
+                };
+                let fixed: Vec<Expr> = pats
+                    .iter()
+                    .enumerate()
+                    .filter(|(i, _)| *i != rest_var)
+                    .map(|(_, p)| p.clone())
+                    .collect();
+                let name = match &pats[rest_var] {
+                    Expr::Var(name) => name.clone(),
+                    _ => return vec![],
+                };
+                self.match_operands(&fixed, ids, binds)
+                    .into_iter()
+                    .map(|(mut b, used)| {
+                        let rest = unused(ids, &used);
+                        b.insert(name.clone(), Bound::Rest { sum, ids: rest });
+                        b
+                    })
+                    .collect()
+            }
+            _ => vec![],
+        }
+    }
+
+    /// Matches each pattern operand against a distinct operand class, returning
+    /// every way of doing so (up to a limit) along with which operands were used.
+    fn match_operands(&self, pats: &[Expr], ids: &[Id], binds: &Matches) -> Vec<(Matches, Vec<bool>)> {
+        let mut out = vec![];
+        let mut used = vec![false; ids.len()];
+        self.match_operands_into(pats, ids, &mut used, binds, &mut out);
+        out
+    }
+
+    fn match_operands_into(
+        &self,
+        pats: &[Expr],
+        ids: &[Id],
+        used: &mut [bool],
+        binds: &Matches,
+        out: &mut Vec<(Matches, Vec<bool>)>,
+    ) {
+        let (first, rest) = match pats.split_first() {
+            Some(split) => split,
+            None => {
+                out.push((binds.clone(), used.to_vec()));
+                return;
+            }
+        };
+        for i in 0..ids.len() {
+            if used[i] {
+                continue;
+            }
+            for candidate in self.ematch(first, ids[i], binds) {
+                if out.len() >= MAX_MATCHES_PER_NODE {
+                    return;
+                }
+                used[i] = true;
+                self.match_operands_into(rest, ids, used, &candidate, out);
+                used[i] = false;
+            }
+        }
+    }
+
+    /// Turns class bindings into expression bindings using the cheapest forms.
+    fn bindings(&self, matches: &Matches, best: &Best) -> Bindings {
+        matches
+            .iter()
+            .map(|(name, bound)| {
+                let expr = match bound {
+                    Bound::Class(id) => self.extract(*id, best),
+                    Bound::Rest { sum, ids } => {
+                        let operands = ids.iter().map(|&id| self.extract(id, best)).collect();
+                        normalize(&if *sum { Expr::Sum(operands) } else { Expr::Product(operands) })
+                    }
+                };
+                (name.clone(), expr)
+            })
+            .collect()
+    }
+
+    /// Finds every rewrite of every node, returning the expressions to merge
+    /// into each class. Besides the rules, every node is re-canonicalized with
+    /// the other forms of its operands, so that terms exposed by a rewrite
+    /// further down can combine.
+    fn search(&self, rules: &[&Rule], best: &Best, assumptions: &Assumptions, deadline: Instant) -> Vec<(Id, Expr)> {
+        let mut found = vec![];
+        for (&id, nodes) in &self.classes {
+            if Instant::now() >= deadline {
+                break;
+            }
+            for node in nodes {
+                if !self.node_children_known(node, best) {
+                    continue;
+                }
+                self.variants(id, node, best, &mut found);
+                for rule in rules {
+                    self.apply_rule(rule, id, node, best, assumptions, &mut found);
+                }
+            }
+        }
+        found
+    }
+
+    fn node_children_known(&self, node: &Node, best: &Best) -> bool {
+        node.children().iter().all(|id| best.contains_key(&self.find(*id)))
+    }
+
+    /// Re-canonicalizes a node with one operand at a time replaced by each of
+    /// its class's forms (the others taking their cheapest forms), so that e.g.
+    /// `cos(x) * sec(x)^2` meets `sec(x)^2 = cos(x)^-2` and cancels to `sec(x)`,
+    /// and a nested sum or product is flattened into its parent.
+    fn variants(&self, id: Id, node: &Node, best: &Best, found: &mut Vec<(Id, Expr)>) {
+        let children = node.children();
+        for (i, child) in children.iter().enumerate() {
+            for alt in &self.classes[&self.find(*child)] {
+                if !self.node_children_known(alt, best) {
+                    continue;
+                }
+                let mut operands: Vec<Expr> = children.iter().map(|&c| self.extract(c, best)).collect();
+                operands[i] = self.node_expr(alt, best);
+                found.push((id, normalize(&with_operands(node, operands))));
+            }
+        }
+    }
+
+    fn apply_rule(
+        &self,
+        rule: &Rule,
+        id: Id,
+        node: &Node,
+        best: &Best,
+        assumptions: &Assumptions,
+        found: &mut Vec<(Id, Expr)>,
+    ) {
+        match (rule.lhs(), node) {
+            // As in the rewriting simplifier, a sum or product pattern may match
+            // just some of the operands, and the rest are kept alongside.
+            (Expr::Sum(pats), Node::Sum(ids)) | (Expr::Product(pats), Node::Product(ids)) if pats.len() <= ids.len() => {
+                for (matches, used) in self.match_operands(pats, ids, &Matches::new()) {
+                    let rhs = match rule.instantiate(&self.bindings(&matches, best), assumptions) {
+                        Some(rhs) => rhs,
+                        None => continue,
+                    };
+                    let mut operands = vec![rhs];
+                    operands.extend(unused(ids, &used).into_iter().map(|id| self.extract(id, best)));
+                    let rewritten = match node {
+                        Node::Sum(_) => Expr::Sum(operands),
+                        _ => Expr::Product(operands),
+                    };
+                    found.push((id, normalize(&rewritten)));
+                }
+            }
+            (lhs, _) => {
+                for matches in self.ematch_node(lhs, node, &Matches::new()) {
+                    if let Some(rhs) = rule.instantiate(&self.bindings(&matches, best), assumptions) {
+                        found.push((id, normalize(&rhs)));
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Builds an expression of the same shape as `node` from operand expressions.
+fn with_operands(node: &Node, mut operands: Vec<Expr>) -> Expr {
+    match node {
+        Node::Num(bits) => Expr::Num(f64::from_bits(*bits)),
+        Node::Var(v) => Expr::Var(v.clone()),
+        Node::Func(name, _) => Expr::Func(name.clone(), Box::new(operands.remove(0))),
+        Node::Pow(_) => {
+            let exp = operands.pop().unwrap();
+            let base = operands.pop().unwrap();
+            Expr::BinaryOp {
+                op: Op::Pow,
+                left: Box::new(base),
+                right: Box::new(exp),
+            }
+        }
+        Node::Sum(_) => Expr::Sum(operands),
+        Node::Product(_) => Expr::Product(operands),
+    }
+}
+
+/// The operands not marked as used.
+fn unused(ids: &[Id], used: &[bool]) -> Vec<Id> {
+    ids.iter().zip(used).filter(|(_, used)| !**used).map(|(&id, _)| id).collect()
+}
+
+/// Simplifies an expression by equality saturation: the expression goes into
+/// an e-graph, the rewrite rules (the context's, those of its trig and log
+/// modes, with trig identities in both directions, and distributing and
+/// factoring) add every equivalent form they can find until nothing new turns
+/// up or a limit in `settings` or `ctx.limits` is hit, and the cheapest form
+/// under `settings.cost` is returned.
+///
+/// Unlike [`simplify`](crate::simplifier::simplify), which rewrites greedily and
+/// can get stuck, this can go through a larger form to reach a smaller one,
+/// but it is much slower.
+pub fn simplify(expr: &Expr, ctx: &Context, settings: &EGraphSettings) -> Expr {
+    on_large_stack(|| simplify_limited(expr, ctx, settings, &Budget::start(&ctx.limits)))
+}
+
+/// Like [`simplify`], but stops growing the e-graph by the deadline of
+/// `budget` if that comes before `settings.time_limit` is up.
+pub fn simplify_limited(expr: &Expr, ctx: &Context, settings: &EGraphSettings, budget: &Budget) -> Expr {
+    // The identities also go the other way, through sin and cos, since the
+    // cheapest form is picked in the end anyway
+    let reverse_trig = match ctx.trig {
+        TrigMode::Identities => TrigMode::SinCos.rules(),
+        _ => TrigMode::Off.rules(),
+    };
+    let rules: Vec<&Rule> = ctx
+        .rules
+        .iter()
+        .chain(ctx.trig.rules().iter())
+        .chain(reverse_trig.iter())
+        .chain(ctx.log.rules().iter())
+        .chain(ALGEBRA_RULES.iter())
+        .collect();
+
+    let own_deadline = Instant::now() + settings.time_limit;
+    let deadline = budget.deadline().map_or(own_deadline, |d| d.min(own_deadline));
+    let mut graph = EGraph::default();
+    let root = graph.add_expr(&normalize(expr));
+    for _ in 0..settings.iter_limit {
+        let best = graph.best(settings.cost);
+        let found = graph.search(&rules, &best, &ctx.assumptions, deadline);
+        let mut changed = false;
+        for (id, expr) in found {
+            if graph.node_count() >= settings.node_limit || Instant::now() >= deadline {
+                break;
+            }
+            let new = graph.add_expr(&expr);
+            changed |= graph.union(id, new);
+        }
+        graph.rebuild();
+        let cancelled = ctx.limits.cancel.is_cancelled();
+        if !changed || cancelled || graph.node_count() >= settings.node_limit || Instant::now() >= deadline {
+            break;
+        }
+    }
+    let best = graph.best(settings.cost);
+    normalize(&graph.extract(root, &best))
+}
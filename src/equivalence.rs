@@ -0,0 +1,161 @@
+use std::fmt;
+
+use crate::ast::{Expr, Op};
+use crate::compile::CompiledFn;
+use crate::constants::IMAGINARY_UNIT;
+use crate::context::Context;
+use crate::rational;
+use crate::simplifier::simplify_with;
+
+/// How many random points are tried when the expressions do not simplify to
+/// the same thing.
+const SAMPLES: usize = 200;
+
+/// How many of those points must lie in both expressions' domain before
+/// agreement at all of them counts as equality.
+const MIN_VALID_SAMPLES: usize = 20;
+
+/// Values this close, relative to their size, are taken to be equal.
+const TOLERANCE: f64 = 1e-8;
+
+/// The outcome of comparing two expressions.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Equivalence {
+    /// The expressions simplify to the same thing, or agree at every sampled point.
+    Equal,
+    /// The expressions differ at this point (a value for every variable): only
+    /// one of them is defined or infinite there, or they are different numbers.
+    NotEqual(Vec<(String, f64)>),
+    /// Too few sampled points lie where both expressions are defined to tell.
+    Unknown,
+}
+
+impl fmt::Display for Equivalence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Equivalence::Equal => write!(f, "equal"),
+            Equivalence::NotEqual(point) if point.is_empty() => write!(f, "not equal"),
+            Equivalence::NotEqual(point) => {
+                let values: Vec<String> = point.iter().map(|(v, x)| format!("{} = {}", v, x)).collect();
+                write!(f, "not equal at {}", values.join(", "))
+            }
+            Equivalence::Unknown => write!(f, "unknown"),
+        }
+    }
+}
+
+/// Checks whether two expressions are equal, see [`equivalent_with`].
+pub fn equivalent(a: &Expr, b: &Expr) -> Equivalence {
+    equivalent_with(a, b, &Context::default())
+}
+
+/// Checks whether two expressions are the same function.
+///
+/// The difference `a - b` is simplified first: if it becomes zero, the
+/// expressions agree wherever both are defined. Both are then evaluated at
+/// random points (that satisfy the assumptions in `ctx`). A point where only
+/// one of them is defined, as for `exp(log(x))` and `x` at negative `x`, or
+/// only one is infinite, or they are infinite with opposite signs, proves them
+/// unequal, and so does one where their values differ, unless the difference
+/// simplified to zero. Agreement at every point is taken as equality.
+pub fn equivalent_with(a: &Expr, b: &Expr, ctx: &Context) -> Equivalence {
+    let difference = Expr::BinaryOp {
+        op: Op::Sub,
+        left: Box::new(a.clone()),
+        right: Box::new(b.clone()),
+    };
+    // If simplifying hits a limit, the sampled comparison can still decide.
+    // A difference that simplifies to zero still leaves the domains to compare,
+    // since simplifying `0*log(x)` to `0` forgets that `log(x)` needs `x > 0`
+    let cancels = match simplify_with(&difference, ctx) {
+        Ok(simplified) => simplified == Expr::Num(0.0) || rational::cancel(&simplified) == Expr::Num(0.0),
+        Err(_) => false,
+    };
+    let undecided = if cancels { Equivalence::Equal } else { Equivalence::Unknown };
+
+    let mut names = a.variables();
+    names.extend(b.variables());
+    // The imaginary unit has no real value to sample, so an expression using
+    // it fails to compile below and is left undecided
+    names.retain(|name| name != IMAGINARY_UNIT);
+    names.sort();
+    names.dedup();
+    // Both are compiled together, so the work they share is done once per point
+    let vars: Vec<&str> = names.iter().map(String::as_str).collect();
+    let functions = [ctx.angle.to_radians(a), ctx.angle.to_radians(b)];
+    let Ok(compiled) = CompiledFn::compile_all(&functions, &vars) else {
+        return undecided;
+    };
+
+    let mut rng = Rng::new(0x2545_f491_4f6c_dd1d);
+    let mut columns = vec![Vec::with_capacity(SAMPLES); names.len()];
+    for i in 0..SAMPLES {
+        for (column, name) in columns.iter_mut().zip(&names) {
+            column.push(sample(&mut rng, i, name, ctx));
+        }
+    }
+    let columns: Vec<&[f64]> = columns.iter().map(Vec::as_slice).collect();
+    let (mut values_a, mut values_b) = (vec![0.0; SAMPLES], vec![0.0; SAMPLES]);
+    if compiled.eval_batch(&columns, &mut [&mut values_a, &mut values_b]).is_err() {
+        return undecided;
+    }
+
+    let point = |i: usize| names.iter().zip(&columns).map(|(name, column)| (name.clone(), column[i])).collect();
+    let mut valid = 0;
+    for (i, (&va, &vb)) in values_a.iter().zip(&values_b).enumerate() {
+        // Only points where both are undefined, or both the same infinity, tell nothing
+        if va.is_nan() != vb.is_nan() || va.is_infinite() != vb.is_infinite() || (va.is_infinite() && va != vb) {
+            return Equivalence::NotEqual(point(i));
+        }
+        if !va.is_finite() {
+            continue;
+        }
+        valid += 1;
+        // Where the difference cancels, a gap in the values is rounding error
+        if !cancels && (va - vb).abs() > TOLERANCE * va.abs().max(vb.abs()).max(1.0) {
+            return Equivalence::NotEqual(point(i));
+        }
+    }
+    if cancels || valid >= MIN_VALID_SAMPLES {
+        Equivalence::Equal
+    } else {
+        Equivalence::Unknown
+    }
+}
+
+/// A random value for a variable, respecting what is assumed about it. Early
+/// samples stay near zero, where most functions are defined; later ones
+/// spread further out.
+fn sample(rng: &mut Rng, i: usize, name: &str, ctx: &Context) -> f64 {
+    let scale = if i < SAMPLES / 2 { 1.0 } else { 10.0 };
+    let facts = ctx.assumptions.facts(name);
+    let x = if facts.positive {
+        rng.next_f64() * scale + f64::EPSILON
+    } else {
+        (2.0 * rng.next_f64() - 1.0) * scale
+    };
+    if facts.integer {
+        let n = (x * 10.0).round();
+        if facts.positive { n.max(1.0) } else { n }
+    } else {
+        x
+    }
+}
+
+/// A small xorshift generator, so that checks are reproducible and need no
+/// extra dependencies.
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Self {
+        Rng(seed)
+    }
+
+    /// A uniformly distributed value in `[0, 1)`.
+    fn next_f64(&mut self) -> f64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
@@ -0,0 +1,160 @@
+use std::collections::HashMap;
+use std::f64::consts::FRAC_PI_2;
+
+use crate::ast::{Expr, Op};
+
+/// A built-in function, resolved from its name once so that evaluating it
+/// many times does not compare strings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Function {
+    Sin,
+    Cos,
+    Tan,
+    Cot,
+    Sec,
+    Cosec,
+    Arcsin,
+    Arccos,
+    Arctan,
+    Arccsc,
+    Arcsec,
+    Arccot,
+    Sinh,
+    Cosh,
+    Tanh,
+    Coth,
+    Sech,
+    Cosech,
+    Arsinh,
+    Arcosh,
+    Artanh,
+    Arcosech,
+    Arsech,
+    Arcoth,
+    Log,
+    Exp,
+    Sqrt,
+    Abs,
+}
+
+impl Function {
+    /// The built-in function called `name`, if there is one.
+    pub fn from_name(name: &str) -> Option<Function> {
+        let function = match name {
+            // Regular trigonometric functions
+            "sin" => Function::Sin,
+            "cos" => Function::Cos,
+            "tan" => Function::Tan,
+            "cot" => Function::Cot,
+            "sec" => Function::Sec,
+            "cosec" => Function::Cosec,
+
+            // Inverse trigonometric functions
+            "arcsin" => Function::Arcsin,
+            "arccos" => Function::Arccos,
+            "arctan" => Function::Arctan,
+            "arccsc" => Function::Arccsc,
+            "arcsec" => Function::Arcsec,
+            "arccot" => Function::Arccot,
+
+            // Hyperbolic functions
+            "sinh" => Function::Sinh,
+            "cosh" => Function::Cosh,
+            "tanh" => Function::Tanh,
+            "coth" => Function::Coth,
+            "sech" => Function::Sech,
+            "cosech" => Function::Cosech,
+
+            // Inverse hyperbolic functions
+            "arsinh" => Function::Arsinh,
+            "arcosh" => Function::Arcosh,
+            "artanh" => Function::Artanh,
+            "arcosech" => Function::Arcosech,
+            "arsech" => Function::Arsech,
+            "arcoth" => Function::Arcoth,
+
+            // Logarithmic, exponential and other functions
+            "log" => Function::Log,
+            "exp" => Function::Exp,
+            "sqrt" => Function::Sqrt,
+            "abs" => Function::Abs,
+            _ => return None,
+        };
+        Some(function)
+    }
+
+    /// Evaluates the function at a real number; values outside its domain give NaN.
+    pub fn apply(self, x: f64) -> f64 {
+        match self {
+            Function::Sin => x.sin(),
+            Function::Cos => x.cos(),
+            Function::Tan => x.tan(),
+            Function::Cot => 1.0 / x.tan(),
+            Function::Sec => 1.0 / x.cos(),
+            Function::Cosec => 1.0 / x.sin(),
+            Function::Arcsin => x.asin(),
+            Function::Arccos => x.acos(),
+            Function::Arctan => x.atan(),
+            Function::Arccsc => (1.0 / x).asin(),
+            Function::Arcsec => (1.0 / x).acos(),
+            Function::Arccot => FRAC_PI_2 - x.atan(),
+            Function::Sinh => x.sinh(),
+            Function::Cosh => x.cosh(),
+            Function::Tanh => x.tanh(),
+            Function::Coth => 1.0 / x.tanh(),
+            Function::Sech => 1.0 / x.cosh(),
+            Function::Cosech => 1.0 / x.sinh(),
+            Function::Arsinh => x.asinh(),
+            Function::Arcosh => x.acosh(),
+            Function::Artanh => x.atanh(),
+            Function::Arcosech => (1.0 / x).asinh(),
+            Function::Arsech => (1.0 / x).acosh(),
+            Function::Arcoth => (1.0 / x).atanh(),
+            Function::Log => x.ln(),
+            Function::Exp => x.exp(),
+            Function::Sqrt => x.sqrt(),
+            Function::Abs => x.abs(),
+        }
+    }
+}
+
+/// Evaluates a built-in function at a real number. Returns `None` if `name`
+/// is not a known function; values outside a function's domain give NaN.
+pub fn apply_function(name: &str, x: f64) -> Option<f64> {
+    Function::from_name(name).map(|f| f.apply(x))
+}
+
+/// Evaluates an expression numerically, looking variables up in `vars`.
+pub fn eval(expr: &Expr, vars: &HashMap<String, f64>) -> Result<f64, String> {
+    match expr {
+        Expr::Num(n) => Ok(*n),
+        Expr::Var(v) => vars
+            .get(v)
+            .copied()
+            .ok_or_else(|| format!("No value given for variable '{}'", v)),
+        Expr::Neg(inner) => Ok(-eval(inner, vars)?),
+        Expr::BinaryOp { op, left, right } => {
+            let l = eval(left, vars)?;
+            let r = eval(right, vars)?;
+            Ok(match op {
+                Op::Add => l + r,
+                Op::Sub => l - r,
+                Op::Mul => l * r,
+                Op::Div => l / r,
+                Op::Pow => l.powf(r),
+            })
+        }
+        Expr::Func(name, arg) => {
+            let x = eval(arg, vars)?;
+            apply_function(name, x).ok_or_else(|| format!("Unknown function: {}", name))
+        }
+        Expr::Sum(terms) => terms.iter().map(|t| eval(t, vars)).sum(),
+        Expr::Product(factors) => factors.iter().map(|f| eval(f, vars)).product(),
+    }
+}
+
+/// Evaluates an expression that contains no variables, e.g. `sqrt 3 / 2`.
+/// Returns `None` if it does contain variables or unknown functions.
+pub fn eval_constant(expr: &Expr) -> Option<f64> {
+    eval(expr, &HashMap::new()).ok()
+}
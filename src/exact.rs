@@ -0,0 +1,124 @@
+use std::f64::consts::{E, PI};
+
+use crate::ast::{Expr, Op};
+use crate::eval::eval_constant;
+
+/// How many rounding errors a computed angle or value may be off a notable
+/// one by and still count as it, e.g. `pi/6` computed in floating point.
+const ULPS: f64 = 8.0;
+
+/// The largest angle [`sin_value`] looks at. Beyond it the rounding errors are
+/// too large to tell a notable angle from any other, e.g. `1e15` lies within
+/// rounding of a multiple of `pi/12`.
+const MAX_ANGLE: f64 = 1e6;
+
+/// Returns the exact value of `name(arg)` when `arg` is a constant at which the
+/// function has a well-known closed form, e.g. `sin(pi/6) = 1/2`,
+/// `cos(pi) = -1`, `arctan(1) = pi/4` or `sqrt(4) = 2`.
+pub fn special_value(name: &str, arg: &Expr) -> Option<Expr> {
+    let x = eval_constant(arg)?;
+    match name {
+        "sin" => sin_value(x),
+        "cos" => sin_value(x + PI / 2.0),
+        "tan" => quotient(sin_value(x)?, sin_value(x + PI / 2.0)?),
+        "cot" => quotient(sin_value(x + PI / 2.0)?, sin_value(x)?),
+        "sec" => quotient(Expr::Num(1.0), sin_value(x + PI / 2.0)?),
+        "cosec" => quotient(Expr::Num(1.0), sin_value(x)?),
+        "arcsin" => inverse_value(x, |k| sin_value(k as f64 * PI / 12.0), -6, 6),
+        "arccos" => inverse_value(x, |k| sin_value(k as f64 * PI / 12.0 + PI / 2.0), 0, 12),
+        "arctan" => inverse_value(x, |k| tan_value(k as f64 * PI / 12.0), -5, 5),
+        "arccot" => inverse_value(x, |k| tan_value(PI / 2.0 - k as f64 * PI / 12.0), 1, 11),
+        "sinh" | "tanh" | "arsinh" | "artanh" if x == 0.0 => Some(Expr::Num(0.0)),
+        "cosh" if x == 0.0 => Some(Expr::Num(1.0)),
+        "arcosh" if x == 1.0 => Some(Expr::Num(0.0)),
+        "exp" if x == 0.0 => Some(Expr::Num(1.0)),
+        "exp" if x == 1.0 => Some(Expr::Num(E)),
+        "log" if x == 1.0 => Some(Expr::Num(0.0)),
+        "log" if x == E => Some(Expr::Num(1.0)),
+        "sqrt" => {
+            let root = x.sqrt();
+            if root.fract() == 0.0 || (1.0 / root).fract() == 0.0 {
+                Some(Expr::Num(root))
+            } else {
+                None
+            }
+        }
+        "abs" if x >= 0.0 => Some(arg.clone()),
+        "abs" if x < 0.0 => Some(Expr::Neg(Box::new(arg.clone()))),
+        _ => None,
+    }
+}
+
+/// `sin(x)` for `x` a multiple of `pi/6` or `pi/4`, as an exact expression.
+fn sin_value(x: f64) -> Option<Expr> {
+    if x.abs() > MAX_ANGLE {
+        return None;
+    }
+    let k = (x / (PI / 12.0)).round();
+    if !close(x, k * PI / 12.0) {
+        return None;
+    }
+    // Position on the unit circle in twelfths of pi, in 0..24
+    let m = (k as i64).rem_euclid(24);
+    let reference = match m % 12 {
+        r if r > 6 => 12 - r,
+        r => r,
+    };
+    let magnitude = match reference {
+        0 => Expr::Num(0.0),
+        2 => Expr::Num(0.5),
+        3 => half_sqrt(2.0),
+        4 => half_sqrt(3.0),
+        6 => Expr::Num(1.0),
+        _ => return None,
+    };
+    if m > 12 {
+        Some(Expr::Neg(Box::new(magnitude)))
+    } else {
+        Some(magnitude)
+    }
+}
+
+/// Whether `a` and `b` differ by no more than a few rounding errors. The
+/// tolerance is absolute near zero, so that e.g. `sin(1e-13)` is not taken for `sin(0)`.
+fn close(a: f64, b: f64) -> bool {
+    (a - b).abs() <= ULPS * f64::EPSILON * a.abs().max(b.abs()).max(1.0)
+}
+
+fn tan_value(x: f64) -> Option<Expr> {
+    quotient(sin_value(x)?, sin_value(x + PI / 2.0)?)
+}
+
+/// `sqrt(n) / 2`
+fn half_sqrt(n: f64) -> Expr {
+    Expr::BinaryOp {
+        op: Op::Div,
+        left: Box::new(Expr::Func("sqrt".to_string(), Box::new(Expr::Num(n)))),
+        right: Box::new(Expr::Num(2.0)),
+    }
+}
+
+/// `a / b`, or `None` if `b` is zero.
+fn quotient(a: Expr, b: Expr) -> Option<Expr> {
+    if eval_constant(&b)? == 0.0 {
+        return None;
+    }
+    Some(Expr::BinaryOp {
+        op: Op::Div,
+        left: Box::new(a),
+        right: Box::new(b),
+    })
+}
+
+/// Finds the angle `k * pi/12`, for `k` in `lo..=hi`, at which `forward` takes
+/// the value `x`, and returns it as a number.
+fn inverse_value(x: f64, forward: impl Fn(i64) -> Option<Expr>, lo: i64, hi: i64) -> Option<Expr> {
+    (lo..=hi).find_map(|k| {
+        let y = eval_constant(&forward(k)?)?;
+        if close(x, y) {
+            Some(Expr::Num(k as f64 * PI / 12.0))
+        } else {
+            None
+        }
+    })
+}
@@ -1,8 +1,18 @@
 use std::collections::HashMap;
 
+use crate::assumptions::Assumptions;
 use crate::ast::{Expr,Op}; // Assuming `Expr` is in ast.rs
 
-pub fn conversion(func_name: &str, arg: Expr) -> Result<Expr, String> {
+/// `|arg|`, or just `arg` when it is known to be positive.
+fn magnitude(arg: &Expr, assumptions: &Assumptions) -> Expr {
+    if assumptions.is_positive(arg) {
+        arg.clone()
+    } else {
+        Expr::Func("abs".to_string(), Box::new(arg.clone()))
+    }
+}
+
+pub fn conversion(func_name: &str, arg: Expr, assumptions: &Assumptions) -> Result<Expr, String> {
     let mut rules: HashMap<&str, &str> = HashMap::new();
 
     // Regular trigonometric functions
@@ -48,39 +58,27 @@ pub fn conversion(func_name: &str, arg: Expr) -> Result<Expr, String> {
     match rules.get(func_name) {
         // Regular trigonometric derivatives
         Some(&"cos") => Ok(Expr::Func("cos".to_string(), Box::new(arg))),
-        Some(&"-sin") => Ok(Expr::BinaryOp {
-            op: Op::Mul,
-            left: Box::new(Expr::Var("-1".to_string())),
-            right: Box::new(Expr::Func("sin".to_string(), Box::new(arg))),
-        }),
+        Some(&"-sin") => Ok(Expr::Neg(Box::new(Expr::Func("sin".to_string(), Box::new(arg))))),
         Some(&"sec^2") => Ok(Expr::BinaryOp {
             op: Op::Pow,
             left: Box::new(Expr::Func("sec".to_string(), Box::new(arg.clone()))),
-            right: Box::new(Expr::Var("2".to_string())),
-        }),
-        Some(&"-cosec^2") => Ok(Expr::BinaryOp {
-            op: Op::Mul,
-            left: Box::new(Expr::Var("-1".to_string())),
-            right: Box::new(Expr::BinaryOp {
-                op: Op::Pow,
-                left: Box::new(Expr::Func("cosec".to_string(), Box::new(arg.clone()))),
-                right: Box::new(Expr::Var("2".to_string())),
-            }),
+            right: Box::new(Expr::Num(2.0)),
         }),
+        Some(&"-cosec^2") => Ok(Expr::Neg(Box::new(Expr::BinaryOp {
+            op: Op::Pow,
+            left: Box::new(Expr::Func("cosec".to_string(), Box::new(arg.clone()))),
+            right: Box::new(Expr::Num(2.0)),
+        }))),
         Some(&"sec*tan") => Ok(Expr::BinaryOp {
             op: Op::Mul,
             left: Box::new(Expr::Func("sec".to_string(), Box::new(arg.clone()))),
             right: Box::new(Expr::Func("tan".to_string(), Box::new(arg))),
         }),
-        Some(&"-cosec*cot") => Ok(Expr::BinaryOp {
+        Some(&"-cosec*cot") => Ok(Expr::Neg(Box::new(Expr::BinaryOp {
             op: Op::Mul,
-            left: Box::new(Expr::Var("-1".to_string())),
-            right: Box::new(Expr::BinaryOp {
-                op: Op::Mul,
-                left: Box::new(Expr::Func("cosec".to_string(), Box::new(arg.clone()))),
-                right: Box::new(Expr::Func("cot".to_string(), Box::new(arg))),
-            }),
-        }),
+            left: Box::new(Expr::Func("cosec".to_string(), Box::new(arg.clone()))),
+            right: Box::new(Expr::Func("cot".to_string(), Box::new(arg))),
+        }))),
         
         // Hyperbolic function derivatives
         Some(&"cosh") => Ok(Expr::Func("cosh".to_string(), Box::new(arg))),
@@ -88,132 +86,108 @@ pub fn conversion(func_name: &str, arg: Expr) -> Result<Expr, String> {
         Some(&"sech^2") => Ok(Expr::BinaryOp {
             op: Op::Pow,
             left: Box::new(Expr::Func("sech".to_string(), Box::new(arg.clone()))),
-            right: Box::new(Expr::Var("2".to_string())),
-        }),
-        Some(&"-cosech^2") => Ok(Expr::BinaryOp {
-            op: Op::Mul,
-            left: Box::new(Expr::Var("-1".to_string())),
-            right: Box::new(Expr::BinaryOp {
-                op: Op::Pow,
-                left: Box::new(Expr::Func("cosech".to_string(), Box::new(arg.clone()))),
-                right: Box::new(Expr::Var("2".to_string())),
-            }),
+            right: Box::new(Expr::Num(2.0)),
         }),
-        Some(&"-sech*tanh") => Ok(Expr::BinaryOp {
+        Some(&"-cosech^2") => Ok(Expr::Neg(Box::new(Expr::BinaryOp {
+            op: Op::Pow,
+            left: Box::new(Expr::Func("cosech".to_string(), Box::new(arg.clone()))),
+            right: Box::new(Expr::Num(2.0)),
+        }))),
+        Some(&"-sech*tanh") => Ok(Expr::Neg(Box::new(Expr::BinaryOp {
             op: Op::Mul,
-            left: Box::new(Expr::Var("-1".to_string())),
-            right: Box::new(Expr::BinaryOp {
-                op: Op::Mul,
-                left: Box::new(Expr::Func("sech".to_string(), Box::new(arg.clone()))),
-                right: Box::new(Expr::Func("tanh".to_string(), Box::new(arg))),
-            }),
-        }),
-        Some(&"-cosech*coth") => Ok(Expr::BinaryOp {
+            left: Box::new(Expr::Func("sech".to_string(), Box::new(arg.clone()))),
+            right: Box::new(Expr::Func("tanh".to_string(), Box::new(arg))),
+        }))),
+        Some(&"-cosech*coth") => Ok(Expr::Neg(Box::new(Expr::BinaryOp {
             op: Op::Mul,
-            left: Box::new(Expr::Var("-1".to_string())),
-            right: Box::new(Expr::BinaryOp {
-                op: Op::Mul,
-                left: Box::new(Expr::Func("cosech".to_string(), Box::new(arg.clone()))),
-                right: Box::new(Expr::Func("coth".to_string(), Box::new(arg))),
-            }),
-        }),
+            left: Box::new(Expr::Func("cosech".to_string(), Box::new(arg.clone()))),
+            right: Box::new(Expr::Func("coth".to_string(), Box::new(arg))),
+        }))),
         
         // Inverse trigonometric derivatives
         Some(&"1/sqrt(1-x^2)") => Ok(Expr::BinaryOp {
             op: Op::Div,
-            left: Box::new(Expr::Var("1".to_string())),
+            left: Box::new(Expr::Num(1.0)),
             right: Box::new(Expr::Func("sqrt".to_string(), Box::new(Expr::BinaryOp {
                 op: Op::Sub,
-                left: Box::new(Expr::Var("1".to_string())),
+                left: Box::new(Expr::Num(1.0)),
                 right: Box::new(Expr::BinaryOp {
                     op: Op::Pow,
                     left: Box::new(arg),
-                    right: Box::new(Expr::Var("2".to_string())),
+                    right: Box::new(Expr::Num(2.0)),
                 }),
             }))),
         }),
-        Some(&"-1/sqrt(1-x^2)") => Ok(Expr::BinaryOp {
-            op: Op::Mul,
-            left: Box::new(Expr::Var("-1".to_string())),
-            right: Box::new(Expr::BinaryOp {
-                op: Op::Div,
-                left: Box::new(Expr::Var("1".to_string())),
-                right: Box::new(Expr::Func("sqrt".to_string(), Box::new(Expr::BinaryOp {
-                    op: Op::Sub,
-                    left: Box::new(Expr::Var("1".to_string())),
-                    right: Box::new(Expr::BinaryOp {
-                        op: Op::Pow,
-                        left: Box::new(arg),
-                        right: Box::new(Expr::Var("2".to_string())),
-                    }),
-                }))),
-            }),
-        }),
+        Some(&"-1/sqrt(1-x^2)") => Ok(Expr::Neg(Box::new(Expr::BinaryOp {
+            op: Op::Div,
+            left: Box::new(Expr::Num(1.0)),
+            right: Box::new(Expr::Func("sqrt".to_string(), Box::new(Expr::BinaryOp {
+                op: Op::Sub,
+                left: Box::new(Expr::Num(1.0)),
+                right: Box::new(Expr::BinaryOp {
+                    op: Op::Pow,
+                    left: Box::new(arg),
+                    right: Box::new(Expr::Num(2.0)),
+                }),
+            }))),
+        }))),
         Some(&"1/(1+x^2)") => Ok(Expr::BinaryOp {
             op: Op::Div,
-            left: Box::new(Expr::Var("1".to_string())),
+            left: Box::new(Expr::Num(1.0)),
             right: Box::new(Expr::BinaryOp {
                 op: Op::Add,
-                left: Box::new(Expr::Var("1".to_string())),
+                left: Box::new(Expr::Num(1.0)),
                 right: Box::new(Expr::BinaryOp {
                     op: Op::Pow,
                     left: Box::new(arg),
-                    right: Box::new(Expr::Var("2".to_string())),
+                    right: Box::new(Expr::Num(2.0)),
                 }),
             }),
         }),
-        Some(&"-1/(1+x^2)") => Ok(Expr::BinaryOp {
-            op: Op::Mul,
-            left: Box::new(Expr::Var("-1".to_string())),
+        Some(&"-1/(1+x^2)") => Ok(Expr::Neg(Box::new(Expr::BinaryOp {
+            op: Op::Div,
+            left: Box::new(Expr::Num(1.0)),
             right: Box::new(Expr::BinaryOp {
-                op: Op::Div,
-                left: Box::new(Expr::Var("1".to_string())),
+                op: Op::Add,
+                left: Box::new(Expr::Num(1.0)),
                 right: Box::new(Expr::BinaryOp {
-                    op: Op::Add,
-                    left: Box::new(Expr::Var("1".to_string())),
-                    right: Box::new(Expr::BinaryOp {
-                        op: Op::Pow,
-                        left: Box::new(arg),
-                        right: Box::new(Expr::Var("2".to_string())),
-                    }),
+                    op: Op::Pow,
+                    left: Box::new(arg),
+                    right: Box::new(Expr::Num(2.0)),
                 }),
             }),
-        }),
-        Some(&"-1/(|x|*sqrt(x^2-1))") => Ok(Expr::BinaryOp {
-            op: Op::Mul,
-            left: Box::new(Expr::Var("-1".to_string())),
+        }))),
+        Some(&"-1/(|x|*sqrt(x^2-1))") => Ok(Expr::Neg(Box::new(Expr::BinaryOp {
+            op: Op::Div,
+            left: Box::new(Expr::Num(1.0)),
             right: Box::new(Expr::BinaryOp {
-                op: Op::Div,
-                left: Box::new(Expr::Var("1".to_string())),
-                right: Box::new(Expr::BinaryOp {
-                    op: Op::Mul,
-                    left: Box::new(Expr::Func("abs".to_string(), Box::new(arg.clone()))),
-                    right: Box::new(Expr::Func("sqrt".to_string(), Box::new(Expr::BinaryOp {
-                        op: Op::Sub,
-                        left: Box::new(Expr::BinaryOp {
-                            op: Op::Pow,
-                            left: Box::new(arg),
-                            right: Box::new(Expr::Var("2".to_string())),
-                        }),
-                        right: Box::new(Expr::Var("1".to_string())),
-                    }))),
-                }),
+                op: Op::Mul,
+                left: Box::new(magnitude(&arg, assumptions)),
+                right: Box::new(Expr::Func("sqrt".to_string(), Box::new(Expr::BinaryOp {
+                    op: Op::Sub,
+                    left: Box::new(Expr::BinaryOp {
+                        op: Op::Pow,
+                        left: Box::new(arg),
+                        right: Box::new(Expr::Num(2.0)),
+                    }),
+                    right: Box::new(Expr::Num(1.0)),
+                }))),
             }),
-        }),
+        }))),
         Some(&"1/(|x|*sqrt(x^2-1))") => Ok(Expr::BinaryOp {
             op: Op::Div,
-            left: Box::new(Expr::Var("1".to_string())),
+            left: Box::new(Expr::Num(1.0)),
             right: Box::new(Expr::BinaryOp {
                 op: Op::Mul,
-                left: Box::new(Expr::Func("abs".to_string(), Box::new(arg.clone()))),
+                left: Box::new(magnitude(&arg, assumptions)),
                 right: Box::new(Expr::Func("sqrt".to_string(), Box::new(Expr::BinaryOp {
                     op: Op::Sub,
                     left: Box::new(Expr::BinaryOp {
                         op: Op::Pow,
                         left: Box::new(arg),
-                        right: Box::new(Expr::Var("2".to_string())),
+                        right: Box::new(Expr::Num(2.0)),
                     }),
-                    right: Box::new(Expr::Var("1".to_string())),
+                    right: Box::new(Expr::Num(1.0)),
                 }))),
             }),
         }),
@@ -221,106 +195,99 @@ pub fn conversion(func_name: &str, arg: Expr) -> Result<Expr, String> {
         // Inverse hyperbolic function derivatives
         Some(&"1/sqrt(1+x^2)") => Ok(Expr::BinaryOp {
             op: Op::Div,
-            left: Box::new(Expr::Var("1".to_string())),
+            left: Box::new(Expr::Num(1.0)),
             right: Box::new(Expr::Func("sqrt".to_string(), Box::new(Expr::BinaryOp {
                 op: Op::Add,
-                left: Box::new(Expr::Var("1".to_string())),
+                left: Box::new(Expr::Num(1.0)),
                 right: Box::new(Expr::BinaryOp {
                     op: Op::Pow,
                     left: Box::new(arg),
-                    right: Box::new(Expr::Var("2".to_string())),
+                    right: Box::new(Expr::Num(2.0)),
                 }),
             }))),
         }),
         Some(&"1/sqrt(x^2-1)") => Ok(Expr::BinaryOp {
             op: Op::Div,
-            left: Box::new(Expr::Var("1".to_string())),
+            left: Box::new(Expr::Num(1.0)),
             right: Box::new(Expr::Func("sqrt".to_string(), Box::new(Expr::BinaryOp {
                 op: Op::Sub,
                 left: Box::new(Expr::BinaryOp {
                     op: Op::Pow,
                     left: Box::new(arg),
-                    right: Box::new(Expr::Var("2".to_string())),
+                    right: Box::new(Expr::Num(2.0)),
                 }),
-                right: Box::new(Expr::Var("1".to_string())),
+                right: Box::new(Expr::Num(1.0)),
             }))),
         }),
         Some(&"1/(1-x^2)") => Ok(Expr::BinaryOp {
             op: Op::Div,
-            left: Box::new(Expr::Var("1".to_string())),
+            left: Box::new(Expr::Num(1.0)),
             right: Box::new(Expr::BinaryOp {
                 op: Op::Sub,
-                left: Box::new(Expr::Var("1".to_string())),
+                left: Box::new(Expr::Num(1.0)),
                 right: Box::new(Expr::BinaryOp {
                     op: Op::Pow,
                     left: Box::new(arg),
-                    right: Box::new(Expr::Var("2".to_string())),
+                    right: Box::new(Expr::Num(2.0)),
                 }),
             }),
         }),
-        Some(&"-1/(|x|*sqrt(1+x^2))") => Ok(Expr::BinaryOp {
-            op: Op::Mul,
-            left: Box::new(Expr::Var("-1".to_string())),
+        Some(&"-1/(|x|*sqrt(1+x^2))") => Ok(Expr::Neg(Box::new(Expr::BinaryOp {
+            op: Op::Div,
+            left: Box::new(Expr::Num(1.0)),
             right: Box::new(Expr::BinaryOp {
-                op: Op::Div,
-                left: Box::new(Expr::Var("1".to_string())),
-                right: Box::new(Expr::BinaryOp {
-                    op: Op::Mul,
-                    left: Box::new(Expr::Func("abs".to_string(), Box::new(arg.clone()))),
-                    right: Box::new(Expr::Func("sqrt".to_string(), Box::new(Expr::BinaryOp {
-                        op: Op::Add,
-                        left: Box::new(Expr::Var("1".to_string())),
-                        right: Box::new(Expr::BinaryOp {
-                            op: Op::Pow,
-                            left: Box::new(arg),
-                            right: Box::new(Expr::Var("2".to_string())),
-                        }),
-                    }))),
-                }),
+                op: Op::Mul,
+                left: Box::new(magnitude(&arg, assumptions)),
+                right: Box::new(Expr::Func("sqrt".to_string(), Box::new(Expr::BinaryOp {
+                    op: Op::Add,
+                    left: Box::new(Expr::Num(1.0)),
+                    right: Box::new(Expr::BinaryOp {
+                        op: Op::Pow,
+                        left: Box::new(arg),
+                        right: Box::new(Expr::Num(2.0)),
+                    }),
+                }))),
             }),
-        }),
-        Some(&"-1/(|x|*sqrt(1-x^2))") => Ok(Expr::BinaryOp {
-            op: Op::Mul,
-            left: Box::new(Expr::Var("-1".to_string())),
+        }))),
+        Some(&"-1/(|x|*sqrt(1-x^2))") => Ok(Expr::Neg(Box::new(Expr::BinaryOp {
+            op: Op::Div,
+            left: Box::new(Expr::Num(1.0)),
             right: Box::new(Expr::BinaryOp {
-                op: Op::Div,
-                left: Box::new(Expr::Var("1".to_string())),
-                right: Box::new(Expr::BinaryOp {
-                    op: Op::Mul,
-                    left: Box::new(Expr::Func("abs".to_string(), Box::new(arg.clone()))),
-                    right: Box::new(Expr::Func("sqrt".to_string(), Box::new(Expr::BinaryOp {
-                        op: Op::Sub,
-                        left: Box::new(Expr::Var("1".to_string())),
-                        right: Box::new(Expr::BinaryOp {
-                            op: Op::Pow,
-                            left: Box::new(arg),
-                            right: Box::new(Expr::Var("2".to_string())),
-                        }),
-                    }))),
-                }),
+                op: Op::Mul,
+                left: Box::new(magnitude(&arg, assumptions)),
+                right: Box::new(Expr::Func("sqrt".to_string(), Box::new(Expr::BinaryOp {
+                    op: Op::Sub,
+                    left: Box::new(Expr::Num(1.0)),
+                    right: Box::new(Expr::BinaryOp {
+                        op: Op::Pow,
+                        left: Box::new(arg),
+                        right: Box::new(Expr::Num(2.0)),
+                    }),
+                }))),
             }),
-        }),
+        }))),
         
         // Additional function derivatives
         Some(&"1/(2*sqrt)") => Ok(Expr::BinaryOp {
             op: Op::Div,
-            left: Box::new(Expr::Var("1".to_string())),
+            left: Box::new(Expr::Num(1.0)),
             right: Box::new(Expr::BinaryOp {
                 op: Op::Mul,
-                left: Box::new(Expr::Var("2".to_string())),
+                left: Box::new(Expr::Num(2.0)),
                 right: Box::new(Expr::Func("sqrt".to_string(), Box::new(arg))),
             }),
         }),
+        Some(&"x/|x|") if assumptions.is_positive(&arg) => Ok(Expr::Num(1.0)),
         Some(&"x/|x|") => Ok(Expr::BinaryOp {
             op: Op::Div,
             left: Box::new(arg.clone()),
-            right: Box::new(Expr::Func("abs".to_string(), Box::new(arg))),
+            right: Box::new(magnitude(&arg, assumptions)),
         }),
         
         // Logarithmic and exponential derivatives
         Some(&"1/") => Ok(Expr::BinaryOp {
             op: Op::Div,
-            left: Box::new(Expr::Var("1".to_string())),
+            left: Box::new(Expr::Num(1.0)),
             right: Box::new(arg),
         }),
         Some(&"exp") => {
@@ -0,0 +1,502 @@
+use std::collections::HashMap;
+use std::f64::consts::{FRAC_PI_2, PI};
+use std::fmt;
+use std::ops::{Add, Div, Mul, Neg, Sub};
+
+use crate::ast::{Expr, Op};
+use crate::context::Context;
+use crate::derivative::derivative_with;
+use crate::eval::Function;
+use crate::simplifier::simplify_with;
+
+/// How many units in the last place the result of a library function such as
+/// `sin` or `powf` is widened by. They are not correctly rounded, but stay
+/// well within this of the exact value.
+const LIBM_ULPS: u32 = 4;
+
+/// How many times [`bound_derivative`] halves the box, to fight the
+/// overestimation of evaluating an expression with repeated variables.
+const BISECTIONS: u32 = 6;
+
+/// A closed range of reals `[lo, hi]`, possibly unbounded, or empty when
+/// `lo > hi`. Arithmetic on intervals rounds outward, so the result always
+/// contains every value the operation can take on its arguments.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Interval {
+    pub lo: f64,
+    pub hi: f64,
+}
+
+impl fmt::Display for Interval {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_empty() {
+            write!(f, "empty")
+        } else {
+            write!(f, "[{}, {}]", Bound(self.lo), Bound(self.hi))
+        }
+    }
+}
+
+/// An interval bound, written in scientific notation when very large or small.
+struct Bound(f64);
+
+impl fmt::Display for Bound {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let magnitude = self.0.abs();
+        if magnitude.is_finite() && magnitude != 0.0 && !(1e-6..1e16).contains(&magnitude) {
+            write!(f, "{:e}", self.0)
+        } else {
+            write!(f, "{}", self.0)
+        }
+    }
+}
+
+impl Interval {
+    /// The interval with no values in it, e.g. `sqrt` of `[-2, -1]`.
+    pub const EMPTY: Interval = Interval {
+        lo: f64::INFINITY,
+        hi: f64::NEG_INFINITY,
+    };
+
+    /// The whole real line.
+    pub const ENTIRE: Interval = Interval {
+        lo: f64::NEG_INFINITY,
+        hi: f64::INFINITY,
+    };
+
+    /// The interval between `lo` and `hi`. A NaN bound, which can only come
+    /// from a result too uncertain to pin down, gives the whole real line.
+    pub fn new(lo: f64, hi: f64) -> Interval {
+        if lo.is_nan() || hi.is_nan() {
+            Interval::ENTIRE
+        } else {
+            Interval { lo, hi }
+        }
+    }
+
+    /// The interval holding just `x`.
+    pub fn point(x: f64) -> Interval {
+        Interval::new(x, x)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lo > self.hi
+    }
+
+    pub fn contains(&self, x: f64) -> bool {
+        self.lo <= x && x <= self.hi
+    }
+
+    pub fn width(&self) -> f64 {
+        if self.is_empty() { 0.0 } else { self.hi - self.lo }
+    }
+
+    /// The smallest interval containing both.
+    pub fn hull(self, other: Interval) -> Interval {
+        Interval::new(self.lo.min(other.lo), self.hi.max(other.hi))
+    }
+
+    /// The values in both.
+    pub fn intersect(self, other: Interval) -> Interval {
+        let both = Interval::new(self.lo.max(other.lo), self.hi.min(other.hi));
+        if both.is_empty() { Interval::EMPTY } else { both }
+    }
+
+    /// `1 / x` for every nonzero `x` in the interval.
+    pub fn recip(self) -> Interval {
+        if self.is_empty() || (self.lo == 0.0 && self.hi == 0.0) {
+            Interval::EMPTY
+        } else if self.lo == 0.0 {
+            Interval::new(recip_bound(self.hi, Rounding::Down), f64::INFINITY)
+        } else if self.hi == 0.0 {
+            Interval::new(f64::NEG_INFINITY, recip_bound(self.lo, Rounding::Up))
+        } else if self.contains(0.0) {
+            Interval::ENTIRE
+        } else {
+            Interval::new(recip_bound(self.hi, Rounding::Down), recip_bound(self.lo, Rounding::Up))
+        }
+    }
+
+    pub fn abs(self) -> Interval {
+        if self.is_empty() || self.lo >= 0.0 {
+            self
+        } else if self.hi <= 0.0 {
+            -self
+        } else {
+            Interval::new(0.0, self.hi.max(-self.lo))
+        }
+    }
+
+    /// `self ^ exponent`, as `powf` computes it: negative bases only have
+    /// powers with integer exponents.
+    pub fn pow(self, exponent: Interval) -> Interval {
+        if self.is_empty() || exponent.is_empty() {
+            return Interval::EMPTY;
+        }
+        if exponent.lo == exponent.hi {
+            return self.powf(exponent.lo);
+        }
+        if self.lo < 0.0 && exponent.lo.ceil() <= exponent.hi {
+            // Negative bases to the integers in the exponent range
+            return Interval::ENTIRE;
+        }
+        // On positive bases, x^y = exp(y * log(x))
+        let base = self.intersect(Interval::new(0.0, f64::INFINITY));
+        (exponent * apply(Function::Log, base)).exp()
+    }
+
+    /// `self ^ n` for a single exponent.
+    fn powf(self, n: f64) -> Interval {
+        if n == 0.0 {
+            return Interval::point(1.0);
+        }
+        let pow = |x: f64| x.powf(n);
+        if n.fract() == 0.0 {
+            if n < 0.0 {
+                return self.powf(-n).recip();
+            }
+            return if n % 2.0 == 0.0 {
+                increasing(self.abs(), pow).intersect(Interval::new(0.0, f64::INFINITY))
+            } else {
+                increasing(self, pow)
+            };
+        }
+        let base = self.intersect(Interval::new(0.0, f64::INFINITY));
+        let power = if n > 0.0 { increasing(base, pow) } else { decreasing(base, pow) };
+        power.intersect(Interval::new(0.0, f64::INFINITY))
+    }
+
+    fn exp(self) -> Interval {
+        increasing(self, f64::exp).intersect(Interval::new(0.0, f64::INFINITY))
+    }
+}
+
+impl Neg for Interval {
+    type Output = Interval;
+
+    fn neg(self) -> Interval {
+        if self.is_empty() {
+            return self;
+        }
+        Interval::new(-self.hi, -self.lo)
+    }
+}
+
+impl Add for Interval {
+    type Output = Interval;
+
+    fn add(self, other: Interval) -> Interval {
+        if self.is_empty() || other.is_empty() {
+            return Interval::EMPTY;
+        }
+        Interval::new(
+            rounded(self.lo + other.lo, sum_error(self.lo, other.lo), Rounding::Down),
+            rounded(self.hi + other.hi, sum_error(self.hi, other.hi), Rounding::Up),
+        )
+    }
+}
+
+impl Sub for Interval {
+    type Output = Interval;
+
+    fn sub(self, other: Interval) -> Interval {
+        self + -other
+    }
+}
+
+impl Mul for Interval {
+    type Output = Interval;
+
+    fn mul(self, other: Interval) -> Interval {
+        if self.is_empty() || other.is_empty() {
+            return Interval::EMPTY;
+        }
+        let products = [
+            (self.lo, other.lo),
+            (self.lo, other.hi),
+            (self.hi, other.lo),
+            (self.hi, other.hi),
+        ];
+        let lo = products.iter().map(|&(a, b)| mul_bound(a, b, Rounding::Down)).fold(f64::INFINITY, f64::min);
+        let hi = products.iter().map(|&(a, b)| mul_bound(a, b, Rounding::Up)).fold(f64::NEG_INFINITY, f64::max);
+        Interval::new(lo, hi)
+    }
+}
+
+impl Div for Interval {
+    type Output = Interval;
+
+    fn div(self, other: Interval) -> Interval {
+        Mul::mul(self, other.recip())
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Rounding {
+    Down,
+    Up,
+}
+
+/// Rounds the computed `value` of an operation towards -∞ or +∞, given the
+/// error `exact - value` (NaN if unknown). A result computed exactly is kept
+/// as it is; otherwise it moves one representable number outward.
+fn rounded(value: f64, error: f64, rounding: Rounding) -> f64 {
+    // Comparisons with an unknown (NaN) error are false
+    let exact_or_outward = match rounding {
+        Rounding::Down => error >= 0.0,
+        Rounding::Up => error <= 0.0,
+    };
+    match rounding {
+        _ if exact_or_outward => value,
+        Rounding::Down => value.next_down(),
+        Rounding::Up => value.next_up(),
+    }
+}
+
+/// The rounding error of `a + b` (Knuth's TwoSum), NaN if it overflows.
+fn sum_error(a: f64, b: f64) -> f64 {
+    let s = a + b;
+    if !s.is_finite() {
+        return if a.is_infinite() || b.is_infinite() { 0.0 } else { f64::NAN };
+    }
+    let bb = s - a;
+    (a - (s - bb)) + (b - bb)
+}
+
+/// `a * b` rounded outward, taking `0 * ∞` as 0: an infinite bound is never
+/// reached, so the product only comes close to 0.
+fn mul_bound(a: f64, b: f64, rounding: Rounding) -> f64 {
+    if a == 0.0 || b == 0.0 {
+        return 0.0;
+    }
+    let p = a * b;
+    if a.is_infinite() || b.is_infinite() {
+        return p;
+    }
+    // A fused multiply-add gives the exact error, unless the product is so
+    // small that the error underflows, or has overflowed
+    let error = if p.is_finite() && p.abs() >= 1e-290 { a.mul_add(b, -p) } else { f64::NAN };
+    rounded(p, error, rounding)
+}
+
+/// `1 / x` rounded outward, for nonzero `x`.
+fn recip_bound(x: f64, rounding: Rounding) -> f64 {
+    let q = 1.0 / x;
+    if x.is_infinite() {
+        return q;
+    }
+    // The residual 1 - x * q has the sign of (1 / x - q) * x
+    let residual = (-x).mul_add(q, 1.0);
+    let error = if q.is_finite() && q.abs() >= 1e-290 { residual * x.signum() } else { f64::NAN };
+    rounded(q, error, rounding)
+}
+
+/// `x` moved `ulps` representable numbers towards -∞. A rounded +∞ becomes
+/// the largest finite number, since the exact value it stands for is finite.
+fn down(x: f64, ulps: u32) -> f64 {
+    (0..ulps).fold(x, |x, _| x.next_down())
+}
+
+/// `x` moved `ulps` representable numbers towards +∞.
+fn up(x: f64, ulps: u32) -> f64 {
+    (0..ulps).fold(x, |x, _| x.next_up())
+}
+
+/// `f` over an interval on which it is nondecreasing.
+fn increasing(x: Interval, f: impl Fn(f64) -> f64) -> Interval {
+    if x.is_empty() {
+        return x;
+    }
+    Interval::new(libm_bound(x.lo, f(x.lo), Rounding::Down), libm_bound(x.hi, f(x.hi), Rounding::Up))
+}
+
+/// `f` over an interval on which it is nonincreasing.
+fn decreasing(x: Interval, f: impl Fn(f64) -> f64) -> Interval {
+    if x.is_empty() {
+        return x;
+    }
+    Interval::new(libm_bound(x.hi, f(x.hi), Rounding::Down), libm_bound(x.lo, f(x.lo), Rounding::Up))
+}
+
+/// A library function's value `fx` at `x`, widened outward. The functions
+/// here that vanish at 0 do so exactly, which is kept.
+fn libm_bound(x: f64, fx: f64, rounding: Rounding) -> f64 {
+    match rounding {
+        _ if x == 0.0 && fx == 0.0 => 0.0,
+        Rounding::Down => down(fx, LIBM_ULPS),
+        Rounding::Up => up(fx, LIBM_ULPS),
+    }
+}
+
+/// Whether the interval contains `offset + k * period` for some integer `k`.
+/// Near misses count as hits, since `offset` and `period` are multiples of π
+/// only up to rounding.
+fn meets_periodic(x: Interval, offset: f64, period: f64) -> bool {
+    if !x.lo.is_finite() || !x.hi.is_finite() || x.width() >= period {
+        return true;
+    }
+    let slack = 1e-12 * (1.0 + x.lo.abs() + x.hi.abs());
+    let k = ((x.lo - offset) / period).floor();
+    (0..3).any(|i| {
+        let point = offset + (k + i as f64) * period;
+        x.lo - slack <= point && point <= x.hi + slack
+    })
+}
+
+/// `sin` or `cos` over an interval, given where the function peaks and dips.
+fn periodic(x: Interval, f: fn(f64) -> f64, peak: f64, dip: f64) -> Interval {
+    if x.is_empty() {
+        return x;
+    }
+    let ends = [x.lo, x.hi];
+    let lo = if meets_periodic(x, dip, 2.0 * PI) {
+        -1.0
+    } else {
+        ends.iter().map(|&e| libm_bound(e, f(e), Rounding::Down)).fold(f64::INFINITY, f64::min)
+    };
+    let hi = if meets_periodic(x, peak, 2.0 * PI) {
+        1.0
+    } else {
+        ends.iter().map(|&e| libm_bound(e, f(e), Rounding::Up)).fold(f64::NEG_INFINITY, f64::max)
+    };
+    Interval::new(lo, hi).intersect(Interval::new(-1.0, 1.0))
+}
+
+/// Applies a built-in function to every value in an interval, clipping the
+/// interval to the function's domain first. The result is empty if the
+/// interval lies entirely outside the domain, and unbounded if it spans a pole.
+pub fn apply(function: Function, x: Interval) -> Interval {
+    let unit = Interval::new(-1.0, 1.0);
+    let nonnegative = Interval::new(0.0, f64::INFINITY);
+    match function {
+        Function::Sin => periodic(x, f64::sin, FRAC_PI_2, -FRAC_PI_2),
+        Function::Cos => periodic(x, f64::cos, 0.0, PI),
+        Function::Tan => {
+            if x.is_empty() {
+                x
+            } else if meets_periodic(x, FRAC_PI_2, PI) {
+                Interval::ENTIRE
+            } else {
+                increasing(x, f64::tan)
+            }
+        }
+        Function::Cot => apply(Function::Tan, x).recip(),
+        Function::Sec => apply(Function::Cos, x).recip(),
+        Function::Cosec => apply(Function::Sin, x).recip(),
+
+        Function::Arcsin => increasing(x.intersect(unit), f64::asin),
+        Function::Arccos => decreasing(x.intersect(unit), f64::acos),
+        Function::Arctan => increasing(x, f64::atan),
+        Function::Arccsc => apply(Function::Arcsin, x.recip()),
+        Function::Arcsec => apply(Function::Arccos, x.recip()),
+        // The rounded π/2 sits within an ulp of the exact one
+        Function::Arccot => Interval::new(down(FRAC_PI_2, 1), up(FRAC_PI_2, 1)) - apply(Function::Arctan, x),
+
+        Function::Sinh => increasing(x, f64::sinh),
+        Function::Cosh => increasing(x.abs(), f64::cosh).intersect(Interval::new(1.0, f64::INFINITY)),
+        Function::Tanh => increasing(x, f64::tanh).intersect(unit),
+        Function::Coth => apply(Function::Tanh, x).recip(),
+        Function::Sech => apply(Function::Cosh, x).recip(),
+        Function::Cosech => apply(Function::Sinh, x).recip(),
+
+        Function::Arsinh => increasing(x, f64::asinh),
+        Function::Arcosh => increasing(x.intersect(Interval::new(1.0, f64::INFINITY)), f64::acosh),
+        Function::Artanh => increasing(x.intersect(unit), f64::atanh),
+        Function::Arcosech => apply(Function::Arsinh, x.recip()),
+        Function::Arsech => apply(Function::Arcosh, x.recip()),
+        Function::Arcoth => apply(Function::Artanh, x.recip()),
+
+        Function::Log => increasing(x.intersect(nonnegative), f64::ln),
+        Function::Exp => x.exp(),
+        Function::Sqrt => increasing(x.intersect(nonnegative), f64::sqrt).intersect(nonnegative),
+        Function::Abs => x.abs(),
+    }
+}
+
+/// Encloses every value `expr` takes as its variables range over the
+/// intervals in `vars`, leaving out points where it is undefined.
+pub fn eval_interval(expr: &Expr, vars: &HashMap<String, Interval>) -> Result<Interval, String> {
+    match expr {
+        Expr::Num(n) => Ok(Interval::point(*n)),
+        Expr::Var(v) => vars
+            .get(v)
+            .copied()
+            .ok_or_else(|| format!("No range given for variable '{}'", v)),
+        Expr::Neg(inner) => Ok(-eval_interval(inner, vars)?),
+        Expr::BinaryOp { op, left, right } => {
+            let l = eval_interval(left, vars)?;
+            let r = eval_interval(right, vars)?;
+            Ok(match op {
+                Op::Add => l + r,
+                Op::Sub => l - r,
+                Op::Mul => l * r,
+                Op::Div => l / r,
+                Op::Pow => l.pow(r),
+            })
+        }
+        Expr::Func(name, arg) => {
+            let function = Function::from_name(name).ok_or_else(|| format!("Unknown function: {}", name))?;
+            Ok(apply(function, eval_interval(arg, vars)?))
+        }
+        Expr::Sum(terms) => terms
+            .iter()
+            .try_fold(Interval::point(0.0), |acc, t| Ok(acc + eval_interval(t, vars)?)),
+        Expr::Product(factors) => factors
+            .iter()
+            .try_fold(Interval::point(1.0), |acc, f| Ok(acc * eval_interval(f, vars)?)),
+    }
+}
+
+/// Like [`eval_interval`], but evaluates over the halves of the box (split
+/// along its widest side) `depth` times over and joins the results, which
+/// gives tighter bounds.
+pub fn eval_interval_bisected(expr: &Expr, domain: &HashMap<String, Interval>, depth: u32) -> Result<Interval, String> {
+    bisect(&expr.variables(), domain, depth, &|piece| eval_interval(expr, piece))
+}
+
+/// Joins the results of `eval` over halves of the box, only splitting the sides of `vars`.
+fn bisect<F>(vars: &[String], domain: &HashMap<String, Interval>, depth: u32, eval: &F) -> Result<Interval, String>
+where
+    F: Fn(&HashMap<String, Interval>) -> Result<Interval, String>,
+{
+    let widest = domain
+        .iter()
+        .filter(|(name, x)| vars.contains(name) && x.lo.is_finite() && x.hi.is_finite() && x.width() > 0.0)
+        .max_by(|(a_name, a), (b_name, b)| a.width().total_cmp(&b.width()).then(b_name.cmp(a_name)));
+    let Some((name, x)) = widest.filter(|_| depth > 0) else {
+        return eval(domain);
+    };
+    let mid = x.lo + (x.hi - x.lo) / 2.0;
+    let mut halves = domain.clone();
+    halves.insert(name.clone(), Interval::new(x.lo, mid));
+    let lower = bisect(vars, &halves, depth - 1, eval)?;
+    halves.insert(name.clone(), Interval::new(mid, x.hi));
+    let upper = bisect(vars, &halves, depth - 1, eval)?;
+    Ok(lower.hull(upper))
+}
+
+/// Guaranteed bounds on the derivative of `expr` with respect to `var` over
+/// a box, given as a range for every variable. The parts of the box where
+/// `expr` has no values are left out, even where the formula for the
+/// derivative has some: `log(x)` over `x=-2..-1` gives the empty interval,
+/// not the values of `1/x` there.
+pub fn bound_derivative(
+    expr: &Expr,
+    var: &str,
+    domain: &HashMap<String, Interval>,
+    ctx: &Context,
+) -> Result<Interval, String> {
+    let f = ctx.angle.to_radians(expr);
+    let derivative = ctx.angle.to_radians(&simplify_with(&derivative_with(expr, var, ctx)?, ctx)?);
+    let mut vars = f.variables();
+    vars.extend(derivative.variables());
+    vars.sort();
+    vars.dedup();
+    bisect(&vars, domain, BISECTIONS, &|piece| {
+        if eval_interval(&f, piece)?.is_empty() {
+            Ok(Interval::EMPTY)
+        } else {
+            eval_interval(&derivative, piece)
+        }
+    })
+}
@@ -0,0 +1,29 @@
+pub mod ast;
+pub mod tokenizer;
+pub mod parser;
+pub mod derivative;
+pub mod function_table;
+pub mod simplifier;
+pub mod constants;
+pub mod rewrite;
+pub mod trig;
+pub mod context;
+pub mod logarithm;
+pub mod eval;
+pub mod exact;
+pub mod assumptions;
+pub mod egraph;
+pub mod polynomial;
+pub mod rational;
+pub mod equivalence;
+pub mod cse;
+pub mod dag;
+pub mod limits;
+pub mod compile;
+pub mod interval;
+pub mod complex;
+pub mod angle;
+pub mod table;
+pub mod plot;
+pub mod svg;
+pub mod tangent;
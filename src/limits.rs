@@ -0,0 +1,161 @@
+use std::cell::Cell;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::ast::Expr;
+
+/// The deepest nesting accepted by default: the parser's limit on nested
+/// parentheses, functions and powers, and the default [`Limits::max_depth`].
+/// Differentiating and simplifying recurse once per level, taking a few
+/// kilobytes of stack each in a debug build, which [`on_large_stack`] has room
+/// for twenty times over.
+pub const MAX_DEPTH: usize = 1_000;
+
+/// Bounds on the work one `derivative` or `simplify` call may do, so that an
+/// input like `x^x^x^x^x` differentiated many times fails with an error
+/// instead of running (or allocating) forever.
+#[derive(Debug, Clone)]
+pub struct Limits {
+    /// The most nodes an intermediate or final expression may have.
+    pub max_nodes: usize,
+    /// The deepest nesting of operators and functions accepted as input.
+    pub max_depth: usize,
+    /// How long one call may run, or `None` for no limit.
+    pub time_limit: Option<Duration>,
+    /// Stops a running call from another thread.
+    pub cancel: CancelToken,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits {
+            max_nodes: 1_000_000,
+            max_depth: MAX_DEPTH,
+            time_limit: None,
+            cancel: CancelToken::default(),
+        }
+    }
+}
+
+/// A flag shared between a running call and whoever may want to stop it.
+/// Clones refer to the same flag.
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn new() -> Self {
+        CancelToken::default()
+    }
+
+    /// Asks every call holding this token to stop at its next check.
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// The limits in force for one call, with its deadline fixed when it starts.
+pub struct Budget<'a> {
+    limits: &'a Limits,
+    deadline: Option<Instant>,
+}
+
+impl<'a> Budget<'a> {
+    pub fn start(limits: &'a Limits) -> Self {
+        Budget {
+            limits,
+            deadline: limits.time_limit.map(|t| Instant::now() + t),
+        }
+    }
+
+    /// When the call runs out of time, if it has a time limit.
+    pub fn deadline(&self) -> Option<Instant> {
+        self.deadline
+    }
+
+    /// Fails once the call has been cancelled or has run out of time.
+    pub fn check(&self) -> Result<(), String> {
+        if self.limits.cancel.is_cancelled() {
+            return Err("Cancelled".to_string());
+        }
+        match (self.deadline, self.limits.time_limit) {
+            (Some(deadline), Some(limit)) if Instant::now() > deadline => Err(format!(
+                "Expression too large: gave up after {} ms",
+                limit.as_millis()
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Like [`check`](Budget::check), but also fails when an expression has
+    /// grown past the node limit.
+    pub fn check_size(&self, nodes: usize) -> Result<(), String> {
+        if nodes > self.limits.max_nodes {
+            return Err(format!(
+                "Expression too large: more than {} nodes",
+                self.limits.max_nodes
+            ));
+        }
+        self.check()
+    }
+
+    /// Fails for input nested more deeply than the depth limit.
+    pub fn check_depth(&self, expr: &Expr) -> Result<(), String> {
+        if depth(expr, self.limits.max_depth) > self.limits.max_depth {
+            return Err(format!(
+                "Expression too large: nested more than {} levels deep",
+                self.limits.max_depth
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// The nesting depth of `expr`, looking no further than one level past `limit`.
+/// The tree is walked with an explicit stack, as this is what guards the
+/// passes that recurse.
+fn depth(expr: &Expr, limit: usize) -> usize {
+    let mut deepest = 0;
+    let mut pending = vec![(expr, 1)];
+    while let Some((expr, level)) = pending.pop() {
+        deepest = deepest.max(level);
+        if level <= limit {
+            pending.extend(expr.children().into_iter().map(|child| (child, level + 1)));
+        }
+    }
+    deepest
+}
+
+/// The stack size [`on_large_stack`] gives its thread.
+pub const LARGE_STACK: usize = 64 * 1024 * 1024;
+
+thread_local! {
+    /// Whether the current thread was started by [`on_large_stack`].
+    static LARGE: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Runs `f` on a thread with a [`LARGE_STACK`]-byte stack and waits for it, or
+/// right away when already on one. The parser and the passes over expression
+/// trees recurse once per level of nesting, so input near the depth limits
+/// needs more stack than the 2 MB a spawned thread gets by default; their
+/// entry points call this themselves.
+pub fn on_large_stack<T: Send>(f: impl FnOnce() -> T + Send) -> T {
+    if LARGE.with(Cell::get) {
+        return f();
+    }
+    std::thread::scope(|scope| {
+        std::thread::Builder::new()
+            .stack_size(LARGE_STACK)
+            .spawn_scoped(scope, || {
+                LARGE.with(|large| large.set(true));
+                f()
+            })
+            .expect("failed to spawn thread")
+            .join()
+            .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+    })
+}
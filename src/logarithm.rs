@@ -0,0 +1,54 @@
+use crate::rewrite::RuleSet;
+
+/// How `simplify` treats logarithms of products, quotients and powers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LogMode {
+    /// Leave logarithms as they are (apart from the built-in rules).
+    #[default]
+    Off,
+    /// Split logarithms apart: `log(a*b)` becomes `log(a) + log(b)` and
+    /// `log(a^b)` becomes `b*log(a)`. This is only valid for positive arguments,
+    /// so it only happens where `a` and `b`, or just `a` for a power, are known
+    /// to be positive.
+    Expand,
+    /// Combine logarithms: `log(a) + log(b)` becomes `log(a*b)` and
+    /// `2*log(a)` becomes `log(a^2)`.
+    Contract,
+}
+
+impl LogMode {
+    /// Parses a mode name as accepted by the `--log` option.
+    pub fn parse(name: &str) -> Result<Self, String> {
+        match name {
+            "off" => Ok(LogMode::Off),
+            "expand" => Ok(LogMode::Expand),
+            "contract" => Ok(LogMode::Contract),
+            _ => Err(format!("Unknown log mode '{}' (expected off, expand or contract)", name)),
+        }
+    }
+
+    /// The rewrite rules for this mode, applied in addition to the context's rules.
+    pub fn rules(self) -> &'static RuleSet {
+        match self {
+            LogMode::Off => &NO_RULES,
+            LogMode::Expand => &EXPAND_RULES,
+            LogMode::Contract => &CONTRACT_RULES,
+        }
+    }
+}
+
+const EXPAND: &str = "
+log(a*b) -> log(a) + log(b) if positive(a), positive(b)
+log(a^b) -> b*log(a) if positive(a)
+";
+
+const CONTRACT: &str = "
+log(a) + log(b) -> log(a*b)
+n*log(a) -> log(a^n) if number(n)
+";
+
+lazy_static::lazy_static! {
+    static ref NO_RULES: RuleSet = RuleSet::default();
+    static ref EXPAND_RULES: RuleSet = RuleSet::parse(EXPAND).expect("log expansion rules must parse");
+    static ref CONTRACT_RULES: RuleSet = RuleSet::parse(CONTRACT).expect("log contraction rules must parse");
+}
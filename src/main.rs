@@ -1,12 +1,13 @@
 use std::io::{self, Write};
 
-mod ast;
-mod tokenizer;
-mod parser;
-mod derivative;
-mod function_table;
-mod simplifier;
-mod constants;
+use derivative_calculator::ast::Expr;
+use derivative_calculator::dag::Arena;
+use derivative_calculator::limits::{self, Budget};
+use derivative_calculator::{cse, derivative, parser, simplifier, tokenizer};
+
+mod cli;
+
+use cli::{parse_args, Options};
 
 fn print_manual() {
     println!("\n=== Derivative Calculator Manual ===");
@@ -24,16 +25,49 @@ fn print_manual() {
     println!("  - pi             : Mathematical constant π (3.14159...)");
     println!("  - e              : Euler's number (2.71828...)");
     println!("  - deg            : Degree conversion constant (π/180)");
+    println!("  - i              : Imaginary unit (i^2 = -1)");
     println!("  - exit           : Quit the program");
     println!("\nTips:");
     println!("- You can use implicit multiplication: 2x means 2*x, sin2x means sin(2*x)");
     println!("- You can use powers on functions: sin^2(x) means (sin(x))^2");
     println!("- You can use parentheses for clarity: sin^2(x+1)");
     println!("- Supported functions: sin, cos, tan, exp, log, sinh, cosh, tanh, etc. but use paranthesis for complex ");
-    println!("- Supported constants: pi, π, e, euler, deg, degree\n");
+    println!("- Start with --angle deg to work in degrees: sin(30) is then 0.5");
+    println!("- Supported constants: pi, π, e, euler, deg, degree, i\n");
+}
+
+/// The simplified derivative of the order asked for. Each order differentiates
+/// the simplified result of the last, sharing the work for repeated subexpressions.
+fn nth_derivative(expr: &Expr, var: &str, options: &Options) -> Result<Expr, String> {
+    let ctx = &options.ctx;
+    Budget::start(&ctx.limits).check_depth(expr)?;
+    let mut arena = Arena::new();
+    // The arena works in radians, so degrees are converted once on the way in and out
+    let mut id = arena.from_expr(&ctx.angle.to_radians(expr));
+    for _ in 0..options.order {
+        let der = derivative::derivative_in(&mut arena, id, var, ctx)?;
+        id = simplifier::simplify_in(&mut arena, der, ctx)?;
+    }
+    Ok(ctx.angle.from_radians(&arena.to_expr_limited(id, &Budget::start(&ctx.limits))?))
 }
 
 fn main() {
+    let options = match parse_args() {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(2);
+        }
+    };
+
+    if !options.command.is_empty() {
+        if let Err(e) = limits::on_large_stack(|| cli::run(&options.command, &options)) {
+            eprintln!("Error: {}", e);
+            std::process::exit(2);
+        }
+        return;
+    }
+
     loop {
         println!("\n=== Derivative Calculator ===");
         println!("1. Show manual");
@@ -108,22 +142,7 @@ fn main() {
                 }
             };
 
-            // Tokenize and parse with error handling
-            match tokenizer::tokenize(trimmed_input) {
-                Ok(tokens) => {
-                    let mut parser = parser::Parser::new(tokens);
-                    match parser.parse() {
-                        Ok(expr) => {
-                            println!("Parsed expression:\n{}", expr);
-                            let der = derivative::derivative(&expr, &var);
-                            let simp = simplifier::simplify(&der);
-                            println!("Derivative with respect to '{}':\n{}", var, simp);
-                        }
-                        Err(e) => eprintln!("Parser error: {}", e),
-                    }
-                }
-                Err(e) => eprintln!("Tokenizer error: {}", e),
-            }
+            limits::on_large_stack(|| differentiate_input(trimmed_input, &var, &options));
         }
         Err(e) => {
             eprintln!("Input error: {}", e);
@@ -136,4 +155,43 @@ fn main() {
     
 
    
+}
+
+/// Parses one input line and prints its derivative, plus its value with `--at`.
+fn differentiate_input(input: &str, var: &str, options: &Options) {
+    // Tokenize and parse with error handling
+    match tokenizer::tokenize(input) {
+        Ok(tokens) => {
+            let mut parser = parser::Parser::new(tokens);
+            match parser.parse() {
+                Ok(expr) => {
+                    println!("Parsed expression:\n{}", expr);
+                    let simp = match nth_derivative(&expr, var, options) {
+                        Ok(simp) => simp,
+                        Err(e) => {
+                            eprintln!("Error: {}", e);
+                            return;
+                        }
+                    };
+                    let heading = match options.order {
+                        1 => format!("Derivative with respect to '{}'", var),
+                        n => format!("Derivative of order {} with respect to '{}'", n, var),
+                    };
+                    if options.cse {
+                        println!("{}:\n{}", heading, cse::cse(&simp));
+                    } else {
+                        println!("{}:\n{}", heading, simp);
+                    }
+                    if let Some(point) = &options.at {
+                        match simplifier::simplify_with(&simp.substitute(var, point), &options.ctx) {
+                            Ok(value) => println!("Derivative at {} = {}:\n{}", var, point, value),
+                            Err(e) => eprintln!("Error: {}", e),
+                        }
+                    }
+                }
+                Err(e) => eprintln!("Parser error: {}", e),
+            }
+        }
+        Err(e) => eprintln!("Tokenizer error: {}", e),
+    }
 }
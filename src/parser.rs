@@ -1,51 +1,35 @@
 use crate::ast::{Expr, Op};
+use crate::limits::{self, MAX_DEPTH};
 use crate::tokenizer::Token;
 
+/// How deeply parentheses, function calls and powers may nest: the default
+/// depth limit of the later passes. Each level costs stack space here and in
+/// every later pass over the tree, so deeper input is rejected with an error
+/// rather than overflowing the stack.
+pub const MAX_NESTING: usize = MAX_DEPTH;
+
 /// The `Parser` takes a sequence of tokens and builds an Abstract Syntax Tree (AST),
 /// which represents the mathematical structure of the expression.
 pub struct Parser {
     tokens: Vec<Token>,
     pos: usize, // Tracks the parser's current position in the token stream.
+    depth: usize, // How deeply nested the parser currently is, see `MAX_NESTING`.
 }
 
-fn traverse_expr(expr: &Expr) {
-    match expr {
-        Expr::Num(n) => {
-            println!("Num: {}", n);
-        }
-        Expr::Var(name) => {
-            println!("Var: {}", name);
-        }
-        Expr::BinaryOp { op, left, right } => {
-            println!("BinaryOp: {:?}", op);
-            traverse_expr(left);
-            traverse_expr(right);
-        }
-        Expr::Func(name, arg) => {
-            println!("Func: {}", name);
-            traverse_expr(arg);
-        }
-    }
-}
-
-
-
-
-
-
-
-
-
 impl Parser {
     /// Creates a new `Parser` for a given list of tokens.
     pub fn new(tokens: Vec<Token>) -> Self {
-        Parser { tokens, pos: 0 }
+        Parser { tokens, pos: 0, depth: 0 }
     }
 
     /// The primary public method that initiates the parsing process.
     /// It expects to parse a complete expression and returns an error
     /// if any tokens remain unconsumed.
     pub fn parse(&mut self) -> Result<Expr, String> {
+        limits::on_large_stack(|| self.parse_all())
+    }
+
+    fn parse_all(&mut self) -> Result<Expr, String> {
         let expr = self.parse_expr()?;
         // After a successful parse, we expect to be at the end of the token stream.
         if self.pos < self.tokens.len() {
@@ -66,10 +50,25 @@ impl Parser {
         token
     }
 
+    /// Runs `parse` one nesting level deeper, failing once `MAX_NESTING` is reached.
+    fn nested(&mut self, parse: impl FnOnce(&mut Self) -> Result<Expr, String>) -> Result<Expr, String> {
+        if self.depth >= MAX_NESTING {
+            return Err(format!("Expression is nested too deeply (more than {} levels)", MAX_NESTING));
+        }
+        self.depth += 1;
+        let result = parse(self);
+        self.depth -= 1;
+        result
+    }
+
     /// Parses the most fundamental units of an expression: numbers, variables,
     /// or sub-expressions enclosed in parentheses. This function handles the
     /// highest level of operator precedence.
     fn parse_primary(&mut self) -> Result<Expr, String> {
+        self.nested(Self::parse_primary_unnested)
+    }
+
+    fn parse_primary_unnested(&mut self) -> Result<Expr, String> {
         // Look ahead at the next token without consuming
         let next_token = self.peek().cloned();
         match next_token {
@@ -98,7 +97,6 @@ impl Parser {
     } else if let Some(Token::LParen) = self.peek() {
      
         self.consume(); // consume '('
-        println!("{:?} checking",self.peek());
         let inner = self.parse_expr()?;  // parse inner expression
         match self.consume() {
             Some(Token::RParen) => {
@@ -123,21 +121,18 @@ impl Parser {
     // Now parse the function argument AFTER the power expression
     let arg = if matches!(self.peek(), Some(Token::LParen)) {
         self.consume(); // consume '('
-        println!("{:?}",self.peek());
         let arg_expr = self.parse_expr()?;
         match self.consume() {
             Some(Token::RParen) => arg_expr,
             _ => return Err("Expected ')' after function argument".to_string()),
         }
     }else if matches!(self.peek(),Some(Token::Var(_)) | Some(Token::Num(_)) | Some(Token::Func(_)  )) {
-        println!("{:?} peeking",self.peek());
     
         self.parse_primary()?
     } else {
         return Err("Expected function argument after power".to_string());
     };
 
-  traverse_expr(&arg);
     
 
 
@@ -146,10 +141,8 @@ impl Parser {
     let func_expr = Expr::Func(name.clone(), Box::new(arg));
 
     // If power was 1, just return the function call
-    if let Expr::Num(n) = power_expr {
-        if (n - 1.0).abs() < std::f64::EPSILON {
-            return Ok(func_expr);
-        }
+    if matches!(power_expr, Expr::Num(n) if (n - 1.0).abs() < f64::EPSILON) {
+        return Ok(func_expr);
     }
 
     // Otherwise, return power expression: (sin(x))^(power_expr)
@@ -177,25 +170,26 @@ impl Parser {
     /// Parses exponentiation (the `^` operator). Exponentiation has higher
     /// precedence than multiplication and division and is right-associative,
     /// meaning `2^3^4` is parsed as `2^(3^4)`.
-    
 fn parse_factor(&mut self) -> Result<Expr, String> {
     // Start by parsing unary expressions to handle negation
     let mut left = self.parse_unary()?;
+    // Each power wraps `left` once more, so long chains nest as deep as they are long
+    let mut powers = 0;
 
     loop {
         match self.peek() {
             // Implicit multiplication: e.g., 2sinx, xsinx, (x+1)sinx
             Some(Token::Func(_)) | Some(Token::Var(_)) | Some(Token::Num(_)) | Some(Token::LParen) => {
                 let right = self.parse_unary()?;
-                left = Expr::BinaryOp {
-                    op: Op::Mul,
-                    left: Box::new(left),
-                    right: Box::new(right),
-                };
+                left = join(Op::Mul, left, right);
             }
 
             Some(Token::Pow) => {
                 self.consume(); // consume '^'
+                powers += 1;
+                if self.depth + powers > MAX_NESTING {
+                    return Err(format!("Expression is nested too deeply (more than {} levels)", MAX_NESTING));
+                }
 
                 match self.peek() {
                     Some(Token::Num(_)) => {
@@ -211,7 +205,7 @@ fn parse_factor(&mut self) -> Result<Expr, String> {
                                 };
                             } else {
                                 // Single number exponent
-                                let right = self.parse_factor()?;
+                                let right = self.nested(Self::parse_factor)?;
                                 left = Expr::BinaryOp {
                                     op: Op::Pow,
                                     left: Box::new(left),
@@ -220,7 +214,7 @@ fn parse_factor(&mut self) -> Result<Expr, String> {
                             }
                         } else {
                             // End of tokens, just parse factor
-                            let right = self.parse_factor()?;
+                            let right = self.nested(Self::parse_factor)?;
                             left = Expr::BinaryOp {
                                 op: Op::Pow,
                                 left: Box::new(left),
@@ -265,11 +259,7 @@ fn parse_factor(&mut self) -> Result<Expr, String> {
                     let op = if matches!(token, Token::Mul) { Op::Mul } else { Op::Div };
                     self.consume(); // Consume the '*' or '/' token.
                     let right = self.parse_factor()?;
-                    left = Expr::BinaryOp {
-                        op,
-                        left: Box::new(left),
-                        right: Box::new(right),
-                    };
+                    left = join(op, left, right);
                 }
                 _ => break,
             }
@@ -287,11 +277,7 @@ fn parse_factor(&mut self) -> Result<Expr, String> {
                     let op = if matches!(token, Token::Plus) { Op::Add } else { Op::Sub };
                     self.consume(); // Consume the '+' or '-' token.
                     let right = self.parse_term()?;
-                    left = Expr::BinaryOp {
-                        op,
-                        left: Box::new(left),
-                        right: Box::new(right),
-                    };
+                    left = join(op, left, right);
                 }
                 _ => break,
             }
@@ -314,40 +300,27 @@ fn parse_exponent_chain(&mut self) -> Result<Expr, String> {
     };
 
     // Keep multiplying by next parts if there's implicit multiplication:
-    loop {
-        match self.peek() {
-            Some(Token::Var(_)) | Some(Token::Num(_)) | Some(Token::Func(_)) | Some(Token::LParen) => {
-                let next = self.parse_primary()?;
-                expr = Expr::BinaryOp {
-                    op: Op::Mul,
-                    left: Box::new(expr),
-                    right: Box::new(next),
-                };
-            }
-            _ => break,
-        }
+    while let Some(Token::Var(_)) | Some(Token::Num(_)) | Some(Token::Func(_)) | Some(Token::LParen) = self.peek() {
+        let next = self.parse_primary()?;
+        expr = join(Op::Mul, expr, next);
     }
 
     Ok(expr)
 }
 
 fn parse_unary(&mut self) -> Result<Expr, String> {
-    if let Some(Token::Minus) = self.peek() {
+    // Count the leading minus signs in a loop rather than recursing once per
+    // sign (support --x); an even number of them cancels out
+    let mut negative = false;
+    while let Some(Token::Minus) = self.peek() {
         self.consume(); // consume '-'
-        let expr = self.parse_unary()?;  // recursively parse after minus (support --x)
-        match expr {
-            Expr::Num(n) => Ok(Expr::Num(-n)),  // Negate number literals directly
-            _ => {
-                // For non-numbers, represent unary minus as multiplication by -1
-                Ok(Expr::BinaryOp {
-                    op: Op::Mul,
-                    left: Box::new(Expr::Num(-1.0)),
-                    right: Box::new(expr),
-                })
-            }
-        }
+        negative = !negative;
+    }
+    let expr = self.parse_primary()?;  // fallback to primary parsing
+    if negative {
+        Ok(negate(expr))
     } else {
-        self.parse_primary()  // fallback to primary parsing
+        Ok(expr)
     }
 }
 
@@ -356,4 +329,50 @@ fn parse_unary(&mut self) -> Result<Expr, String> {
 
 
 
+}
+
+/// Negates an expression, negating number literals directly.
+fn negate(expr: Expr) -> Expr {
+    match expr {
+        Expr::Num(n) => Expr::Num(-n),
+        _ => Expr::Neg(Box::new(expr)),
+    }
+}
+
+/// Builds `left op right` for a left-associative chain of operators. Once a
+/// chain of `+`/`-` or `*`/`/` has more than two operands it becomes a flat
+/// `Sum` or `Product`, so that long input such as `x + x + ... + x` does not
+/// build a tree as deep as it is long.
+fn join(op: Op, left: Expr, right: Expr) -> Expr {
+    // The operand as it appears in a flat sum or product
+    let operand = |op: &Op, expr: Expr| match op {
+        Op::Sub => negate(expr),
+        Op::Div => Expr::BinaryOp {
+            op: Op::Pow,
+            left: Box::new(expr),
+            right: Box::new(Expr::Num(-1.0)),
+        },
+        _ => expr,
+    };
+    match (op, left) {
+        (op @ (Op::Add | Op::Sub), Expr::Sum(mut terms)) => {
+            terms.push(operand(&op, right));
+            Expr::Sum(terms)
+        }
+        (op @ (Op::Mul | Op::Div), Expr::Product(mut factors)) => {
+            factors.push(operand(&op, right));
+            Expr::Product(factors)
+        }
+        (op @ (Op::Add | Op::Sub), Expr::BinaryOp { op: first @ (Op::Add | Op::Sub), left, right: second }) => {
+            Expr::Sum(vec![*left, operand(&first, *second), operand(&op, right)])
+        }
+        (op @ (Op::Mul | Op::Div), Expr::BinaryOp { op: first @ (Op::Mul | Op::Div), left, right: second }) => {
+            Expr::Product(vec![*left, operand(&first, *second), operand(&op, right)])
+        }
+        (op, left) => Expr::BinaryOp {
+            op,
+            left: Box::new(left),
+            right: Box::new(right),
+        },
+    }
 }
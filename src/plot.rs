@@ -0,0 +1,422 @@
+use std::ops::RangeInclusive;
+
+use crate::ast::Expr;
+use crate::compile::{grid, CompiledFn};
+use crate::context::Context;
+
+/// A sampled curve: the values `ys` of a function at the points `xs`, NaN
+/// where it is undefined.
+#[derive(Debug, Clone)]
+pub struct Series {
+    pub label: String,
+    pub xs: Vec<f64>,
+    pub ys: Vec<f64>,
+}
+
+/// Samples `expr` (labelled `f`) and its derivative with respect to `var`
+/// (labelled `f'`) at `n` evenly spaced points from `from` to `to`.
+pub fn sample(expr: &Expr, var: &str, from: f64, to: f64, n: usize, ctx: &Context) -> Result<Vec<Series>, String> {
+    let compiled = CompiledFn::with_derivatives(expr, var, 1, &[var], ctx)?;
+    sample_compiled(&compiled, from, to, n)
+}
+
+fn sample_compiled(compiled: &CompiledFn, from: f64, to: f64, n: usize) -> Result<Vec<Series>, String> {
+    let xs = grid(from, to, n);
+    let (mut f, mut df) = (vec![0.0; n], vec![0.0; n]);
+    compiled.eval_batch(&[&xs], &mut [&mut f, &mut df])?;
+    Ok(vec![
+        Series { label: "f".to_string(), xs: xs.clone(), ys: f },
+        Series { label: "f'".to_string(), xs, ys: df },
+    ])
+}
+
+/// The evenly spaced points [`sample_adaptive`] starts from.
+const ADAPTIVE_POINTS: usize = 200;
+
+/// How many times [`sample_adaptive`] may halve a step.
+const MAX_REFINEMENTS: u32 = 6;
+
+/// How far, as a fraction of the y range, a curve may stray from the straight
+/// segment between two samples before [`sample_adaptive`] adds one in between.
+const FLATNESS: f64 = 1e-3;
+
+/// Like [`sample`], but adds points where a curve bends sharply, is steep
+/// or stops being defined, so that it is smooth when drawn at high resolution.
+pub fn sample_adaptive(expr: &Expr, var: &str, from: f64, to: f64, ctx: &Context) -> Result<Vec<Series>, String> {
+    let compiled = CompiledFn::with_derivatives(expr, var, 1, &[var], ctx)?;
+    let series = sample_compiled(&compiled, from, to, ADAPTIVE_POINTS)?;
+    let Some((lo, hi)) = y_range(&series) else {
+        return Ok(series);
+    };
+    let refinement = Refinement {
+        tolerance: (hi - lo) * FLATNESS,
+        // Beyond this the curve is off the plot, so there is nothing to smooth
+        band: (lo - (hi - lo))..=(hi + (hi - lo)),
+    };
+    Ok(series
+        .iter()
+        .enumerate()
+        .map(|(k, s)| refinement.refine(s, |x| compiled.call_all(&[x])[k]))
+        .collect())
+}
+
+struct Refinement {
+    tolerance: f64,
+    band: RangeInclusive<f64>,
+}
+
+impl Refinement {
+    fn refine(&self, series: &Series, value: impl Fn(f64) -> f64) -> Series {
+        let mut points = vec![(series.xs[0], series.ys[0])];
+        for i in 1..series.xs.len() {
+            let (a, b) = ((series.xs[i - 1], series.ys[i - 1]), (series.xs[i], series.ys[i]));
+            self.split(&value, a, b, MAX_REFINEMENTS, &mut points);
+        }
+        let (xs, ys) = points.into_iter().unzip();
+        Series { label: series.label.clone(), xs, ys }
+    }
+
+    /// Adds the points after `a` up to and including `b`, halving the step
+    /// while the midpoint is far from the straight segment or only one end is
+    /// defined, unless the segment is far off the plot.
+    fn split(&self, value: &impl Fn(f64) -> f64, a: (f64, f64), b: (f64, f64), depth: u32, points: &mut Vec<(f64, f64)>) {
+        let x = (a.0 + b.0) / 2.0;
+        let y = value(x);
+        let bent = if a.1.is_finite() && b.1.is_finite() {
+            !y.is_finite() || (y - (a.1 + b.1) / 2.0).abs() > self.tolerance
+        } else {
+            a.1.is_finite() != b.1.is_finite()
+        };
+        let shown = [a.1, y, b.1].iter().any(|v| self.band.contains(v));
+        if depth > 0 && bent && shown {
+            self.split(value, a, (x, y), depth - 1, points);
+            self.split(value, (x, y), b, depth - 1, points);
+        } else {
+            points.push(b);
+        }
+    }
+}
+
+/// The tangent to `expr` at `x0`, as a series from `from` to `to`. `None` if
+/// `expr` or its derivative is undefined at `x0`.
+pub fn tangent(expr: &Expr, var: &str, x0: f64, from: f64, to: f64, ctx: &Context) -> Result<Option<Series>, String> {
+    let values = CompiledFn::with_derivatives(expr, var, 1, &[var], ctx)?.call_all(&[x0]);
+    let (y0, slope) = (values[0], values[1]);
+    if !y0.is_finite() || !slope.is_finite() {
+        return Ok(None);
+    }
+    Ok(Some(Series {
+        label: format!("tangent at {} = {}", var, label(x0)),
+        xs: vec![from, to],
+        ys: vec![y0 + slope * (from - x0), y0 + slope * (to - x0)],
+    }))
+}
+
+/// The fraction of values at each end that [`y_range`] treats as possible outliers.
+const TAIL: f64 = 0.05;
+
+/// The range of y values to show. It covers all finite values, except that
+/// those far outside the bulk of them, as near a pole of `tan x`, are cut off.
+/// `None` if no value is finite.
+pub fn y_range(series: &[Series]) -> Option<(f64, f64)> {
+    // Each value is weighted by the stretch of x around it, so that the extra
+    // points `sample_adaptive` puts near a pole do not count for more
+    let mut ys = vec![];
+    for s in series {
+        let n = s.xs.len();
+        for i in 0..n {
+            let before = if i > 0 { (s.xs[i - 1] + s.xs[i]) / 2.0 } else { s.xs[i] };
+            let after = if i + 1 < n { (s.xs[i] + s.xs[i + 1]) / 2.0 } else { s.xs[i] };
+            if s.ys[i].is_finite() {
+                ys.push((s.ys[i], (after - before).abs()));
+            }
+        }
+    }
+    if ys.is_empty() {
+        return None;
+    }
+    ys.sort_by(|a, b| a.0.total_cmp(&b.0));
+    let total: f64 = ys.iter().map(|(_, weight)| weight).sum();
+    let quantile = |q: f64| {
+        if total == 0.0 {
+            return ys[((ys.len() - 1) as f64 * q).round() as usize].0;
+        }
+        let mut seen = 0.0;
+        for &(y, weight) in &ys {
+            seen += weight;
+            if seen >= q * total {
+                return y;
+            }
+        }
+        ys[ys.len() - 1].0
+    };
+    let (low, high) = (quantile(TAIL), quantile(1.0 - TAIL));
+    // The bulk of the values, widened by its own size on each side
+    let spread = high - low;
+    let (mut lo, mut hi) = (ys[0].0.max(low - spread), ys[ys.len() - 1].0.min(high + spread));
+    if lo == hi {
+        // A constant: centre it
+        let pad = if lo == 0.0 { 1.0 } else { lo.abs() / 2.0 };
+        (lo, hi) = (lo - pad, hi + pad);
+    }
+    // Round outwards to two significant digits of the range, e.g. to 1 rather than 0.9999
+    let step = 10f64.powf((hi - lo).log10().floor() - 1.0);
+    Some(((lo / step).floor() * step, (hi / step).ceil() * step))
+}
+
+/// The characters a terminal plot is drawn with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Glyphs {
+    /// Braille patterns, each character showing 2×4 dots.
+    #[default]
+    Braille,
+    /// One ASCII character per point, for terminals without Unicode.
+    Ascii,
+}
+
+/// Settings for drawing series on a terminal, e.g. over SSH.
+#[derive(Debug, Clone)]
+pub struct TerminalPlot {
+    /// The width of the plotting area, in characters.
+    pub width: usize,
+    /// The height of the plotting area, in characters.
+    pub height: usize,
+    pub glyphs: Glyphs,
+    /// Whether to color each series with ANSI escape codes.
+    pub color: bool,
+}
+
+impl Default for TerminalPlot {
+    fn default() -> Self {
+        TerminalPlot {
+            width: 72,
+            height: 20,
+            glyphs: Glyphs::Braille,
+            color: false,
+        }
+    }
+}
+
+/// The ANSI color of each series in turn, and of the axes.
+const COLORS: [&str; 4] = ["36", "35", "33", "32"];
+const AXIS_COLOR: &str = "90";
+
+/// The ASCII character of each series in turn.
+const ASCII_MARKS: [char; 4] = ['*', 'o', '#', '+'];
+
+/// What is drawn in a character cell, the later taking precedence.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Mark {
+    Empty,
+    XAxis,
+    YAxis,
+    Origin,
+    Series(usize),
+}
+
+/// A grid of character cells, each made of a block of dots.
+struct Canvas {
+    columns: usize,
+    rows: usize,
+    /// The dots across and down one cell.
+    cell_width: usize,
+    cell_height: usize,
+    /// The dots set in each cell, one bit per dot.
+    dots: Vec<u8>,
+    marks: Vec<Mark>,
+}
+
+impl Canvas {
+    fn new(columns: usize, rows: usize, glyphs: Glyphs) -> Canvas {
+        let (cell_width, cell_height) = match glyphs {
+            Glyphs::Braille => (2, 4),
+            Glyphs::Ascii => (1, 1),
+        };
+        Canvas {
+            columns,
+            rows,
+            cell_width,
+            cell_height,
+            dots: vec![0; columns * rows],
+            marks: vec![Mark::Empty; columns * rows],
+        }
+    }
+
+    fn dot_width(&self) -> usize {
+        self.columns * self.cell_width
+    }
+
+    fn dot_height(&self) -> usize {
+        self.rows * self.cell_height
+    }
+
+    fn set(&mut self, x: usize, y: usize, mark: Mark) {
+        let cell = (y / self.cell_height) * self.columns + x / self.cell_width;
+        self.dots[cell] |= BRAILLE_DOTS[x % self.cell_width][y % self.cell_height];
+        self.marks[cell] = match (self.marks[cell], mark) {
+            (Mark::XAxis, Mark::YAxis) | (Mark::YAxis, Mark::XAxis) => Mark::Origin,
+            (Mark::Series(_), Mark::XAxis | Mark::YAxis) => self.marks[cell],
+            _ => mark,
+        };
+    }
+
+    /// Draws the segment between two points in dot coordinates, leaving out
+    /// the parts above or below the canvas. Every `dash`-th pair of dots is
+    /// skipped if `dash` is set, counting on from `count`.
+    fn line(&mut self, from: (f64, f64), to: (f64, f64), mark: Mark, dash: Option<usize>, count: &mut usize) {
+        let bottom = (self.dot_height() - 1) as f64;
+        let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+        // The part of the segment with 0 <= y <= bottom
+        let (mut enter, mut exit) = (0.0f64, 1.0f64);
+        if dy == 0.0 {
+            if !(0.0..=bottom).contains(&from.1) {
+                return;
+            }
+        } else {
+            let (t0, t1) = (-from.1 / dy, (bottom - from.1) / dy);
+            enter = enter.max(t0.min(t1));
+            exit = exit.min(t0.max(t1));
+            if enter > exit {
+                return;
+            }
+        }
+        let steps = (dx * (exit - enter)).abs().max((dy * (exit - enter)).abs()).ceil() as usize;
+        for i in 0..=steps {
+            let t = enter + (exit - enter) * i as f64 / steps.max(1) as f64;
+            let (x, y) = ((from.0 + dx * t).round(), (from.1 + dy * t).round());
+            *count += 1;
+            if dash.is_some_and(|period| *count % (2 * period) >= period) {
+                continue;
+            }
+            if x >= 0.0 && (x as usize) < self.dot_width() && y >= 0.0 && y <= bottom {
+                self.set(x as usize, y as usize, mark);
+            }
+        }
+    }
+
+    fn glyph(&self, cell: usize, glyphs: Glyphs) -> char {
+        match glyphs {
+            Glyphs::Braille => char::from_u32(0x2800 + self.dots[cell] as u32).unwrap_or(' '),
+            Glyphs::Ascii => match self.marks[cell] {
+                Mark::Empty => ' ',
+                Mark::XAxis => '-',
+                Mark::YAxis => '|',
+                Mark::Origin => '+',
+                Mark::Series(i) => ASCII_MARKS[i % ASCII_MARKS.len()],
+            },
+        }
+    }
+}
+
+/// The bit of each dot in a braille character, by column and row.
+const BRAILLE_DOTS: [[u8; 4]; 2] = [[0x01, 0x02, 0x04, 0x40], [0x08, 0x10, 0x20, 0x80]];
+
+impl TerminalPlot {
+    /// Draws the series on shared axes, with the y range from [`y_range`],
+    /// labels on both axes and a legend. The first series is drawn solid and
+    /// the others dashed, so they can be told apart without color.
+    pub fn render(&self, series: &[Series]) -> Result<String, String> {
+        let (y0, y1) = y_range(series).ok_or("Nothing to plot: no value is finite in the range")?;
+        let xs = series.iter().flat_map(|s| s.xs.iter().copied());
+        let x0 = xs.clone().fold(f64::INFINITY, f64::min);
+        let x1 = xs.fold(f64::NEG_INFINITY, f64::max);
+        let mut canvas = Canvas::new(self.width.max(2), self.height.max(2), self.glyphs);
+        let (right, bottom) = ((canvas.dot_width() - 1) as f64, (canvas.dot_height() - 1) as f64);
+        let to_x = |x: f64| if x1 > x0 { (x - x0) / (x1 - x0) * right } else { right / 2.0 };
+        let to_y = |y: f64| (y1 - y) / (y1 - y0) * bottom;
+
+        // Axes through the origin, where they fall inside the plot, dotted in braille
+        let dotted = (self.glyphs == Glyphs::Braille).then_some(1);
+        let mut count = 0;
+        if (y0..=y1).contains(&0.0) {
+            canvas.line((0.0, to_y(0.0)), (right, to_y(0.0)), Mark::XAxis, dotted, &mut count);
+        }
+        if (x0..=x1).contains(&0.0) {
+            canvas.line((to_x(0.0), 0.0), (to_x(0.0), bottom), Mark::YAxis, dotted, &mut count);
+        }
+        for (i, s) in series.iter().enumerate() {
+            let dash = (i > 0 && self.glyphs == Glyphs::Braille).then_some(2);
+            let mut count = 0;
+            let points: Vec<(f64, f64)> = s.xs.iter().zip(&s.ys).map(|(&x, &y)| (to_x(x), to_y(y))).collect();
+            for pair in points.windows(2) {
+                let (a, b) = (pair[0], pair[1]);
+                // Skip undefined points and jumps higher than the plot, as at a pole
+                if !a.1.is_finite() || !b.1.is_finite() || (a.1 - b.1).abs() > bottom {
+                    continue;
+                }
+                canvas.line(a, b, Mark::Series(i), dash, &mut count);
+            }
+            if let [(x, y)] = points[..] {
+                canvas.line((x, y), (x, y), Mark::Series(i), None, &mut count);
+            }
+        }
+
+        // The y range at the top and bottom, and 0 in between if it is there
+        let labels = [(0, label(y1)), (canvas.rows - 1, label(y0))];
+        // On the row the x axis is drawn in, whose dots are rounded like any other
+        let zero_row = ((to_y(0.0).round() / canvas.cell_height as f64) as usize).min(canvas.rows - 1);
+        let zero = (y0 < 0.0 && y1 > 0.0 && zero_row != 0 && zero_row != canvas.rows - 1)
+            .then(|| (zero_row, "0".to_string()));
+        let gutter = labels.iter().map(|(_, l)| l.len()).max().unwrap_or(0);
+        let (tick, rule, corner, line) = match self.glyphs {
+            Glyphs::Braille => ('┤', '│', '└', '─'),
+            Glyphs::Ascii => ('+', '|', '+', '-'),
+        };
+
+        let mut out = String::new();
+        for row in 0..canvas.rows {
+            let text = labels.iter().chain(&zero).find(|(r, _)| *r == row).map(|(_, l)| l.as_str());
+            out.push_str(&format!("{:>gutter$} {}", text.unwrap_or(""), if text.is_some() { tick } else { rule }));
+            for column in 0..canvas.columns {
+                let cell = row * canvas.columns + column;
+                let glyph = canvas.glyph(cell, self.glyphs).to_string();
+                match (self.color, canvas.marks[cell]) {
+                    (true, Mark::Series(i)) => out.push_str(&paint(&glyph, COLORS[i % COLORS.len()])),
+                    (true, Mark::XAxis | Mark::YAxis | Mark::Origin) => out.push_str(&paint(&glyph, AXIS_COLOR)),
+                    _ => out.push_str(&glyph),
+                }
+            }
+            out.push('\n');
+        }
+        out.push_str(&format!("{:gutter$} {}{}\n", "", corner, line.to_string().repeat(canvas.columns)));
+        let (from, to) = (label(x0), label(x1));
+        let space = (canvas.columns + 1).saturating_sub(from.len() + to.len()).max(1);
+        out.push_str(&format!("{:gutter$} {}{}{}\n", "", from, " ".repeat(space), to));
+
+        let legend: Vec<String> = series
+            .iter()
+            .enumerate()
+            .map(|(i, s)| {
+                let sample = match self.glyphs {
+                    Glyphs::Braille if i == 0 => "━━".to_string(),
+                    Glyphs::Braille => "╍╍".to_string(),
+                    Glyphs::Ascii => ASCII_MARKS[i % ASCII_MARKS.len()].to_string(),
+                };
+                let sample = if self.color { paint(&sample, COLORS[i % COLORS.len()]) } else { sample };
+                format!("{} {}", sample, s.label)
+            })
+            .collect();
+        out.push_str(&format!("{:gutter$}  {}\n", "", legend.join("   ")));
+        Ok(out)
+    }
+}
+
+/// Text wrapped in the escape codes for an ANSI color.
+fn paint(text: &str, color: &str) -> String {
+    format!("\x1b[{}m{}\x1b[0m", color, text)
+}
+
+/// A short axis label with about four significant digits.
+fn label(value: f64) -> String {
+    let magnitude = value.abs();
+    if magnitude == 0.0 {
+        "0".to_string()
+    } else if (1e-3..1e5).contains(&magnitude) {
+        let decimals = (3 - magnitude.log10().floor() as i32).max(0) as usize;
+        let text = format!("{:.*}", decimals, value);
+        let text = if text.contains('.') { text.trim_end_matches('0').trim_end_matches('.') } else { &text };
+        text.to_string()
+    } else {
+        format!("{:.2e}", value)
+    }
+}
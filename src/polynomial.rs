@@ -0,0 +1,899 @@
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::ops::{Add, Div, Mul, Neg, Sub};
+
+use crate::ast::{Expr, Op};
+use crate::limits::{Budget, Limits};
+use crate::simplifier::normalize;
+
+/// The largest denominator tried when reading a floating-point number as a fraction.
+const MAX_DENOMINATOR: i128 = 1_000_000;
+
+/// How far, relative to its size, a float may be from a fraction and still be
+/// read as it: a few rounding errors. A looser or absolute tolerance reads
+/// `pi / 180` as a fraction, and a small coefficient such as `1e-13` as zero.
+const FRACTION_TOLERANCE: f64 = 4.0 * f64::EPSILON;
+
+/// The largest constant coefficient whose divisors are tried as rational roots.
+const MAX_ROOT_SEARCH: i128 = 1_000_000_000_000;
+
+/// An exact rational number, kept in lowest terms with a positive denominator.
+/// Arithmetic that overflows gives [`Rational::NAN`] rather than panicking.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Rational {
+    num: i128,
+    den: i128,
+}
+
+impl Rational {
+    /// The result of arithmetic that overflowed or divided by zero. Like the
+    /// float NaN, it spreads through everything computed from it, so that
+    /// polynomial operations can notice it and fail.
+    pub const NAN: Rational = Rational { num: 0, den: 0 };
+
+    pub fn new(num: i128, den: i128) -> Self {
+        assert!(den != 0, "rational with zero denominator");
+        let g = gcd_int(num, den).max(1) * den.signum();
+        Rational { num: num / g, den: den / g }
+    }
+
+    pub fn int(n: i128) -> Self {
+        Rational { num: n, den: 1 }
+    }
+
+    pub fn zero() -> Self {
+        Rational::int(0)
+    }
+
+    /// `num / den` in lowest terms, or NaN when either part overflowed.
+    fn checked(num: Option<i128>, den: Option<i128>) -> Self {
+        match (num, den) {
+            (Some(num), Some(den)) if den != 0 => Rational::new(num, den),
+            _ => Rational::NAN,
+        }
+    }
+
+    pub fn is_nan(self) -> bool {
+        self.den == 0
+    }
+
+    pub fn one() -> Self {
+        Rational::int(1)
+    }
+
+    pub fn numer(self) -> i128 {
+        self.num
+    }
+
+    pub fn denom(self) -> i128 {
+        self.den
+    }
+
+    pub fn is_zero(self) -> bool {
+        self.num == 0 && !self.is_nan()
+    }
+
+    /// Reads a float as the fraction it was most likely written as, e.g. `0.1`
+    /// as `1/10`, using continued fractions. Fails for values that are not
+    /// within [`FRACTION_TOLERANCE`] of a fraction with a small denominator.
+    pub fn from_f64(x: f64) -> Option<Self> {
+        if !x.is_finite() || x.abs() > 1e18 {
+            return None;
+        }
+        if x.fract() == 0.0 {
+            return Some(Rational::int(x as i128));
+        }
+        let (mut h0, mut h1, mut k0, mut k1) = (0i128, 1i128, 1i128, 0i128);
+        let mut rest = x;
+        loop {
+            let a = rest.floor();
+            // Close to, but not at, a convergent the next term is huge
+            let next = |p: i128, q: i128| (a as i128).checked_mul(p)?.checked_add(q);
+            let (Some(h), Some(k)) = (next(h1, h0), next(k1, k0)) else {
+                return None;
+            };
+            if k > MAX_DENOMINATOR {
+                return None;
+            }
+            (h0, h1, k0, k1) = (h1, h, k1, k);
+            if (h as f64 / k as f64 - x).abs() <= FRACTION_TOLERANCE * x.abs() {
+                return Some(Rational::new(h, k));
+            }
+            rest = 1.0 / (rest - a);
+        }
+    }
+
+    pub fn to_f64(self) -> f64 {
+        self.num as f64 / self.den as f64
+    }
+
+    pub fn to_expr(self) -> Expr {
+        Expr::Num(self.to_f64())
+    }
+
+    pub fn recip(self) -> Self {
+        Rational::checked(Some(self.den), Some(self.num))
+    }
+
+    pub fn abs(self) -> Self {
+        Rational { num: self.num.abs(), den: self.den }
+    }
+}
+
+impl Default for Rational {
+    fn default() -> Self {
+        Rational::zero()
+    }
+}
+
+impl Add for Rational {
+    type Output = Rational;
+    fn add(self, other: Rational) -> Rational {
+        // Over the least common denominator, so that sums of many terms do not overflow
+        let g = gcd_int(self.den, other.den).max(1);
+        let num = self.num.checked_mul(other.den / g).zip(other.num.checked_mul(self.den / g));
+        Rational::checked(num.and_then(|(a, b)| a.checked_add(b)), (self.den / g).checked_mul(other.den))
+    }
+}
+
+impl Sub for Rational {
+    type Output = Rational;
+    fn sub(self, other: Rational) -> Rational {
+        self + -other
+    }
+}
+
+impl Mul for Rational {
+    type Output = Rational;
+    fn mul(self, other: Rational) -> Rational {
+        // Cancelling across first keeps the products as small as the result
+        let (g, h) = (gcd_int(self.num, other.den).max(1), gcd_int(other.num, self.den).max(1));
+        Rational::checked((self.num / g).checked_mul(other.num / h), (self.den / h).checked_mul(other.den / g))
+    }
+}
+
+impl Div for Rational {
+    type Output = Rational;
+    fn div(self, other: Rational) -> Rational {
+        Mul::mul(self, other.recip())
+    }
+}
+
+impl Neg for Rational {
+    type Output = Rational;
+    fn neg(self) -> Rational {
+        Rational { num: -self.num, den: self.den }
+    }
+}
+
+impl PartialOrd for Rational {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Rational {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self.num.checked_mul(other.den), other.num.checked_mul(self.den)) {
+            (Some(a), Some(b)) if !self.is_nan() && !other.is_nan() => a.cmp(&b),
+            _ => self.to_f64().total_cmp(&other.to_f64()),
+        }
+    }
+}
+
+fn gcd_int(a: i128, b: i128) -> i128 {
+    let (mut a, mut b) = (a.abs(), b.abs());
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+/// The positive divisors of `n`, which must be nonzero.
+fn divisors(n: i128) -> Vec<i128> {
+    let n = n.abs();
+    let mut small = vec![];
+    let mut large = vec![];
+    let mut d = 1;
+    while d * d <= n {
+        if n % d == 0 {
+            small.push(d);
+            if d * d != n {
+                large.push(n / d);
+            }
+        }
+        d += 1;
+    }
+    small.extend(large.into_iter().rev());
+    small
+}
+
+/// A polynomial variable: a symbol, or any subexpression that is not itself a
+/// polynomial, such as `sin x` or `x^-1`, which is then treated as a symbol.
+/// Atoms are ordered like canonical expressions.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Atom(pub Expr);
+
+impl Eq for Atom {}
+
+impl PartialOrd for Atom {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Atom {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.canonical_cmp(&other.0)
+    }
+}
+
+/// A product of atoms raised to positive powers, sorted by atom.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Monomial(Vec<(Atom, u32)>);
+
+impl Monomial {
+    fn atom(atom: Atom, exp: u32) -> Self {
+        if exp == 0 {
+            Monomial::default()
+        } else {
+            Monomial(vec![(atom, exp)])
+        }
+    }
+
+    fn degree_in(&self, atom: &Atom) -> u32 {
+        self.0.iter().find(|(a, _)| a == atom).map_or(0, |(_, e)| *e)
+    }
+
+    fn total_degree(&self) -> u32 {
+        self.0.iter().map(|(_, e)| e).sum()
+    }
+
+    fn without(&self, atom: &Atom) -> Monomial {
+        Monomial(self.0.iter().filter(|(a, _)| a != atom).cloned().collect())
+    }
+
+    fn mul(&self, other: &Monomial) -> Monomial {
+        let mut merged: BTreeMap<&Atom, u32> = BTreeMap::new();
+        for (atom, exp) in self.0.iter().chain(&other.0) {
+            *merged.entry(atom).or_default() += exp;
+        }
+        Monomial(merged.into_iter().map(|(a, e)| (a.clone(), e)).collect())
+    }
+
+    /// `self / other`, if every exponent of `other` is at most the one in `self`.
+    fn div(&self, other: &Monomial) -> Option<Monomial> {
+        let mut result = vec![];
+        for (atom, exp) in &self.0 {
+            let sub = other.degree_in(atom);
+            if sub < *exp {
+                result.push((atom.clone(), exp - sub));
+            } else if sub > *exp {
+                return None;
+            }
+        }
+        if other.0.iter().all(|(atom, _)| self.degree_in(atom) > 0) {
+            Some(Monomial(result))
+        } else {
+            None
+        }
+    }
+
+    /// The largest monomial dividing both.
+    fn common(&self, other: &Monomial) -> Monomial {
+        Monomial(
+            self.0
+                .iter()
+                .filter_map(|(atom, exp)| match other.degree_in(atom).min(*exp) {
+                    0 => None,
+                    e => Some((atom.clone(), e)),
+                })
+                .collect(),
+        )
+    }
+
+    fn to_expr(&self) -> Expr {
+        let factors = self
+            .0
+            .iter()
+            .map(|(atom, exp)| Expr::BinaryOp {
+                op: Op::Pow,
+                left: Box::new(atom.0.clone()),
+                right: Box::new(Expr::Num(*exp as f64)),
+            })
+            .collect();
+        Expr::Product(factors)
+    }
+}
+
+impl PartialOrd for Monomial {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Lexicographic order, with earlier atoms more significant.
+impl Ord for Monomial {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let (mut a, mut b) = (self.0.iter().peekable(), other.0.iter().peekable());
+        loop {
+            match (a.peek(), b.peek()) {
+                (None, None) => return Ordering::Equal,
+                (Some(_), None) => return Ordering::Greater,
+                (None, Some(_)) => return Ordering::Less,
+                (Some((va, ea)), Some((vb, eb))) => match va.cmp(vb) {
+                    Ordering::Equal if ea == eb => {
+                        a.next();
+                        b.next();
+                    }
+                    Ordering::Equal => return ea.cmp(eb),
+                    // `self` has an atom that `other` lacks
+                    Ordering::Less => return Ordering::Greater,
+                    Ordering::Greater => return Ordering::Less,
+                },
+            }
+        }
+    }
+}
+
+/// A sparse multivariate polynomial with rational coefficients.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Poly {
+    /// Nonzero coefficients by monomial, so the last entry is the leading term.
+    terms: BTreeMap<Monomial, Rational>,
+}
+
+impl Poly {
+    pub fn zero() -> Self {
+        Poly::default()
+    }
+
+    pub fn constant(c: Rational) -> Self {
+        Poly::term(Monomial::default(), c)
+    }
+
+    pub fn one() -> Self {
+        Poly::constant(Rational::one())
+    }
+
+    /// The polynomial consisting of just `expr`, taken as a variable.
+    pub fn var(expr: &Expr) -> Self {
+        Poly::term(Monomial::atom(Atom(expr.clone()), 1), Rational::one())
+    }
+
+    fn term(monomial: Monomial, c: Rational) -> Self {
+        let mut terms = BTreeMap::new();
+        if !c.is_zero() {
+            terms.insert(monomial, c);
+        }
+        Poly { terms }
+    }
+
+    /// Converts an expression into a polynomial, multiplying out products and
+    /// integer powers. Anything that is not a polynomial, like `sin x` or
+    /// `1/x`, becomes an atom, and negative powers `a^-n` become `(a^-1)^n`.
+    pub fn from_expr(expr: &Expr) -> Self {
+        let expr = normalize(expr);
+        Poly::from_canonical(&expr)
+    }
+
+    fn from_canonical(expr: &Expr) -> Self {
+        match expr {
+            Expr::Num(n) => match Rational::from_f64(*n) {
+                Some(r) => Poly::constant(r),
+                None => Poly::var(expr),
+            },
+            Expr::Sum(terms) => terms.iter().fold(Poly::zero(), |acc, t| &acc + &Poly::from_canonical(t)),
+            Expr::Product(factors) => factors.iter().fold(Poly::one(), |acc, f| &acc * &Poly::from_canonical(f)),
+            Expr::BinaryOp { op: Op::Pow, left, right } => match **right {
+                Expr::Num(n) if n.fract() == 0.0 && n > 0.0 && n <= u32::MAX as f64 => {
+                    Poly::from_canonical(left).pow(n as u32)
+                }
+                Expr::Num(n) if n.fract() == 0.0 && n < -1.0 && -n <= u32::MAX as f64 => {
+                    Poly::var(&normalize(&Expr::BinaryOp {
+                        op: Op::Pow,
+                        left: left.clone(),
+                        right: Box::new(Expr::Num(-1.0)),
+                    }))
+                    .pow(-n as u32)
+                }
+                _ => Poly::var(expr),
+            },
+            _ => Poly::var(expr),
+        }
+    }
+
+    /// Converts back into a canonical expression.
+    pub fn to_expr(&self) -> Expr {
+        let terms = self
+            .terms
+            .iter()
+            .map(|(m, c)| {
+                let mut factors = vec![c.to_expr()];
+                factors.push(m.to_expr());
+                Expr::Product(factors)
+            })
+            .collect();
+        normalize(&Expr::Sum(terms))
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.terms.is_empty()
+    }
+
+    /// The value of a constant polynomial.
+    pub fn as_constant(&self) -> Option<Rational> {
+        match self.terms.len() {
+            0 => Some(Rational::zero()),
+            1 => self.terms.get(&Monomial::default()).copied(),
+            _ => None,
+        }
+    }
+
+    fn leading(&self) -> Option<(&Monomial, Rational)> {
+        self.terms.iter().next_back().map(|(m, c)| (m, *c))
+    }
+
+    /// The coefficient of the leading term in lexicographic order.
+    pub fn leading_coeff(&self) -> Rational {
+        self.leading().map_or(Rational::zero(), |(_, c)| c)
+    }
+
+    /// The atoms that occur, most significant first.
+    pub fn atoms(&self) -> Vec<Atom> {
+        let mut atoms: Vec<Atom> = self.terms.keys().flat_map(|m| m.0.iter().map(|(a, _)| a.clone())).collect();
+        atoms.sort();
+        atoms.dedup();
+        atoms
+    }
+
+    pub fn scale(&self, c: Rational) -> Poly {
+        if c.is_zero() {
+            return Poly::zero();
+        }
+        Poly {
+            terms: self.terms.iter().map(|(m, k)| (m.clone(), *k * c)).collect(),
+        }
+    }
+
+    pub fn pow(&self, n: u32) -> Poly {
+        let mut result = Poly::one();
+        let mut base = self.clone();
+        let mut n = n;
+        while n > 0 {
+            if n & 1 == 1 {
+                result = &result * &base;
+            }
+            base = &base * &base;
+            n >>= 1;
+        }
+        result
+    }
+
+    /// Like [`pow`](Poly::pow), but fails when the result could grow past the
+    /// node limit of `budget`, or time runs out.
+    pub fn pow_limited(&self, n: u32, budget: &Budget) -> Result<Poly, String> {
+        let mut result = Poly::one();
+        let mut base = self.clone();
+        let mut n = n;
+        while n > 0 {
+            if n & 1 == 1 {
+                result = result.mul_limited(&base, budget)?;
+            }
+            n >>= 1;
+            if n > 0 {
+                base = base.mul_limited(&base, budget)?;
+            }
+        }
+        Ok(result)
+    }
+
+    /// `self * other`, unless the product could have more terms than the node
+    /// limit of `budget` allows or time has run out.
+    pub fn mul_limited(&self, other: &Poly, budget: &Budget) -> Result<Poly, String> {
+        budget.check_size(self.terms.len().saturating_mul(other.terms.len()))?;
+        Ok(self * other)
+    }
+
+    /// The highest power of `var` that occurs.
+    pub fn degree(&self, var: &Expr) -> u32 {
+        let atom = Atom(var.clone());
+        self.terms.keys().map(|m| m.degree_in(&atom)).max().unwrap_or(0)
+    }
+
+    /// The highest total degree of any term.
+    pub fn total_degree(&self) -> u32 {
+        self.terms.keys().map(Monomial::total_degree).max().unwrap_or(0)
+    }
+
+    /// The coefficient of `var^k`, a polynomial in the other atoms.
+    pub fn coeff(&self, var: &Expr, k: u32) -> Poly {
+        let atom = Atom(var.clone());
+        Poly {
+            terms: self
+                .terms
+                .iter()
+                .filter(|(m, _)| m.degree_in(&atom) == k)
+                .map(|(m, c)| (m.without(&atom), *c))
+                .collect(),
+        }
+    }
+
+    /// The nonzero coefficients with respect to `var`, highest power first.
+    pub fn coefficients(&self, var: &Expr) -> Vec<(u32, Poly)> {
+        (0..=self.degree(var))
+            .rev()
+            .map(|k| (k, self.coeff(var, k)))
+            .filter(|(_, c)| !c.is_zero())
+            .collect()
+    }
+
+    /// Divides by `divisor` as polynomials in `var`, returning the quotient and
+    /// the remainder, whose degree in `var` is below the divisor's. Fails if
+    /// the divisor's leading coefficient does not divide the coefficients met
+    /// along the way, which cannot happen when it is a number.
+    pub fn div_rem(&self, divisor: &Poly, var: &Expr) -> Result<(Poly, Poly), String> {
+        if divisor.is_zero() {
+            return Err("Division by the zero polynomial".to_string());
+        }
+        self.check_finite()?;
+        divisor.check_finite()?;
+        let d = divisor.degree(var);
+        let lc = divisor.coeff(var, d);
+        let mut quotient = Poly::zero();
+        let mut rest = self.clone();
+        while !rest.is_zero() && rest.degree(var) >= d {
+            let k = rest.degree(var);
+            let c = rest
+                .coeff(var, k)
+                .div_exact(&lc)
+                .ok_or_else(|| format!("The leading coefficient {} does not divide {}", lc.to_expr(), rest.to_expr()))?;
+            let step = &c * &Poly::var(var).pow(k - d);
+            rest = &rest - &(&step * divisor);
+            quotient = &quotient + &step;
+            shrinks(&rest, var, k)?;
+        }
+        Ok((quotient, rest))
+    }
+
+    /// Fails if an atom holds an infinite or NaN number, as in `x + 0/0`. NaN
+    /// does not even equal itself, so terms with it would never cancel and
+    /// division and the gcd would not finish.
+    fn check_finite(&self) -> Result<(), String> {
+        match self.atoms().iter().find(|atom| !atom.0.numbers_finite()) {
+            Some(atom) => Err(format!("Cannot divide polynomials with the coefficient {}", atom.0)),
+            None => self.check_coefficients(),
+        }
+    }
+
+    /// Fails if a coefficient has grown past what a [`Rational`] can hold.
+    fn check_coefficients(&self) -> Result<(), String> {
+        if self.terms.values().any(|c| c.is_nan()) {
+            Err("The coefficients grow too large for exact division".to_string())
+        } else {
+            Ok(())
+        }
+    }
+
+    /// `self / divisor` if it divides exactly.
+    pub fn div_exact(&self, divisor: &Poly) -> Option<Poly> {
+        let (dm, dc) = divisor.leading()?;
+        let mut quotient = Poly::zero();
+        let mut rest = self.clone();
+        while let Some((m, c)) = rest.leading() {
+            let step = Poly::term(m.div(dm)?, c / dc);
+            let next = &rest - &(&step * divisor);
+            // The leading term must cancel, or this would never finish
+            if next.leading().is_some_and(|(n, _)| n >= m) {
+                return None;
+            }
+            rest = next;
+            quotient = &quotient + &step;
+        }
+        Some(quotient)
+    }
+
+    /// Scales so that the leading coefficient is 1.
+    pub fn monic(&self) -> Poly {
+        match self.leading() {
+            Some((_, c)) => self.scale(c.recip()),
+            None => Poly::zero(),
+        }
+    }
+
+    /// The greatest common divisor, made monic. Polynomials in several
+    /// atoms are handled recursively, one atom at a time.
+    pub fn gcd(&self, other: &Poly) -> Poly {
+        let limits = Limits::default();
+        self.gcd_limited(other, &Budget::start(&limits)).unwrap_or_else(|_| Poly::one())
+    }
+
+    /// Like [`gcd`](Poly::gcd), but checks `budget` at every step, since the
+    /// remainder sequence of large polynomials can take very long.
+    pub fn gcd_limited(&self, other: &Poly, budget: &Budget) -> Result<Poly, String> {
+        budget.check()?;
+        self.check_finite()?;
+        other.check_finite()?;
+        if self.is_zero() {
+            return Ok(other.monic());
+        }
+        if other.is_zero() {
+            return Ok(self.monic());
+        }
+        let mut atoms = self.atoms();
+        atoms.extend(other.atoms());
+        let var = match atoms.into_iter().min() {
+            Some(atom) => atom.0,
+            None => return Ok(Poly::one()),
+        };
+        let (ca, cb) = (self.content(&var, budget)?, other.content(&var, budget)?);
+        let common = ca.gcd_limited(&cb, budget)?;
+        let mut a = self.div_exact(&ca).unwrap_or_default();
+        let mut b = other.div_exact(&cb).unwrap_or_default();
+        if a.degree(&var) < b.degree(&var) {
+            std::mem::swap(&mut a, &mut b);
+        }
+        // Primitive remainder sequence
+        while !b.is_zero() {
+            let r = a.pseudo_rem(&b, &var, budget)?;
+            a = b;
+            b = if r.is_zero() { r } else { r.primitive(&var, budget)? };
+        }
+        Ok((&a.primitive(&var, budget)? * &common).monic())
+    }
+
+    /// The gcd of the coefficients with respect to `var`.
+    fn content(&self, var: &Expr, budget: &Budget) -> Result<Poly, String> {
+        self.coefficients(var)
+            .iter()
+            .try_fold(Poly::zero(), |acc, (_, c)| acc.gcd_limited(c, budget))
+    }
+
+    /// `self` divided by its content with respect to `var`.
+    fn primitive(&self, var: &Expr, budget: &Budget) -> Result<Poly, String> {
+        Ok(self.div_exact(&self.content(var, budget)?).unwrap_or_default())
+    }
+
+    /// The remainder of `lc(other)^k * self` divided by `other` in `var`, which
+    /// needs no division of coefficients. It is only determined up to a numeric
+    /// factor, which is divided out at every step to keep the coefficients small.
+    fn pseudo_rem(&self, other: &Poly, var: &Expr, budget: &Budget) -> Result<Poly, String> {
+        let d = other.degree(var);
+        let lc = other.coeff(var, d);
+        let mut rest = self.clone();
+        while !rest.is_zero() && rest.degree(var) >= d {
+            budget.check()?;
+            let k = rest.degree(var);
+            let step = &rest.coeff(var, k) * &Poly::var(var).pow(k - d);
+            rest = (&(&rest * &lc) - &(&step * other)).integer_primitive().1;
+            shrinks(&rest, var, k)?;
+        }
+        Ok(rest)
+    }
+
+    /// Splits off the numeric content: returns `(c, p)` with `self = c * p`,
+    /// where `p` has coprime integer coefficients and a positive leading one.
+    pub fn integer_primitive(&self) -> (Rational, Poly) {
+        let (mut num, mut den) = (0, 1);
+        for c in self.terms.values() {
+            num = gcd_int(num, c.numer());
+            match (den / gcd_int(den, c.denom())).checked_mul(c.denom()) {
+                Some(lcm) if lcm != 0 => den = lcm,
+                _ => return (Rational::one(), self.clone()),
+            }
+        }
+        let mut content = Rational::new(num.max(1), den);
+        if self.leading_coeff() < Rational::zero() {
+            content = -content;
+        }
+        (content, self.scale(content.recip()))
+    }
+
+    /// Factors over the rationals. Common numeric and monomial factors and
+    /// factors shared by all coefficients of an atom are always split off;
+    /// what remains is split into linear factors at its rational roots when it
+    /// has a single atom, which factors polynomials of degree up to 3
+    /// completely. Returns the numeric factor and the other factors with their
+    /// multiplicities.
+    pub fn factor(&self) -> (Rational, Vec<(Poly, u32)>) {
+        if self.is_zero() {
+            return (Rational::zero(), vec![]);
+        }
+        let (c, p) = self.integer_primitive();
+        let mut factors = vec![];
+        p.factor_into(&mut factors);
+
+        // Merge equal factors
+        let mut merged: Vec<(Poly, u32)> = vec![];
+        for (f, k) in factors {
+            match merged.iter_mut().find(|(g, _)| *g == f) {
+                Some((_, total)) => *total += k,
+                None => merged.push((f, k)),
+            }
+        }
+        merged.sort_by(|(a, _), (b, _)| a.total_degree().cmp(&b.total_degree()).then_with(|| a.to_expr().canonical_cmp(&b.to_expr())));
+        (c, merged)
+    }
+
+    /// Factors a polynomial with coprime integer coefficients into `out`.
+    fn factor_into(&self, out: &mut Vec<(Poly, u32)>) {
+        if self.as_constant().is_some() {
+            return;
+        }
+
+        // Common monomial factor
+        let common = self
+            .terms
+            .keys()
+            .skip(1)
+            .fold(self.terms.keys().next().cloned().unwrap_or_default(), |acc, m| acc.common(m));
+        for (atom, exp) in &common.0 {
+            out.push((Poly::var(&atom.0), *exp));
+        }
+        let rest = self.div_exact(&Poly::term(common, Rational::one())).unwrap_or_default();
+        if rest.as_constant().is_some() {
+            return;
+        }
+
+        // A factor shared by all coefficients of some atom
+        let atoms = rest.atoms();
+        let limits = Limits::default();
+        for atom in &atoms {
+            let content = rest.content(&atom.0, &Budget::start(&limits)).unwrap_or_else(|_| Poly::one());
+            if content.as_constant().is_none() {
+                let (_, content) = content.integer_primitive();
+                let cofactor = rest.div_exact(&content).unwrap_or_default();
+                content.factor_into(out);
+                cofactor.integer_primitive().1.factor_into(out);
+                return;
+            }
+        }
+
+        if atoms.len() == 1 {
+            rest.factor_univariate(&atoms[0].0, out);
+        } else {
+            out.push((rest, 1));
+        }
+    }
+
+    /// Splits off linear factors `q*x - p` at the rational roots `p/q`.
+    fn factor_univariate(&self, var: &Expr, out: &mut Vec<(Poly, u32)>) {
+        let mut rest = self.clone();
+        let mut searched = false;
+        while rest.degree(var) > 1 && !searched {
+            searched = true;
+            let (_, p) = rest.integer_primitive();
+            let constant = p.coeff(var, 0).as_constant().unwrap_or_default();
+            let leading = p.leading_coeff();
+            if constant.is_zero() || constant.numer().abs() > MAX_ROOT_SEARCH || leading.numer().abs() > MAX_ROOT_SEARCH {
+                break;
+            }
+            'search: for q in divisors(leading.numer()) {
+                for num in divisors(constant.numer()) {
+                    for num in [num, -num] {
+                        if gcd_int(num, q) != 1 {
+                            continue;
+                        }
+                        let root = Rational::new(num, q);
+                        if p.eval_univariate(root).is_zero() {
+                            let linear = &Poly::var(var).scale(Rational::int(q)) - &Poly::constant(Rational::int(num));
+                            rest = p.div_exact(&linear).unwrap_or_default();
+                            out.push((linear, 1));
+                            searched = false;
+                            break 'search;
+                        }
+                    }
+                }
+            }
+        }
+        let (_, rest) = rest.integer_primitive();
+        if rest.as_constant().is_none() {
+            out.push((rest, 1));
+        }
+    }
+
+    /// Evaluates a polynomial in a single atom.
+    fn eval_univariate(&self, x: Rational) -> Rational {
+        self.terms.iter().fold(Rational::zero(), |acc, (m, c)| {
+            let power = (0..m.total_degree()).fold(Rational::one(), |p, _| p * x);
+            acc + *c * power
+        })
+    }
+}
+
+/// Fails unless a remainder has dropped below degree `k` in `var`, so that a
+/// division that makes no progress stops instead of looping, or if its
+/// coefficients have overflowed.
+fn shrinks(rest: &Poly, var: &Expr, k: u32) -> Result<(), String> {
+    rest.check_coefficients()?;
+    if rest.is_zero() || rest.degree(var) < k {
+        Ok(())
+    } else {
+        Err(format!("The division in {} makes no progress", var))
+    }
+}
+
+impl Add for &Poly {
+    type Output = Poly;
+    fn add(self, other: &Poly) -> Poly {
+        let mut terms = self.terms.clone();
+        for (m, c) in &other.terms {
+            let sum = terms.get(m).copied().unwrap_or_default() + *c;
+            if sum.is_zero() {
+                terms.remove(m);
+            } else {
+                terms.insert(m.clone(), sum);
+            }
+        }
+        Poly { terms }
+    }
+}
+
+impl Sub for &Poly {
+    type Output = Poly;
+    fn sub(self, other: &Poly) -> Poly {
+        self + &-other
+    }
+}
+
+impl Mul for &Poly {
+    type Output = Poly;
+    fn mul(self, other: &Poly) -> Poly {
+        // Accumulate in place, since adding term by term would copy the result every time
+        let mut terms: BTreeMap<Monomial, Rational> = BTreeMap::new();
+        for (ma, ca) in &self.terms {
+            for (mb, cb) in &other.terms {
+                let c = terms.entry(ma.mul(mb)).or_default();
+                *c = *c + *ca * *cb;
+            }
+        }
+        terms.retain(|_, c| !c.is_zero());
+        Poly { terms }
+    }
+}
+
+impl Neg for &Poly {
+    type Output = Poly;
+    fn neg(self) -> Poly {
+        self.scale(-Rational::one())
+    }
+}
+
+/// Multiplies out products and integer powers, including inside function
+/// arguments, e.g. `(x+1)*(x-1)` becomes `x^2 - 1`.
+pub fn expand(expr: &Expr) -> Expr {
+    Poly::from_expr(&expr.map_children(expand)).to_expr()
+}
+
+/// Writes a polynomial in powers of `var`, with the other atoms gathered into
+/// each coefficient, e.g. `a*x + b*x + c` becomes `(a + b) * x + c`.
+pub fn collect(expr: &Expr, var: &Expr) -> Expr {
+    let poly = Poly::from_expr(expr);
+    let terms = poly
+        .coefficients(var)
+        .into_iter()
+        .map(|(k, c)| {
+            Expr::Product(vec![
+                c.to_expr(),
+                Expr::BinaryOp {
+                    op: Op::Pow,
+                    left: Box::new(var.clone()),
+                    right: Box::new(Expr::Num(k as f64)),
+                },
+            ])
+        })
+        .collect();
+    normalize(&Expr::Sum(terms))
+}
+
+/// Factors a polynomial expression over the rationals, see [`Poly::factor`].
+pub fn factor(expr: &Expr) -> Expr {
+    let (c, factors) = Poly::from_expr(expr).factor();
+    let mut product = vec![c.to_expr()];
+    product.extend(factors.into_iter().map(|(f, k)| Expr::BinaryOp {
+        op: Op::Pow,
+        left: Box::new(f.to_expr()),
+        right: Box::new(Expr::Num(k as f64)),
+    }));
+    normalize(&Expr::Product(product))
+}
@@ -0,0 +1,221 @@
+use crate::ast::{Expr, Op};
+use crate::limits::{Budget, Limits};
+use crate::polynomial::{Poly, Rational};
+use crate::simplifier::normalize;
+
+/// A quotient of two polynomials. The atoms of the polynomials may themselves
+/// be anything that is not a polynomial, like `sin x`, so any expression built
+/// from sums, products and integer powers has this form.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RationalFn {
+    pub num: Poly,
+    pub den: Poly,
+}
+
+impl RationalFn {
+    fn poly(p: Poly) -> Self {
+        RationalFn { num: p, den: Poly::one() }
+    }
+
+    /// Converts an expression, putting every sum of fractions over a common
+    /// denominator. Function arguments are combined on their own first.
+    pub fn from_expr(expr: &Expr) -> Self {
+        let limits = Limits::default();
+        RationalFn::from_expr_limited(expr, &Budget::start(&limits)).unwrap_or_else(|_| RationalFn::poly(Poly::var(expr)))
+    }
+
+    /// Like [`from_expr`](RationalFn::from_expr), but checks `budget` as it goes,
+    /// since multiplying out large products can take very long.
+    pub fn from_expr_limited(expr: &Expr, budget: &Budget) -> Result<Self, String> {
+        RationalFn::from_canonical(&normalize(expr), budget)
+    }
+
+    fn from_canonical(expr: &Expr, budget: &Budget) -> Result<Self, String> {
+        budget.check()?;
+        let atom = |expr: &Expr| -> Result<RationalFn, String> {
+            let combined = expr.map_children(|c| together_limited(c, budget).unwrap_or_else(|_| c.clone()));
+            // A child that gave up has left the budget spent, which this reports
+            budget.check()?;
+            Ok(RationalFn::poly(Poly::var(&normalize(&combined))))
+        };
+        match expr {
+            Expr::Sum(terms) => terms.iter().try_fold(RationalFn::poly(Poly::zero()), |acc, t| {
+                acc.add(&RationalFn::from_canonical(t, budget)?, budget)
+            }),
+            Expr::Product(factors) => factors.iter().try_fold(RationalFn::poly(Poly::one()), |acc, f| {
+                acc.mul(&RationalFn::from_canonical(f, budget)?, budget)
+            }),
+            Expr::BinaryOp { op: Op::Pow, left, right } => match **right {
+                Expr::Num(n) if n.fract() == 0.0 && n.abs() <= u32::MAX as f64 => {
+                    let base = RationalFn::from_canonical(left, budget)?;
+                    let base = if n < 0.0 { base.recip() } else { base };
+                    base.pow(n.abs() as u32, budget)
+                }
+                // `a^-b` is `1 / a^b`, so the power can go into the denominator
+                Expr::Num(n) if n < 0.0 => Ok(RationalFn::poly(Poly::var(&normalize(&Expr::BinaryOp {
+                    op: Op::Pow,
+                    left: left.clone(),
+                    right: Box::new(Expr::Num(-n)),
+                })))
+                .recip()),
+                _ => atom(expr),
+            },
+            Expr::Num(_) | Expr::Var(_) => Ok(RationalFn::poly(Poly::from_expr(expr))),
+            _ => atom(expr),
+        }
+    }
+
+    fn add(&self, other: &RationalFn, budget: &Budget) -> Result<RationalFn, String> {
+        // Use the least common multiple of the denominators
+        let g = self.den.gcd_limited(&other.den, budget)?;
+        let left = other.den.div_exact(&g).unwrap_or_default();
+        let right = self.den.div_exact(&g).unwrap_or_default();
+        Ok(RationalFn {
+            num: &self.num.mul_limited(&left, budget)? + &other.num.mul_limited(&right, budget)?,
+            den: self.den.mul_limited(&left, budget)?,
+        })
+    }
+
+    fn mul(&self, other: &RationalFn, budget: &Budget) -> Result<RationalFn, String> {
+        Ok(RationalFn {
+            num: self.num.mul_limited(&other.num, budget)?,
+            den: self.den.mul_limited(&other.den, budget)?,
+        })
+    }
+
+    fn recip(&self) -> RationalFn {
+        RationalFn {
+            num: self.den.clone(),
+            den: self.num.clone(),
+        }
+    }
+
+    fn pow(&self, n: u32, budget: &Budget) -> Result<RationalFn, String> {
+        Ok(RationalFn {
+            num: self.num.pow_limited(n, budget)?,
+            den: self.den.pow_limited(n, budget)?,
+        })
+    }
+
+    /// Divides the numerator and denominator by their greatest common divisor,
+    /// and scales them so that the denominator has coprime integer coefficients
+    /// and a positive leading coefficient.
+    pub fn cancel(&self) -> RationalFn {
+        let limits = Limits::default();
+        self.cancel_limited(&Budget::start(&limits)).unwrap_or_else(|_| self.clone())
+    }
+
+    /// Like [`cancel`](RationalFn::cancel), but checks `budget` while finding
+    /// the greatest common divisor.
+    pub fn cancel_limited(&self, budget: &Budget) -> Result<RationalFn, String> {
+        let g = self.num.gcd_limited(&self.den, budget)?;
+        let num = self.num.div_exact(&g).unwrap_or_default();
+        let den = self.den.div_exact(&g).unwrap_or_default();
+        let (c, den) = den.integer_primitive();
+        Ok(RationalFn {
+            num: num.scale(c.recip()),
+            den,
+        })
+    }
+
+    /// The quotient as a canonical expression, with the numerator multiplied
+    /// out and the denominator factored.
+    pub fn to_expr(&self) -> Expr {
+        if self.num.is_zero() {
+            return Expr::Num(0.0);
+        }
+        let (c, factors) = self.den.factor();
+        let mut product = vec![self.num.to_expr(), Expr::Num(c.recip().to_f64())];
+        product.extend(factors.into_iter().map(|(f, k)| Expr::BinaryOp {
+            op: Op::Pow,
+            left: Box::new(f.to_expr()),
+            right: Box::new(Expr::Num(-(k as f64))),
+        }));
+        normalize(&Expr::Product(product))
+    }
+}
+
+/// Puts sums of fractions over a common denominator, e.g. `1/x + 1/(x+1)`
+/// becomes `(2x + 1) / (x * (x + 1))`. Common factors that are not needed for
+/// the common denominator are left in place; see [`cancel`] for removing them.
+pub fn together(expr: &Expr) -> Expr {
+    RationalFn::from_expr(expr).to_expr()
+}
+
+/// Like [`together`], but checks `budget` as it goes.
+pub fn together_limited(expr: &Expr, budget: &Budget) -> Result<Expr, String> {
+    Ok(RationalFn::from_expr_limited(expr, budget)?.to_expr())
+}
+
+/// Like [`together`], but also cancels the greatest common divisor of the
+/// numerator and denominator, e.g. `(x^2 - 1) / (x + 1)` becomes `x - 1`.
+pub fn cancel(expr: &Expr) -> Expr {
+    RationalFn::from_expr(expr).cancel().to_expr()
+}
+
+/// Like [`cancel`], but checks `budget` as it goes, failing once time runs out,
+/// the call is cancelled or the polynomials grow too large.
+pub fn cancel_limited(expr: &Expr, budget: &Budget) -> Result<Expr, String> {
+    Ok(RationalFn::from_expr_limited(expr, budget)?.cancel_limited(budget)?.to_expr())
+}
+
+/// Decomposes a quotient of polynomials in `var` into partial fractions: a
+/// polynomial plus a sum of terms `a / f^k`, where the `f` are the irreducible
+/// factors of the denominator over the rationals and each `a` has a lower degree
+/// than its `f`. Coefficients must be numbers.
+pub fn apart(expr: &Expr, var: &Expr) -> Result<Expr, String> {
+    let f = RationalFn::from_expr(expr).cancel();
+    for p in [&f.num, &f.den] {
+        if p.atoms().iter().any(|a| a.0 != *var) {
+            return Err(format!("apart needs a quotient of polynomials in {} alone", var));
+        }
+    }
+    let (poly_part, rest) = f.num.div_rem(&f.den, var)?;
+    let mut terms = vec![poly_part.to_expr()];
+    if !rest.is_zero() {
+        let (c, factors) = f.den.factor();
+        let rest = rest.scale(c.recip());
+        let den = f.den.scale(c.recip());
+        for (factor, k) in factors {
+            // The part of `rest / den` that belongs to `factor^k`, as `a / factor^k`
+            let power = factor.pow(k);
+            let cofactor = den.div_exact(&power).unwrap_or_default();
+            let inverse = inverse_mod(&cofactor, &power, var)?;
+            let (_, mut a) = (&rest * &inverse).div_rem(&power, var)?;
+            // Write `a` in powers of `factor`: a = a_k + a_(k-1) factor + ...
+            for j in (1..=k).rev() {
+                let (q, r) = a.div_rem(&factor, var)?;
+                if !r.is_zero() {
+                    terms.push(Expr::Product(vec![
+                        r.to_expr(),
+                        Expr::BinaryOp {
+                            op: Op::Pow,
+                            left: Box::new(factor.to_expr()),
+                            right: Box::new(Expr::Num(-(j as f64))),
+                        },
+                    ]));
+                }
+                a = q;
+            }
+        }
+    }
+    Ok(normalize(&Expr::Sum(terms)))
+}
+
+/// The inverse of `a` modulo `m` as polynomials in `var`, which exists when
+/// they are coprime.
+fn inverse_mod(a: &Poly, m: &Poly, var: &Expr) -> Result<Poly, String> {
+    // Extended Euclid, keeping track of the coefficient of `a` only
+    let (mut r0, mut r1) = (m.clone(), a.div_rem(m, var)?.1);
+    let (mut s0, mut s1) = (Poly::zero(), Poly::one());
+    while !r1.is_zero() {
+        let (q, r) = r0.div_rem(&r1, var)?;
+        let s = &s0 - &(&q * &s1);
+        (r0, r1) = (r1, r);
+        (s0, s1) = (s1, s);
+    }
+    match r0.as_constant() {
+        Some(g) if !g.is_zero() => Ok(s0.scale(Rational::one() / g)),
+        _ => Err("The denominator has a repeated factor that could not be separated".to_string()),
+    }
+}
@@ -0,0 +1,399 @@
+use std::collections::HashMap;
+use std::fs;
+
+use crate::assumptions::Assumptions;
+use crate::ast::Expr;
+use crate::limits::{Budget, Limits};
+use crate::parser::Parser;
+use crate::simplifier::normalize;
+use crate::tokenizer::tokenize;
+
+/// Rules that `simplify` applies by default, written in the same syntax as the
+/// expressions we differentiate. Every variable on the left-hand side is a
+/// pattern variable that matches any subexpression, unless an `if` clause
+/// restricts it.
+const DEFAULT_RULES: &str = "
+# Inverse function pairs
+log(exp(a)) -> a
+exp(log(a)) -> a if positive(a)
+
+# Exponentials
+e^a -> exp(a)
+exp(a)^b -> exp(a*b)
+exp(a)*exp(b) -> exp(a + b)
+
+# Pythagorean identities
+sin(a)^2 + cos(a)^2 -> 1
+cosh(a)^2 - sinh(a)^2 -> 1
+
+# Roots and absolute values
+sqrt(a)^2 -> a if positive(a)
+abs(abs(a)) -> abs(a)
+abs(-a) -> abs(a)
+abs(a) -> a if positive(a)
+sqrt(a^2) -> a if positive(a)
+sqrt(a^2) -> abs(a) if real(a)
+(a^b)^c -> a^(b*c) if positive(a)
+
+# Logarithms of powers
+log(a^b) -> b*log(a) if positive(a)
+";
+
+/// The maximum number of rule applications made by a single call to [`RuleSet::apply`].
+pub const DEFAULT_STEP_BUDGET: usize = 1000;
+
+lazy_static::lazy_static! {
+    pub static ref BUILTIN_RULES: RuleSet =
+        RuleSet::parse(DEFAULT_RULES).expect("built-in rewrite rules must parse");
+}
+
+/// Pattern variable bindings built up while matching a rule.
+pub(crate) type Bindings = HashMap<String, Expr>;
+
+/// A single rewrite rule `lhs -> rhs`, optionally guarded by conditions on its
+/// pattern variables. Both sides are kept in canonical form so that they line
+/// up with simplified expressions.
+#[derive(Debug, Clone)]
+pub struct Rule {
+    lhs: Expr,
+    rhs: Expr,
+    conditions: Vec<Condition>,
+}
+
+/// A side condition on a pattern variable, written after `if` in a rule,
+/// e.g. `n*log(a) -> log(a^n) if number(n)`.
+#[derive(Debug, Clone)]
+enum Condition {
+    /// The variable is bound to a number.
+    Number(String),
+    /// The variable is bound to something known to be an integer.
+    Integer(String),
+    /// The variable is bound to something known to be real.
+    Real(String),
+    /// The variable is bound to something known to be positive.
+    Positive(String),
+    /// The variable is bound to something known to be nonzero.
+    Nonzero(String),
+}
+
+impl Condition {
+    fn parse(text: &str) -> Result<Self, String> {
+        let text = text.trim();
+        let (name, var) = text
+            .strip_suffix(')')
+            .and_then(|t| t.split_once('('))
+            .ok_or_else(|| format!("Condition '{}' should look like number(a)", text))?;
+        let var = var.trim().to_string();
+        match name.trim() {
+            "number" => Ok(Condition::Number(var)),
+            "integer" => Ok(Condition::Integer(var)),
+            "real" => Ok(Condition::Real(var)),
+            "positive" => Ok(Condition::Positive(var)),
+            "nonzero" => Ok(Condition::Nonzero(var)),
+            other => Err(format!("Unknown condition '{}'", other)),
+        }
+    }
+
+    fn holds(&self, binds: &Bindings, assumptions: &Assumptions) -> bool {
+        let known = |var: &String, test: fn(&Assumptions, &Expr) -> bool| {
+            binds.get(var).is_some_and(|e| test(assumptions, e))
+        };
+        match self {
+            Condition::Number(var) => matches!(binds.get(var), Some(Expr::Num(_))),
+            Condition::Integer(var) => known(var, Assumptions::is_integer),
+            Condition::Real(var) => known(var, Assumptions::is_real),
+            Condition::Positive(var) => known(var, Assumptions::is_positive),
+            Condition::Nonzero(var) => known(var, Assumptions::is_nonzero),
+        }
+    }
+}
+
+impl Rule {
+    /// Parses a rule written as `lhs -> rhs`, e.g. `log(exp(a)) -> a`, or as
+    /// `lhs -> rhs if cond(a), ...`, e.g. `n*log(a) -> log(a^n) if number(n)`.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let (lhs, rhs) = text
+            .split_once("->")
+            .ok_or_else(|| format!("Rule '{}' is missing '->'", text.trim()))?;
+        let (rhs, conditions) = match rhs.split_once(" if ") {
+            Some((rhs, conditions)) => (
+                rhs,
+                conditions.split(',').map(Condition::parse).collect::<Result<Vec<_>, _>>()?,
+            ),
+            None => (rhs, vec![]),
+        };
+        Ok(Rule {
+            lhs: parse_side(lhs)?,
+            rhs: parse_side(rhs)?,
+            conditions,
+        })
+    }
+
+    fn conditions_hold(&self, binds: &Bindings, assumptions: &Assumptions) -> bool {
+        self.conditions.iter().all(|c| c.holds(binds, assumptions))
+    }
+
+    /// The pattern side of the rule.
+    pub(crate) fn lhs(&self) -> &Expr {
+        &self.lhs
+    }
+
+    /// The right-hand side with `binds` substituted, if the rule's conditions hold for them.
+    pub(crate) fn instantiate(&self, binds: &Bindings, assumptions: &Assumptions) -> Option<Expr> {
+        self.conditions_hold(binds, assumptions).then(|| substitute(&self.rhs, binds))
+    }
+
+    /// Tries to rewrite `expr` itself (not its children) with this rule.
+    fn apply_at(&self, expr: &Expr, assumptions: &Assumptions) -> Option<Expr> {
+        match (&self.lhs, expr) {
+            // A sum or product pattern may match just some of the target's operands,
+            // e.g. `sin(a)^2 + cos(a)^2` inside `sin(x)^2 + cos(x)^2 + 1`.
+            (Expr::Sum(pats), Expr::Sum(targets)) | (Expr::Product(pats), Expr::Product(targets))
+                if pats.len() <= targets.len() =>
+            {
+                let mut used = vec![false; targets.len()];
+                let binds = match_operands(pats, targets, &mut used, &Bindings::new(), &|b| self.conditions_hold(b, assumptions))?;
+                let mut operands = vec![substitute(&self.rhs, &binds)];
+                operands.extend(
+                    targets
+                        .iter()
+                        .zip(&used)
+                        .filter(|(_, used)| !**used)
+                        .map(|(t, _)| t.clone()),
+                );
+                Some(match expr {
+                    Expr::Sum(_) => Expr::Sum(operands),
+                    _ => Expr::Product(operands),
+                })
+            }
+            _ => {
+                let binds = match_expr(&self.lhs, expr, &Bindings::new())
+                    .into_iter()
+                    .find(|b| self.conditions_hold(b, assumptions))?;
+                Some(substitute(&self.rhs, &binds))
+            }
+        }
+    }
+}
+
+fn parse_side(text: &str) -> Result<Expr, String> {
+    let tokens = tokenize(text.trim())?;
+    let expr = Parser::new(tokens).parse()?;
+    Ok(normalize(&expr))
+}
+
+/// An ordered collection of rewrite rules.
+#[derive(Debug, Clone, Default)]
+pub struct RuleSet {
+    rules: Vec<Rule>,
+}
+
+impl RuleSet {
+    /// Parses a rules file: one `lhs -> rhs` rule per line, with blank lines and
+    /// lines starting with `#` ignored.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut set = RuleSet::default();
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            set.rules
+                .push(Rule::parse(line).map_err(|e| format!("line {}: {}", i + 1, e))?);
+        }
+        Ok(set)
+    }
+
+    /// Reads and parses a rules file from disk.
+    pub fn load_file(path: &str) -> Result<Self, String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("Cannot read rules file '{}': {}", path, e))?;
+        RuleSet::parse(&text).map_err(|e| format!("{}: {}", path, e))
+    }
+
+    /// Registers a rule written as `lhs -> rhs`.
+    pub fn add(&mut self, rule: &str) -> Result<(), String> {
+        self.rules.push(Rule::parse(rule)?);
+        Ok(())
+    }
+
+    /// Appends all the rules of `other` after this set's rules.
+    pub fn extend(&mut self, other: RuleSet) {
+        self.rules.extend(other.rules);
+    }
+
+    /// The rules in the order they are tried.
+    pub(crate) fn iter(&self) -> std::slice::Iter<'_, Rule> {
+        self.rules.iter()
+    }
+
+    /// Rewrites a canonical expression until no rule applies, making at most
+    /// [`DEFAULT_STEP_BUDGET`] rewrites. Rule conditions are checked against `assumptions`.
+    pub fn apply(&self, expr: &Expr, assumptions: &Assumptions) -> Expr {
+        self.apply_with_budget(expr, assumptions, DEFAULT_STEP_BUDGET)
+    }
+
+    /// Rewrites a canonical expression until no rule applies or `steps` rewrites
+    /// have been made, whichever comes first.
+    pub fn apply_with_budget(&self, expr: &Expr, assumptions: &Assumptions, mut steps: usize) -> Expr {
+        let limits = Limits::default();
+        let budget = Budget::start(&limits);
+        let mut current = expr.clone();
+        loop {
+            let before = steps;
+            current = self.rewrite_pass(&current, assumptions, &mut steps, &budget);
+            if steps == before || steps == 0 {
+                return current;
+            }
+        }
+    }
+
+    /// Like [`apply`](RuleSet::apply), but checks `budget` before every pass,
+    /// failing once the expression has grown too large or time has run out.
+    pub fn apply_limited(&self, expr: &Expr, assumptions: &Assumptions, budget: &Budget) -> Result<Expr, String> {
+        let mut steps = DEFAULT_STEP_BUDGET;
+        let mut current = expr.clone();
+        loop {
+            budget.check_size(current.size())?;
+            let before = steps;
+            current = self.rewrite_pass(&current, assumptions, &mut steps, budget);
+            if steps == before || steps == 0 {
+                budget.check()?;
+                return Ok(current);
+            }
+        }
+    }
+
+    /// One top-down pass over the tree. A node is tried before its children, so
+    /// that rules spanning several factors, like `sin(a)/cos(a) -> tan(a)`, get
+    /// the first chance before a rule for one factor, like `1/cos(a) -> sec(a)`.
+    /// A pass gives up, leaving the rest of the tree as it is, once `budget`
+    /// runs out; the caller reports that.
+    fn rewrite_pass(&self, expr: &Expr, assumptions: &Assumptions, steps: &mut usize, budget: &Budget) -> Expr {
+        if *steps == 0 || budget.check().is_err() {
+            return expr.clone();
+        }
+        for rule in &self.rules {
+            if let Some(rewritten) = rule.apply_at(expr, assumptions) {
+                let rewritten = normalize(&rewritten);
+                if rewritten != *expr {
+                    *steps -= 1;
+                    return rewritten;
+                }
+            }
+        }
+        let rewritten_children = expr.map_children(|child| self.rewrite_pass(child, assumptions, steps, budget));
+        // A rewritten child can make this node non-canonical, e.g. by turning a factor into a sum
+        if rewritten_children != *expr {
+            normalize(&rewritten_children)
+        } else {
+            rewritten_children
+        }
+    }
+}
+
+/// Matches `pattern` against `expr`, returning every consistent extension of `binds`.
+/// Several results are possible because sums and products match in any order.
+fn match_expr(pattern: &Expr, expr: &Expr, binds: &Bindings) -> Vec<Bindings> {
+    match (pattern, expr) {
+        (Expr::Var(name), _) => match binds.get(name) {
+            Some(bound) if bound == expr => vec![binds.clone()],
+            Some(_) => vec![],
+            None => {
+                let mut binds = binds.clone();
+                binds.insert(name.clone(), expr.clone());
+                vec![binds]
+            }
+        },
+        (Expr::Num(a), Expr::Num(b)) if a == b => vec![binds.clone()],
+        (Expr::Neg(p), Expr::Neg(e)) => match_expr(p, e, binds),
+        (Expr::Func(pn, pa), Expr::Func(en, ea)) if pn == en => match_expr(pa, ea, binds),
+        (
+            Expr::BinaryOp { op: po, left: pl, right: pr },
+            Expr::BinaryOp { op: eo, left: el, right: er },
+        ) if po == eo => match_expr(pl, el, binds)
+            .iter()
+            .flat_map(|b| match_expr(pr, er, b))
+            .collect(),
+        (Expr::Sum(pats), Expr::Sum(targets)) | (Expr::Product(pats), Expr::Product(targets))
+            if pats.len() == targets.len() =>
+        {
+            let mut used = vec![false; targets.len()];
+            match_operands(pats, targets, &mut used, binds, &|_| true).into_iter().collect()
+        }
+        // With fewer pattern operands, a free pattern variable captures all the
+        // leftover operands, so `log(a*b)` also matches `log(x*y*z)` with `b = y*z`.
+        (Expr::Sum(pats), Expr::Sum(targets)) | (Expr::Product(pats), Expr::Product(targets))
+            if pats.len() < targets.len() =>
+        {
+            let rest_var = pats.iter().rposition(|p| matches!(p, Expr::Var(v) if !binds.contains_key(v)));
+            let rest_var = match rest_var {
+                Some(i) => i,
+                None => return vec![],
+            };
+            let fixed: Vec<Expr> = pats
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| *i != rest_var)
+                .map(|(_, p)| p.clone())
+                .collect();
+            let mut used = vec![false; targets.len()];
+            let mut binds = match match_operands(&fixed, targets, &mut used, binds, &|_| true) {
+                Some(binds) => binds,
+                None => return vec![],
+            };
+            let rest: Vec<Expr> = targets
+                .iter()
+                .zip(&used)
+                .filter(|(_, used)| !**used)
+                .map(|(t, _)| t.clone())
+                .collect();
+            let rest = match expr {
+                Expr::Sum(_) => Expr::Sum(rest),
+                _ => Expr::Product(rest),
+            };
+            if let Expr::Var(name) = &pats[rest_var] {
+                binds.insert(name.clone(), normalize(&rest));
+            }
+            vec![binds]
+        }
+        _ => vec![],
+    }
+}
+
+/// Matches each pattern operand against a distinct, not yet `used` target operand,
+/// backtracking over the possible pairings until one is found whose bindings
+/// satisfy `accept`. On success, `used` marks the targets that were consumed.
+fn match_operands(
+    pats: &[Expr],
+    targets: &[Expr],
+    used: &mut [bool],
+    binds: &Bindings,
+    accept: &dyn Fn(&Bindings) -> bool,
+) -> Option<Bindings> {
+    let (first, rest) = match pats.split_first() {
+        Some(split) => split,
+        None if accept(binds) => return Some(binds.clone()),
+        None => return None,
+    };
+    for i in 0..targets.len() {
+        if used[i] {
+            continue;
+        }
+        for candidate in match_expr(first, &targets[i], binds) {
+            used[i] = true;
+            if let Some(done) = match_operands(rest, targets, used, &candidate, accept) {
+                return Some(done);
+            }
+            used[i] = false;
+        }
+    }
+    None
+}
+
+/// Replaces the pattern variables in `expr` with their bound values.
+fn substitute(expr: &Expr, binds: &Bindings) -> Expr {
+    match expr {
+        Expr::Var(name) => binds.get(name).cloned().unwrap_or_else(|| expr.clone()),
+        _ => expr.map_children(|c| substitute(c, binds)),
+    }
+}
@@ -1,142 +1,474 @@
+use std::collections::HashMap;
+
 use crate::ast::{Expr, Op};
+use crate::complex::{self, Complex};
+use crate::constants::is_imaginary_unit;
+use crate::context::Context;
+use crate::dag::{Arena, ExprId};
+use crate::egraph;
+use crate::eval::{apply_function, Function};
+use crate::exact::special_value;
+use crate::limits::{on_large_stack, Budget};
+use crate::polynomial;
+use crate::rational;
+use crate::rewrite::RuleSet;
 
 
 
-fn is_like_term(a: &Expr, b: &Expr) -> bool {
-    match (a, b) {
-        (Expr::Var(va), Expr::Var(vb)) => va == vb,
-        (Expr::BinaryOp { op: Op::Pow, left: la, right: ra },
-         Expr::BinaryOp { op: Op::Pow, left: lb, right: rb }) => la == lb && ra == rb,
-        (Expr::Func(fa, aa), Expr::Func(fb, ab)) => fa == fb && aa == ab,
-        _ => false,
+/// Splits a term into its numeric coefficient and the remaining factors,
+/// e.g. `3 * x * y` into `(3, x * y)` and a bare number `5` into `(5, 1)`.
+fn split_coeff(term: &Expr) -> (f64, Expr) {
+    match term {
+        Expr::Num(n) => (*n, Expr::Num(1.0)),
+        Expr::Product(factors) => match factors.split_first() {
+            Some((Expr::Num(c), rest)) => (*c, make_product(rest.to_vec())),
+            _ => (1.0, term.clone()),
+        },
+        _ => (1.0, term.clone()),
     }
 }
 
+/// Builds a canonical n-ary sum from already simplified terms: nested sums are
+/// flattened, like terms are combined, zero terms are dropped and the remaining
+/// terms are sorted (highest first, so constants come last).
+fn make_sum(terms: Vec<Expr>) -> Expr {
+    let mut flat = vec![];
+    for term in terms {
+        match term {
+            Expr::Sum(inner) => flat.extend(inner),
+            other => flat.push(other),
+        }
+    }
 
-fn flatten_mul(expr: &Expr) -> (f64, Expr) {
-    match expr {
-        Expr::BinaryOp { op: Op::Mul, left, right } => {
-            let (cl, bl) = flatten_mul(left);
-            let (cr, br) = flatten_mul(right);
-            let coeff = cl * cr;
-            let base = if bl == Expr::Num(1.0) {
-                br
-            } else if br == Expr::Num(1.0) {
-                bl
-            } else {
-                Expr::BinaryOp {
-                    op: Op::Mul,
-                    left: Box::new(bl),
-                    right: Box::new(br),
+    // Sort by the non-numeric part so that like terms become adjacent
+    let mut split: Vec<(f64, Expr)> = flat.iter().map(split_coeff).collect();
+    split.sort_by(|(_, a), (_, b)| a.canonical_cmp(b));
+
+    // Combine like terms
+    let mut groups: Vec<(f64, Expr)> = vec![];
+    for (coeff, base) in split {
+        match groups.last_mut() {
+            Some((c, b)) if *b == base => *c += coeff,
+            _ => groups.push((coeff, base)),
+        }
+    }
+
+    let mut result: Vec<Expr> = groups
+        .into_iter()
+        .filter(|(coeff, _)| *coeff != 0.0)
+        .map(|(coeff, base)| make_product(vec![Expr::Num(coeff), base]))
+        .collect();
+    result.sort_by(|a, b| b.canonical_cmp(a));
+
+    match result.len() {
+        0 => Expr::Num(0.0),
+        1 => result.pop().unwrap(),
+        _ => Expr::Sum(result),
+    }
+}
+
+/// Builds a canonical n-ary product from already simplified factors: nested
+/// products are flattened, numbers are folded into a single leading
+/// coefficient, factors with equal bases are merged by adding their exponents
+/// (`x * x^2` becomes `x^3`, `x * x^-1` cancels) and the rest are sorted.
+/// A zero factor makes the product zero, even next to an infinite or NaN
+/// one, so that e.g. the derivative of the constant `0^0` stays 0.
+fn make_product(factors: Vec<Expr>) -> Expr {
+    let mut numbers = vec![];
+    let mut powers: Vec<(Expr, Expr)> = vec![];
+    for factor in factors {
+        match factor {
+            Expr::Num(n) => numbers.push(n),
+            Expr::Product(inner) => {
+                for f in inner {
+                    match f {
+                        Expr::Num(n) => numbers.push(n),
+                        other => powers.push(split_power(other)),
+                    }
                 }
-            };
-            (coeff, base)
+            }
+            other => powers.push(split_power(other)),
         }
-        Expr::Num(n) => (*n, Expr::Num(1.0)),
-        _ => (1.0, expr.clone()),
     }
+    if is_zero(&numbers) {
+        return Expr::Num(0.0);
+    }
+
+    // Group equal bases and add their exponents
+    powers.sort_by(|(a, _), (b, _)| a.canonical_cmp(b));
+    let mut groups: Vec<(Expr, Vec<Expr>)> = vec![];
+    for (base, exp) in powers {
+        match groups.last_mut() {
+            Some((b, exps)) if *b == base => exps.push(exp),
+            _ => groups.push((base, vec![exp])),
+        }
+    }
+
+    let mut rest = vec![];
+    for (base, exps) in groups {
+        match make_power(base, make_sum(exps)) {
+            Expr::Num(n) => numbers.push(n),
+            Expr::Product(inner) => {
+                for f in inner {
+                    match f {
+                        Expr::Num(n) => numbers.push(n),
+                        other => rest.push(other),
+                    }
+                }
+            }
+            other => rest.push(other),
+        }
+    }
+    if is_zero(&numbers) {
+        return Expr::Num(0.0);
+    }
+    let coeff: f64 = numbers.iter().product();
+    rest.sort_by(|a, b| a.canonical_cmp(b));
+
+    if coeff != 1.0 {
+        rest.insert(0, Expr::Num(coeff));
+    }
+    match rest.len() {
+        0 => Expr::Num(coeff),
+        1 => rest.pop().unwrap(),
+        _ => Expr::Product(rest),
+    }
+}
+
+/// Whether a product of these numbers is zero: one of them is, or they
+/// underflow. Checked before multiplying, where `0 * inf` would give NaN.
+fn is_zero(numbers: &[f64]) -> bool {
+    numbers.contains(&0.0) || numbers.iter().product::<f64>() == 0.0
+}
+
+/// Splits a factor into base and exponent, treating a plain factor as `f ^ 1`.
+fn split_power(factor: Expr) -> (Expr, Expr) {
+    match factor {
+        Expr::BinaryOp { op: Op::Pow, left, right } => (*left, *right),
+        other => (other, Expr::Num(1.0)),
+    }
+}
+
+/// Builds a canonical power from an already simplified base and exponent.
+/// Integer powers of powers and of products are multiplied out, since
+/// `(a^b)^n = a^(b*n)` and `(a*b)^n = a^n * b^n` hold for any integer `n`,
+/// integer powers of `i` reduce to `1`, `i`, `-1` or `-i` and those of square
+/// roots of numbers to at most one root, e.g. `sqrt 2 ^ -1` to `0.5 * sqrt 2`.
+/// Powers of numbers without a real value, like `(-8)^(1/3)`, are left alone.
+fn make_power(base: Expr, exp: Expr) -> Expr {
+    match (base, exp) {
+        (_, Expr::Num(0.0)) => Expr::Num(1.0),
+        (base, Expr::Num(1.0)) => base,
+        (Expr::Num(a), Expr::Num(b)) if !a.powf(b).is_nan() => Expr::Num(a.powf(b)),
+        (base, Expr::Num(n)) if is_imaginary_unit(&base) && n.fract() == 0.0 => match n.rem_euclid(4.0) {
+            0.0 => Expr::Num(1.0),
+            1.0 => base,
+            2.0 => Expr::Num(-1.0),
+            _ => negate(base),
+        },
+        (Expr::Func(name, arg), Expr::Num(k))
+            if name == "sqrt" && k.fract() == 0.0 && matches!(*arg, Expr::Num(n) if n > 0.0) =>
+        {
+            // sqrt(n)^k = n^(k div 2) * sqrt(n)^(k mod 2)
+            let Expr::Num(n) = *arg else { unreachable!() };
+            let root = if k.rem_euclid(2.0) == 1.0 { Expr::Func(name, arg) } else { Expr::Num(1.0) };
+            make_product(vec![Expr::Num(n.powf(k.div_euclid(2.0))), root])
+        }
+        (Expr::BinaryOp { op: Op::Pow, left, right }, Expr::Num(n)) if n.fract() == 0.0 => {
+            make_power(*left, make_product(vec![*right, Expr::Num(n)]))
+        }
+        (Expr::Product(factors), Expr::Num(n)) if n.fract() == 0.0 => {
+            make_product(factors.into_iter().map(|f| make_power(f, Expr::Num(n))).collect())
+        }
+        (base, exp) => Expr::BinaryOp {
+            op: Op::Pow,
+            left: Box::new(base),
+            right: Box::new(exp),
+        },
+    }
+}
+
+/// Returns the negation of an already simplified expression.
+fn negate(expr: Expr) -> Expr {
+    make_product(vec![Expr::Num(-1.0), expr])
+}
+
+
+
+
+/// Simplifies an expression: puts it into canonical form and then applies the
+/// built-in rewrite rules until nothing changes.
+pub fn simplify(expr: &Expr) -> Result<Expr, String> {
+    simplify_with(expr, &Context::default())
+}
+
+/// Like [`simplify`], but with the rewrite rules, trig mode, log mode, float
+/// folding, expansion and assumptions taken from `ctx`. If `ctx.egraph` is set, the
+/// result is then handed to the e-graph simplifier. Fails with an "expression
+/// too large" error when the limits in `ctx` are exceeded.
+pub fn simplify_with(expr: &Expr, ctx: &Context) -> Result<Expr, String> {
+    // The passes recurse once per level of nesting
+    on_large_stack(|| {
+        let simplified = simplify_radians(&ctx.angle.to_radians(expr), ctx)?;
+        Ok(ctx.angle.from_radians(&simplified))
+    })
+}
+
+/// [`simplify_with`] for an expression in radians, whatever the angle mode.
+fn simplify_radians(expr: &Expr, ctx: &Context) -> Result<Expr, String> {
+    let budget = Budget::start(&ctx.limits);
+    budget.check_depth(expr)?;
+    let rules = all_rules(ctx);
+    let simplified = simplify_tree(expr, ctx, &rules, &budget)?;
+    finish(simplified, ctx, &rules, &budget)
+}
+
+/// The rewrite rules in `ctx` together with those of its trig and log modes.
+fn all_rules(ctx: &Context) -> RuleSet {
+    let mut rules = ctx.rules.clone();
+    rules.extend(ctx.trig.rules().clone());
+    rules.extend(ctx.log.rules().clone());
+    rules
+}
+
+/// The steps of [`simplify_radians`] that work node by node: canonical form,
+/// the rewrite rules, and the folding and expansion `ctx` asks for.
+fn simplify_tree(expr: &Expr, ctx: &Context, rules: &RuleSet, budget: &Budget) -> Result<Expr, String> {
+    let mut normalized = normalize(expr);
+    if ctx.complex {
+        normalized = normalize(&fold_complex(&normalized));
+    }
+    let mut simplified = rules.apply_limited(&normalized, &ctx.assumptions, budget)?;
+    if ctx.fold_floats {
+        simplified = rules.apply_limited(&normalize(&fold_floats(&simplified)), &ctx.assumptions, budget)?;
+    }
+    if ctx.expand {
+        simplified = rules.apply_limited(&polynomial::expand(&simplified), &ctx.assumptions, budget)?;
+    }
+    Ok(simplified)
+}
+
+/// The steps of [`simplify_radians`] that look at a whole expression rather
+/// than at one node at a time: cancelling over a common denominator, then the
+/// e-graph if `ctx.egraph` is set.
+fn finish(mut simplified: Expr, ctx: &Context, rules: &RuleSet, budget: &Budget) -> Result<Expr, String> {
+    // Quotients, e.g. from the quotient rule, may reduce over a common denominator.
+    // When they cannot, say because the coefficients overflow, they are left as they are
+    if worth_cancelling(&simplified) {
+        match rational::cancel_limited(&simplified, budget) {
+            Ok(cancelled) => {
+                let cancelled = rules.apply_limited(&cancelled, &ctx.assumptions, budget)?;
+                if cancelled.size() <= simplified.size() {
+                    simplified = cancelled;
+                }
+            }
+            Err(e) => budget.check().map_err(|_| e)?,
+        }
+    }
+    if let Some(settings) = &ctx.egraph {
+        simplified = egraph::simplify_limited(&simplified, ctx, settings, budget);
+        budget.check()?;
+    }
+    Ok(simplified)
+}
+
+/// How many levels of a node [`simplify_in`] looks at. The rewrite rules and
+/// the grouping of like terms only reach a few levels down, and the nodes
+/// below have been simplified already.
+const NODE_DEPTH: usize = 4;
+
+/// Simplifies an expression stored in `arena`, remembering the result for
+/// every node so that simplifying it again, or a later expression that
+/// shares it, costs nothing. Each node is simplified once, however often it
+/// occurs: its children first, then the node itself, seeing only its top
+/// [`NODE_DEPTH`] levels and taking larger subexpressions below as opaque
+/// symbols. The steps in [`finish`] then run once, on `id`. Like
+/// [`derivative_in`](crate::derivative::derivative_in), this works in radians.
+pub fn simplify_in(arena: &mut Arena, id: ExprId, ctx: &Context) -> Result<ExprId, String> {
+    if let Some(&simplified) = arena.simplified.get(&id) {
+        return Ok(simplified);
+    }
+    let budget = Budget::start(&ctx.limits);
+    let rules = all_rules(ctx);
+    // Children before parents, without recursing, so deep expressions cannot overflow the stack
+    let mut pending = vec![id];
+    while let Some(&top) = pending.last() {
+        if arena.simplified.contains_key(&top) {
+            pending.pop();
+            continue;
+        }
+        let children = arena.children(top);
+        let waiting: Vec<ExprId> = children.iter().filter(|c| !arena.simplified.contains_key(c)).copied().collect();
+        if !waiting.is_empty() {
+            pending.extend(waiting);
+            continue;
+        }
+        pending.pop();
+        budget.check_size(arena.len())?;
+        let children = children.iter().map(|c| arena.simplified[c]).collect();
+        let rebuilt = arena.with_children(top, children);
+        let simplified = match arena.simplified.get(&rebuilt) {
+            Some(&simplified) => simplified,
+            None => {
+                let mut holes = HashMap::new();
+                let expr = arena.to_expr_shallow(rebuilt, NODE_DEPTH, &mut holes);
+                let simplified = simplify_tree(&expr, ctx, &rules, &budget)?;
+                arena.store_shallow(&simplified, &holes)
+            }
+        };
+        arena.simplified.insert(top, simplified);
+        arena.simplified.insert(rebuilt, simplified);
+        // A simplified expression is its own simplification
+        arena.simplified.insert(simplified, simplified);
+    }
+    let mut holes = HashMap::new();
+    let expr = arena.to_expr_shallow(arena.simplified[&id], NODE_DEPTH, &mut holes);
+    let finished = arena.store_shallow(&finish(expr, ctx, &rules, &budget)?, &holes);
+    arena.simplified.insert(id, finished);
+    arena.simplified.insert(finished, finished);
+    Ok(finished)
 }
 
-fn extract_coeff_and_base(expr: &Expr) -> (f64, Expr) {
-    flatten_mul(expr)
+/// The highest power that [`worth_cancelling`] lets be multiplied out.
+const MAX_CANCEL_POWER: f64 = 16.0;
+
+/// Whether a canonical expression divides by something other than a number,
+/// and has no powers so high that multiplying them out would be slow. One
+/// with an infinite or NaN number, such as `1/x + 0/0`, is left alone: it has
+/// no meaningful common denominator.
+fn worth_cancelling(expr: &Expr) -> bool {
+    fn visit(expr: &Expr, found: &mut bool) -> bool {
+        match expr {
+            Expr::BinaryOp { op: Op::Pow, left, right } => match **right {
+                Expr::Num(n) if !n.is_finite() || n.abs() > MAX_CANCEL_POWER => false,
+                Expr::Num(n) => {
+                    *found |= n < 0.0 && !matches!(**left, Expr::Num(_));
+                    visit(left, found)
+                }
+                _ => visit(left, found) && visit(right, found),
+            },
+            Expr::Num(n) => n.is_finite(),
+            Expr::Var(_) => true,
+            Expr::Neg(inner) | Expr::Func(_, inner) => visit(inner, found),
+            Expr::BinaryOp { left, right, .. } => visit(left, found) && visit(right, found),
+            Expr::Sum(operands) | Expr::Product(operands) => operands.iter().all(|e| visit(e, found)),
+        }
+    }
+    let mut found = false;
+    visit(expr, &mut found) && found
 }
 
+/// Replaces every function call on a number with its floating-point value,
+/// e.g. `sqrt 2` with `1.4142135623730951`. Calls outside the function's
+/// domain are left alone.
+fn fold_floats(expr: &Expr) -> Expr {
+    match expr.map_children(fold_floats) {
+        Expr::Func(name, arg) => {
+            let folded = match *arg {
+                Expr::Num(x) => apply_function(&name, x).filter(|v| v.is_finite()),
+                _ => None,
+            };
+            folded.map(Expr::Num).unwrap_or(Expr::Func(name, arg))
+        }
+        other => other,
+    }
+}
 
+/// Replaces every function call or power on numbers that has no real value,
+/// e.g. `sqrt(-4)`, `log(-1)` or `arcsin(2)`, with its principal complex value.
+fn fold_complex(expr: &Expr) -> Expr {
+    match expr.map_children(fold_complex) {
+        Expr::Func(name, arg) => match (Function::from_name(&name), &*arg) {
+            (Some(function), Expr::Num(x)) if function.apply(*x).is_nan() && !x.is_nan() => {
+                complex_value(complex::apply(function, Complex::real(*x))).unwrap_or(Expr::Func(name, arg))
+            }
+            _ => Expr::Func(name, arg),
+        },
+        Expr::BinaryOp { op: Op::Pow, left, right } => match (&*left, &*right) {
+            (Expr::Num(a), Expr::Num(b)) if a.powf(*b).is_nan() && !a.is_nan() && !b.is_nan() => {
+                complex_value(Complex::real(*a).pow(Complex::real(*b)))
+                    .unwrap_or(Expr::BinaryOp { op: Op::Pow, left, right })
+            }
+            _ => Expr::BinaryOp { op: Op::Pow, left, right },
+        },
+        other => other,
+    }
+}
 
+/// A complex value as an expression, unless it is undefined.
+fn complex_value(z: Complex) -> Option<Expr> {
+    (!z.is_nan()).then(|| z.to_expr())
+}
 
-pub fn simplify(expr: &Expr) -> Expr {
+/// Puts an expression into canonical form: sums and products become flat,
+/// sorted n-ary nodes with like terms and equal bases combined, and division
+/// becomes multiplication by a negative power.
+pub fn normalize(expr: &Expr) -> Expr {
     use Expr::*;
     use Op::*;
     match expr {
         Num(n) => Num(*n),
         Var(v) => Var(v.clone()),
         Func(name, arg) => {
-            let simp_arg = simplify(arg);
-            Func(name.clone(), Box::new(simp_arg))
+            let simp_arg = normalize(arg);
+            // Fold calls at notable constants, e.g. sin(pi/6) = 1/2
+            match special_value(name, &simp_arg) {
+                Some(value) => normalize(&value),
+                None => Func(name.clone(), Box::new(simp_arg)),
+            }
         }
-        BinaryOp { op: Add, left, right } => {
-            // Flatten and collect all terms in the sum
+        Neg(inner) => negate(normalize(inner)),
+        BinaryOp { op: Add | Sub, .. } | Sum(_) => {
+            // Flatten the sum, pushing the sign of every `-` down onto its term
             let mut terms = vec![];
-            fn collect_terms(e: &Expr, terms: &mut Vec<Expr>) {
-                if let Expr::BinaryOp { op: Op::Add, left, right } = e {
-                    collect_terms(left, terms);
-                    collect_terms(right, terms);
-                } else {
-                    terms.push(simplify(e));
-                }
-            }
-            collect_terms(left, &mut terms);
-            collect_terms(right, &mut terms);
-            // Combine like terms
-            let mut groups: Vec<(Expr, f64)> = vec![];
-            for term in terms {
-                let (coeff, base) = extract_coeff_and_base(&term);
-                let mut found = false;
-                for (b, c) in &mut groups {
-                    if is_like_term(&base, b) {
-                        *c += coeff;
-                        found = true;
-                        break;
+            fn collect_terms(e: &Expr, negative: bool, terms: &mut Vec<Expr>) {
+                match e {
+                    Expr::BinaryOp { op: Op::Add, left, right } => {
+                        collect_terms(left, negative, terms);
+                        collect_terms(right, negative, terms);
                     }
-                }
-                if !found {
-                    groups.push((base, coeff));
+                    Expr::BinaryOp { op: Op::Sub, left, right } => {
+                        collect_terms(left, negative, terms);
+                        collect_terms(right, !negative, terms);
+                    }
+                    Expr::Sum(inner) => {
+                        for term in inner {
+                            collect_terms(term, negative, terms);
+                        }
+                    }
+                    Expr::Neg(inner) => collect_terms(inner, !negative, terms),
+                    _ if negative => terms.push(negate(normalize(e))),
+                    _ => terms.push(normalize(e)),
                 }
             }
-            // Build the sum
-            let mut result: Option<Expr> = None;
-            for (base, coeff) in groups {
-                let term = if let Expr::Num(1.0) = base {
-                    Expr::Num(coeff)
-                } else if coeff == 1.0 {
-                    base
-                } else if coeff == 0.0 {
-                    Expr::Num(0.0)
-                } else {
-                    Expr::BinaryOp {
-                        op: Op::Mul,
-                        left: Box::new(Expr::Num(coeff)),
-                        right: Box::new(base),
+            collect_terms(expr, false, &mut terms);
+            make_sum(terms)
+        }
+        BinaryOp { op: Mul, .. } | Product(_) => {
+            let mut factors = vec![];
+            fn collect_factors(e: &Expr, factors: &mut Vec<Expr>) {
+                match e {
+                    Expr::BinaryOp { op: Op::Mul, left, right } => {
+                        collect_factors(left, factors);
+                        collect_factors(right, factors);
                     }
-                };
-                result = match result {
-                    None => Some(term),
-                    Some(acc) => Some(Expr::BinaryOp {
-                        op: Op::Add,
-                        left: Box::new(acc),
-                        right: Box::new(term),
-                    }),
-                };
-            }
-            result.unwrap_or(Expr::Num(0.0))
-        }
-        BinaryOp { op, left, right } => {
-            let l = simplify(left);
-            let r = simplify(right);
-            match (op, &l, &r) {
-                // Addition handled above
-                // Subtraction
-                (Sub, x, Num(0.0)) => x.clone(),
-                (Sub, Num(a), Num(b)) => Num(a - b),
-                // Multiplication
-                (Mul, Num(0.0), _) | (Mul, _, Num(0.0)) => Num(0.0),
-                (Mul, Num(1.0), x) => x.clone(),
-                (Mul, x, Num(1.0)) => x.clone(),
-                (Mul, Num(a), Num(b)) => Num(a * b),
-                // Division
-                (Div, x, Num(1.0)) => x.clone(),
-                (Div, Num(a), Num(b)) => Num(a / b),
-                // Power
-                (Pow, _, Num(0.0)) => Num(1.0),
-                (Pow, x, Num(1.0)) => x.clone(),
-                (Pow, Num(a), Num(b)) => Num(a.powf(*b)),
-                // Default: reconstruct
-                _ => BinaryOp {
-                    op: op.clone(),
-                    left: Box::new(l),
-                    right: Box::new(r),
-                },
+                    Expr::Product(inner) => {
+                        for factor in inner {
+                            collect_factors(factor, factors);
+                        }
+                    }
+                    _ => factors.push(normalize(e)),
+                }
             }
+            collect_factors(expr, &mut factors);
+            make_product(factors)
+        }
+        BinaryOp { op: Div, left, right } => {
+            // Division is multiplication by the reciprocal, so common factors cancel
+            let l = normalize(left);
+            let r = normalize(right);
+            make_product(vec![l, make_power(r, Num(-1.0))])
         }
+        BinaryOp { op: Pow, left, right } => make_power(normalize(left), normalize(right)),
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,189 @@
+use std::fmt::Write;
+
+use crate::plot::{y_range, Series};
+
+/// Settings for drawing series as a standalone SVG image, e.g. to show in the
+/// UI or include in a report.
+#[derive(Debug, Clone)]
+pub struct SvgPlot {
+    /// The width of the image, in pixels.
+    pub width: f64,
+    /// The height of the image, in pixels.
+    pub height: f64,
+}
+
+impl Default for SvgPlot {
+    fn default() -> Self {
+        SvgPlot { width: 640.0, height: 400.0 }
+    }
+}
+
+/// The space around the plotting area, left for the tick labels.
+const MARGIN_LEFT: f64 = 56.0;
+const MARGIN_RIGHT: f64 = 16.0;
+const MARGIN_TOP: f64 = 16.0;
+const MARGIN_BOTTOM: f64 = 36.0;
+
+/// The color and dash pattern of each series in turn: the function solid, its
+/// derivative dashed and a tangent dotted.
+const STYLES: [(&str, &str); 4] = [("#1f77b4", ""), ("#d62728", "6 4"), ("#2ca02c", "2 3"), ("#9467bd", "8 3 2 3")];
+
+/// The spacing of the grid lines, in pixels, that [`ticks`] aims for.
+const TICK_SPACING: (f64, f64) = (80.0, 50.0);
+
+impl SvgPlot {
+    /// Draws the series on shared axes with a grid, tick labels and a legend.
+    /// The y range is that of [`y_range`], curves being clipped to it and
+    /// broken where they are undefined or jump across the plot, as at a pole.
+    pub fn render(&self, series: &[Series]) -> Result<String, String> {
+        let (y0, y1) = y_range(series).ok_or("Nothing to plot: no value is finite in the range")?;
+        let xs = series.iter().flat_map(|s| s.xs.iter().copied());
+        let (mut x0, mut x1) = (xs.clone().fold(f64::INFINITY, f64::min), xs.fold(f64::NEG_INFINITY, f64::max));
+        if x0 == x1 {
+            (x0, x1) = (x0 - 1.0, x1 + 1.0);
+        }
+        let (left, right) = (MARGIN_LEFT, self.width - MARGIN_RIGHT);
+        let (top, bottom) = (MARGIN_TOP, self.height - MARGIN_BOTTOM);
+        let px = |x: f64| left + (x - x0) / (x1 - x0) * (right - left);
+        let py = |y: f64| bottom - (y - y0) / (y1 - y0) * (bottom - top);
+
+        let mut svg = String::new();
+        let (w, h) = (self.width, self.height);
+        let _ = writeln!(
+            svg,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{w}" height="{h}" viewBox="0 0 {w} {h}" font-family="sans-serif" font-size="12">"#
+        );
+        let _ = writeln!(svg, r#"<rect width="{w}" height="{h}" fill="white"/>"#);
+        let _ = writeln!(
+            svg,
+            r#"<defs><clipPath id="plot-area"><rect x="{left}" y="{top}" width="{:.2}" height="{:.2}"/></clipPath></defs>"#,
+            right - left,
+            bottom - top
+        );
+
+        // Grid lines with their labels below and left of the plot
+        let (x_ticks, x_step) = ticks(x0, x1, ((right - left) / TICK_SPACING.0).max(2.0));
+        let (y_ticks, y_step) = ticks(y0, y1, ((bottom - top) / TICK_SPACING.1).max(2.0));
+        svg.push_str("<g stroke=\"#e5e5e5\">\n");
+        for &x in &x_ticks {
+            let _ = writeln!(svg, r#"<line x1="{0:.2}" y1="{top}" x2="{0:.2}" y2="{bottom:.2}"/>"#, px(x));
+        }
+        for &y in &y_ticks {
+            let _ = writeln!(svg, r#"<line x1="{left}" y1="{0:.2}" x2="{right:.2}" y2="{0:.2}"/>"#, py(y));
+        }
+        svg.push_str("</g>\n<g fill=\"#555\">\n");
+        for &x in &x_ticks {
+            let _ = writeln!(
+                svg,
+                r#"<text x="{:.2}" y="{:.2}" text-anchor="middle">{}</text>"#,
+                px(x),
+                bottom + 16.0,
+                tick_label(x, x_step)
+            );
+        }
+        for &y in &y_ticks {
+            let _ = writeln!(
+                svg,
+                r#"<text x="{:.2}" y="{:.2}" text-anchor="end">{}</text>"#,
+                left - 6.0,
+                py(y) + 4.0,
+                tick_label(y, y_step)
+            );
+        }
+        svg.push_str("</g>\n");
+
+        // The axes, where they fall inside the plot, and a frame around it
+        svg.push_str("<g stroke=\"#999\">\n");
+        if (y0..=y1).contains(&0.0) {
+            let _ = writeln!(svg, r#"<line x1="{left}" y1="{0:.2}" x2="{right:.2}" y2="{0:.2}"/>"#, py(0.0));
+        }
+        if (x0..=x1).contains(&0.0) {
+            let _ = writeln!(svg, r#"<line x1="{0:.2}" y1="{top}" x2="{0:.2}" y2="{bottom:.2}"/>"#, px(0.0));
+        }
+        let _ = writeln!(
+            svg,
+            r#"<rect x="{left}" y="{top}" width="{:.2}" height="{:.2}" fill="none"/>"#,
+            right - left,
+            bottom - top
+        );
+        svg.push_str("</g>\n");
+
+        svg.push_str("<g clip-path=\"url(#plot-area)\" fill=\"none\" stroke-width=\"2\" stroke-linejoin=\"round\">\n");
+        let height = bottom - top;
+        for (i, s) in series.iter().enumerate() {
+            let (color, dash) = STYLES[i % STYLES.len()];
+            let mut path = String::new();
+            let mut last = None;
+            for (&x, &y) in s.xs.iter().zip(&s.ys) {
+                if !y.is_finite() {
+                    last = None;
+                    continue;
+                }
+                // Far outside the plot a point is as good as clipped, and the path stays small
+                let y = py(y).clamp(top - 10.0 * height, bottom + 10.0 * height);
+                let joined = last.is_some_and(|previous: f64| (y - previous).abs() <= height);
+                let _ = write!(path, "{}{:.2},{:.2} ", if joined { "L" } else { "M" }, px(x), y);
+                last = Some(y);
+            }
+            let _ = writeln!(svg, r#"<path d="{}" stroke="{}"{}/>"#, path.trim_end(), color, dash_attribute(dash));
+        }
+        svg.push_str("</g>\n");
+
+        // The legend, in the top right corner
+        let width = series.iter().map(|s| s.label.chars().count()).max().unwrap_or(0) as f64 * 7.0 + 44.0;
+        let (x, y) = (right - width - 8.0, top + 8.0);
+        let _ = writeln!(
+            svg,
+            r##"<rect x="{x:.2}" y="{y:.2}" width="{width:.2}" height="{:.2}" fill="white" fill-opacity="0.85" stroke="#ccc"/>"##,
+            series.len() as f64 * 18.0 + 8.0
+        );
+        for (i, s) in series.iter().enumerate() {
+            let (color, dash) = STYLES[i % STYLES.len()];
+            let row = y + 15.0 + i as f64 * 18.0;
+            let _ = writeln!(
+                svg,
+                r#"<line x1="{:.2}" y1="{row:.2}" x2="{:.2}" y2="{row:.2}" stroke="{color}" stroke-width="2"{}/>"#,
+                x + 8.0,
+                x + 32.0,
+                dash_attribute(dash)
+            );
+            let _ = writeln!(svg, r#"<text x="{:.2}" y="{:.2}">{}</text>"#, x + 38.0, row + 4.0, escape(&s.label));
+        }
+        svg.push_str("</svg>\n");
+        Ok(svg)
+    }
+}
+
+/// Round numbers from `lo` to `hi` about `count` apart: 1, 2 or 5 times a
+/// power of ten. Returns them with the step between them.
+fn ticks(lo: f64, hi: f64, count: f64) -> (Vec<f64>, f64) {
+    let rough = (hi - lo) / count;
+    let magnitude = 10f64.powf(rough.log10().floor());
+    let step = [1.0, 2.0, 5.0, 10.0]
+        .into_iter()
+        .map(|m| m * magnitude)
+        .find(|&step| step >= rough)
+        .unwrap_or(10.0 * magnitude);
+    let first = (lo / step).ceil() as i64;
+    let last = (hi / step).floor() as i64;
+    ((first..=last).map(|i| i as f64 * step).collect(), step)
+}
+
+/// A tick value with as many decimals as the step between ticks needs.
+fn tick_label(value: f64, step: f64) -> String {
+    let decimals = (-step.log10().floor()).max(0.0) as usize;
+    format!("{:.*}", decimals, value)
+}
+
+fn dash_attribute(dash: &str) -> String {
+    if dash.is_empty() {
+        String::new()
+    } else {
+        format!(r#" stroke-dasharray="{}""#, dash)
+    }
+}
+
+/// Text with the characters that are special in XML escaped.
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
@@ -0,0 +1,90 @@
+use crate::ast::Expr;
+use crate::compile::{grid, CompiledFn};
+use crate::context::Context;
+
+/// Values of a function and some of its derivatives at evenly spaced points.
+#[derive(Debug, Clone)]
+pub struct Table {
+    /// The column names, e.g. `x`, `f`, `f'` and `f''`.
+    pub headers: Vec<String>,
+    /// One column per header, the points first. A value is NaN where the
+    /// function is undefined, such as `log` at a negative number, and so are
+    /// the derivatives there, even where their formulas have values.
+    pub columns: Vec<Vec<f64>>,
+}
+
+impl Table {
+    /// The number of rows, i.e. of points.
+    pub fn rows(&self) -> usize {
+        self.columns.first().map_or(0, Vec::len)
+    }
+
+    /// The table as CSV with a header line. Values that are undefined or
+    /// infinite are left as empty cells.
+    pub fn to_csv(&self) -> String {
+        let mut csv = self.headers.join(",");
+        csv.push('\n');
+        for row in 0..self.rows() {
+            let cells: Vec<String> = self.columns.iter().map(|column| cell(column[row])).collect();
+            csv.push_str(&cells.join(","));
+            csv.push('\n');
+        }
+        csv
+    }
+}
+
+/// A value as a CSV cell, in scientific notation when very large or small so
+/// that e.g. `sin(pi)` does not take up 30 digits.
+fn cell(value: f64) -> String {
+    let magnitude = value.abs();
+    if !value.is_finite() {
+        String::new()
+    } else if magnitude != 0.0 && !(1e-6..1e16).contains(&magnitude) {
+        format!("{:e}", value)
+    } else {
+        // Adding zero turns -0 into 0
+        (value + 0.0).to_string()
+    }
+}
+
+/// The column name for a derivative: `f`, `f'`, `f''`, `f'''`, then `f^(4)` and so on.
+fn header(order: usize) -> String {
+    match order {
+        0..=3 => format!("f{}", "'".repeat(order)),
+        _ => format!("f^({})", order),
+    }
+}
+
+/// Samples `expr` and the derivatives with respect to `var` of the given
+/// `orders` (0 for the function itself) at `steps + 1` evenly spaced points
+/// from `from` to `to`. Where the function is not finite, neither are its
+/// derivatives: `1/x` is no derivative of `log(x)` at `x = -1`. Fails if the
+/// expression uses a variable other than `var`.
+pub fn tabulate(
+    expr: &Expr,
+    var: &str,
+    from: f64,
+    to: f64,
+    steps: usize,
+    orders: &[usize],
+    ctx: &Context,
+) -> Result<Table, String> {
+    let highest = orders.iter().copied().max().unwrap_or(0);
+    let compiled = CompiledFn::with_derivatives(expr, var, highest, &[var], ctx)?;
+    let points = grid(from, to, steps + 1);
+    let mut values = vec![vec![0.0; points.len()]; highest + 1];
+    let mut outputs: Vec<&mut [f64]> = values.iter_mut().map(Vec::as_mut_slice).collect();
+    compiled.eval_batch(&[&points], &mut outputs)?;
+    let (f, derivatives) = values.split_first_mut().expect("the function itself is always evaluated");
+    for (i, _) in f.iter().enumerate().filter(|(_, v)| !v.is_finite()) {
+        for column in derivatives.iter_mut() {
+            column[i] = f64::NAN;
+        }
+    }
+
+    let mut headers = vec![var.to_string()];
+    headers.extend(orders.iter().map(|&k| header(k)));
+    let mut columns = vec![points];
+    columns.extend(orders.iter().map(|&k| values[k].clone()));
+    Ok(Table { headers, columns })
+}
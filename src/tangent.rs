@@ -0,0 +1,88 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::ast::{Expr, Op};
+use crate::context::Context;
+use crate::derivative::derivative_with;
+use crate::eval::{eval, eval_constant};
+use crate::simplifier::simplify_with;
+
+/// A line `y = slope * x + intercept` that is not vertical.
+#[derive(Debug, Clone)]
+pub struct Line {
+    /// The right-hand side, simplified, in the variable of the function.
+    pub expr: Expr,
+    pub slope: f64,
+    pub intercept: f64,
+}
+
+impl fmt::Display for Line {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "y = {}", self.expr)
+    }
+}
+
+/// The tangent and normal lines to the graph of a function at a point.
+#[derive(Debug, Clone)]
+pub struct TangentLines {
+    /// The point `(x0, f(x0))` both lines pass through.
+    pub point: (f64, f64),
+    pub tangent: Line,
+    /// `None` when the tangent is horizontal, the normal then being the
+    /// vertical line `x = x0`.
+    pub normal: Option<Line>,
+}
+
+/// The tangent line `y = f(x0) + f'(x0) (x - x0)` to the graph of `expr` as a
+/// function of `var`, and the normal line through the same point. `x0` is an
+/// expression so that the lines stay exact, e.g. `y = 1/2 * x + sqrt 3 / 2 - pi / 12`
+/// for `sin(x)` at `pi/6`. Fails if `f` or `f'` has no finite value at `x0`,
+/// or if `f` is not differentiable there, as `abs(x)` is not at 0.
+pub fn tangent_lines(expr: &Expr, var: &str, x0: &Expr, ctx: &Context) -> Result<TangentLines, String> {
+    let at = |e: &Expr| simplify_with(&e.substitute(var, x0), ctx);
+    let number = |e: &Expr, what: &str| {
+        eval_constant(&ctx.angle.to_radians(e))
+            .filter(|v| v.is_finite())
+            .ok_or_else(|| format!("{} at {} = {} is not a finite number: {}", what, var, x0, e))
+    };
+    let y0 = at(expr)?;
+    let derivative = derivative_with(expr, var, ctx)?;
+    let slope = at(&derivative)?;
+    let point = (number(x0, "The point")?, number(&y0, "The value")?);
+    let m = number(&slope, "The derivative")?;
+    if jumps(&ctx.angle.to_radians(&derivative), var, point.0) {
+        return Err(format!("The function is not differentiable at {} = {}", var, x0));
+    }
+
+    let (x, y) = point;
+    let tangent = Line { expr: line(&slope, var, x0, &y0, ctx)?, slope: m, intercept: y - m * x };
+    let normal = if m == 0.0 {
+        None
+    } else {
+        let perpendicular = Expr::BinaryOp { op: Op::Div, left: Box::new(Expr::Num(-1.0)), right: Box::new(slope) };
+        let expr = line(&simplify_with(&perpendicular, ctx)?, var, x0, &y0, ctx)?;
+        Some(Line { expr, slope: -1.0 / m, intercept: y + x / m })
+    };
+    Ok(TangentLines { point, tangent, normal })
+}
+
+/// Whether `derivative` jumps at `x0` or has no value just beside it. Its
+/// value at `x0` itself can hide this: `x / abs(x)`, the derivative of
+/// `abs(x)`, simplifies to `0` at `x = 0`. On both sides of a point where the
+/// function is smooth, the derivative differs by about `2 h f''(x0)`, so the
+/// gap shrinks with `h`, while a jump stays.
+fn jumps(derivative: &Expr, var: &str, x0: f64) -> bool {
+    let value = |x: f64| eval(derivative, &HashMap::from([(var.to_string(), x)])).unwrap_or(f64::NAN);
+    let gap = |h: f64| (value(x0 + h) - value(x0 - h)).abs();
+    let h = 1e-6 * x0.abs().max(1.0);
+    let (wide, narrow) = (gap(h), gap(h / 1000.0));
+    let scale = value(x0 + h / 1000.0).abs().max(1.0);
+    !narrow.is_finite() || (narrow > 1e-6 * scale && narrow > wide / 10.0)
+}
+
+/// The line with the given slope through `(x0, y0)`, as `slope * x + y0 - slope * x0`, simplified.
+fn line(slope: &Expr, var: &str, x0: &Expr, y0: &Expr, ctx: &Context) -> Result<Expr, String> {
+    let offset = Expr::Product(vec![Expr::Num(-1.0), slope.clone(), x0.clone()]);
+    let expr = Expr::Sum(vec![Expr::Product(vec![slope.clone(), Expr::Var(var.to_string())]), y0.clone(), offset]);
+    simplify_with(&expr, ctx)
+}
@@ -39,7 +39,7 @@ fn tokenize_help(input: &str) -> Result<Vec<Token>, String> {
     ];
     
     // Sort by length (longest first) to ensure longer function names are matched before shorter ones
-    parser_functions.sort_by(|a, b| b.len().cmp(&a.len()));
+    parser_functions.sort_by_key(|f| std::cmp::Reverse(f.len()));
 
     while let Some(&c) = chars.peek() {
         match c {
@@ -48,7 +48,7 @@ fn tokenize_help(input: &str) -> Result<Vec<Token>, String> {
                 let mut num_str = String::new();
                 // Accumulate digits and decimal points
                 while let Some(&c) = chars.peek() {
-                    if c.is_digit(10) || c == '.' {
+                    if c.is_ascii_digit() || c == '.' {
                         num_str.push(c);
                         chars.next();
                     } else {
@@ -187,12 +187,11 @@ pub fn tokenize(input: &str) -> Result<Vec<Token>, String> {
         // Detect implicit power: if a Var is immediately followed by a Num,
         // and not already followed by an explicit Pow token,
         // insert a Pow token between them.
-        if let Token::Var(_) = tokens[i] {
-            if let Some(Token::Num(_)) = tokens.get(i + 1) {
-                if tokens.get(i + 2) != Some(&Token::Pow) {
-                    final_tokens.push(Token::Pow);
-                }
-            }
+        if matches!(tokens[i], Token::Var(_))
+            && matches!(tokens.get(i + 1), Some(Token::Num(_)))
+            && tokens.get(i + 2) != Some(&Token::Pow)
+        {
+            final_tokens.push(Token::Pow);
         }
 
         i += 1;
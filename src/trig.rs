@@ -0,0 +1,139 @@
+use crate::rewrite::RuleSet;
+
+/// How `simplify` treats trigonometric and hyperbolic functions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TrigMode {
+    /// Leave trigonometric functions as they are (apart from the built-in rules).
+    #[default]
+    Off,
+    /// Apply the Pythagorean, double-angle and reciprocal identities, preferring
+    /// the most compact function, e.g. `1/cos(x)` becomes `sec(x)`.
+    Identities,
+    /// Rewrite everything in terms of `sin`/`cos` (and `sinh`/`cosh`).
+    SinCos,
+    /// Rewrite everything in terms of `exp`, circular functions through the
+    /// imaginary unit, e.g. `cos(x) = (exp(i*x) + exp(-i*x))/2`.
+    Exponential,
+}
+
+impl TrigMode {
+    /// Parses a mode name as accepted by the `--trig` option.
+    pub fn parse(name: &str) -> Result<Self, String> {
+        match name {
+            "off" => Ok(TrigMode::Off),
+            "identities" => Ok(TrigMode::Identities),
+            "sincos" => Ok(TrigMode::SinCos),
+            "exp" => Ok(TrigMode::Exponential),
+            _ => Err(format!("Unknown trig mode '{}' (expected off, identities, sincos or exp)", name)),
+        }
+    }
+
+    /// The rewrite rules for this mode, applied in addition to the context's rules.
+    pub fn rules(self) -> &'static RuleSet {
+        match self {
+            TrigMode::Off => &NO_RULES,
+            TrigMode::Identities => &IDENTITY_RULES,
+            TrigMode::SinCos => &SIN_COS_RULES,
+            TrigMode::Exponential => &EXP_RULES,
+        }
+    }
+}
+
+const IDENTITIES: &str = "
+# Quotient and reciprocal identities, most specific first
+sin(a)/cos(a)^2 -> tan(a)*sec(a)
+cos(a)/sin(a)^2 -> cot(a)*cosec(a)
+sin(a)/cos(a) -> tan(a)
+cos(a)/sin(a) -> cot(a)
+1/sin(a)^2 -> cosec(a)^2
+1/cos(a)^2 -> sec(a)^2
+1/tan(a)^2 -> cot(a)^2
+1/sin(a) -> cosec(a)
+1/cos(a) -> sec(a)
+1/tan(a) -> cot(a)
+sinh(a)/cosh(a)^2 -> tanh(a)*sech(a)
+cosh(a)/sinh(a)^2 -> coth(a)*cosech(a)
+sinh(a)/cosh(a) -> tanh(a)
+cosh(a)/sinh(a) -> coth(a)
+1/sinh(a)^2 -> cosech(a)^2
+1/cosh(a)^2 -> sech(a)^2
+1/tanh(a)^2 -> coth(a)^2
+1/sinh(a) -> cosech(a)
+1/cosh(a) -> sech(a)
+1/tanh(a) -> coth(a)
+
+# Pythagorean identities
+1 - sin(a)^2 -> cos(a)^2
+1 - cos(a)^2 -> sin(a)^2
+1 + tan(a)^2 -> sec(a)^2
+1 + cot(a)^2 -> cosec(a)^2
+sec(a)^2 - 1 -> tan(a)^2
+cosec(a)^2 - 1 -> cot(a)^2
+1 + sinh(a)^2 -> cosh(a)^2
+cosh(a)^2 - 1 -> sinh(a)^2
+1 - tanh(a)^2 -> sech(a)^2
+coth(a)^2 - 1 -> cosech(a)^2
+
+# Functions of inverse functions, as in the derivatives of compositions
+sin(arcsin(a)) -> a
+cos(arccos(a)) -> a
+tan(arctan(a)) -> a
+# Squares before square roots, since sqrt(a)^2 only cancels for positive a
+cos(arcsin(a))^2 -> 1 - a^2
+sin(arccos(a))^2 -> 1 - a^2
+sec(arctan(a))^2 -> 1 + a^2
+cosh(arsinh(a))^2 -> 1 + a^2
+sech(artanh(a))^2 -> 1 - a^2
+cos(arcsin(a)) -> sqrt(1 - a^2)
+sin(arccos(a)) -> sqrt(1 - a^2)
+tan(arcsin(a)) -> a/sqrt(1 - a^2)
+tan(arccos(a)) -> sqrt(1 - a^2)/a
+sin(arctan(a)) -> a/sqrt(1 + a^2)
+cos(arctan(a)) -> 1/sqrt(1 + a^2)
+sec(arctan(a)) -> sqrt(1 + a^2)
+sinh(arsinh(a)) -> a
+cosh(arcosh(a)) -> a
+tanh(artanh(a)) -> a
+cosh(arsinh(a)) -> sqrt(1 + a^2)
+cosh(artanh(a)) -> 1/sqrt(1 - a^2)
+sech(artanh(a)) -> sqrt(1 - a^2)
+
+# Double-angle identities
+2*sin(a)*cos(a) -> sin(2a)
+cos(a)^2 - sin(a)^2 -> cos(2a)
+2*sinh(a)*cosh(a) -> sinh(2a)
+cosh(a)^2 + sinh(a)^2 -> cosh(2a)
+";
+
+const SIN_COS: &str = "
+tan(a) -> sin(a)/cos(a)
+cot(a) -> cos(a)/sin(a)
+sec(a) -> 1/cos(a)
+cosec(a) -> 1/sin(a)
+tanh(a) -> sinh(a)/cosh(a)
+coth(a) -> cosh(a)/sinh(a)
+sech(a) -> 1/cosh(a)
+cosech(a) -> 1/sinh(a)
+";
+
+const EXPONENTIAL: &str = "
+sin(a) -> (exp(i*a) - exp(-i*a))/(2*i)
+cos(a) -> (exp(i*a) + exp(-i*a))/2
+sinh(a) -> (exp(a) - exp(-a))/2
+cosh(a) -> (exp(a) + exp(-a))/2
+tanh(a) -> (exp(a) - exp(-a))/(exp(a) + exp(-a))
+coth(a) -> (exp(a) + exp(-a))/(exp(a) - exp(-a))
+sech(a) -> 2/(exp(a) + exp(-a))
+cosech(a) -> 2/(exp(a) - exp(-a))
+";
+
+lazy_static::lazy_static! {
+    static ref NO_RULES: RuleSet = RuleSet::default();
+    static ref IDENTITY_RULES: RuleSet = RuleSet::parse(IDENTITIES).expect("trig identities must parse");
+    static ref SIN_COS_RULES: RuleSet = RuleSet::parse(SIN_COS).expect("sin/cos rules must parse");
+    static ref EXP_RULES: RuleSet = {
+        let mut rules = RuleSet::parse(SIN_COS).expect("sin/cos rules must parse");
+        rules.extend(RuleSet::parse(EXPONENTIAL).expect("exponential rules must parse"));
+        rules
+    };
+}
@@ -0,0 +1,54 @@
+use std::collections::HashMap;
+
+use derivative_calculator::angle::AngleMode;
+use derivative_calculator::context::Context;
+use derivative_calculator::derivative::derivative_with;
+use derivative_calculator::eval::eval;
+use derivative_calculator::simplifier::simplify_with;
+
+mod common;
+use common::parse;
+
+fn degrees() -> Context {
+    Context { angle: AngleMode::Degrees, ..Context::default() }
+}
+
+#[test]
+fn modes_are_read_by_name() {
+    assert_eq!(AngleMode::parse("deg"), Ok(AngleMode::Degrees));
+    assert_eq!(AngleMode::parse("degrees"), Ok(AngleMode::Degrees));
+    assert_eq!(AngleMode::parse("rad"), Ok(AngleMode::Radians));
+    assert!(AngleMode::parse("grad").is_err());
+}
+
+#[test]
+fn trig_functions_take_and_give_degrees() {
+    let ctx = degrees();
+    for (f, expected) in [
+        ("sin(30)", "0.5"),
+        ("tan(45)", "1"),
+        ("cos(60) + sin(x)", "(sin x + 0.5)"),
+        ("arctan(1)", "45"),
+        ("arcsin(1/2)", "30"),
+        ("arcsin(1/2) + x", "(x + 30)"),
+        ("sin(x)^2 + cos(x)^2", "1"),
+        // Hyperbolic functions have no angle
+        ("sinh(1)", "sinh 1"),
+    ] {
+        assert_eq!(simplify_with(&parse(f), &ctx).unwrap().to_string(), expected, "simplify {}", f);
+    }
+    let radians = AngleMode::Degrees.to_radians(&parse("sin(x)"));
+    let value = eval(&radians, &HashMap::from([("x".to_string(), 30.0)])).unwrap();
+    assert!((value - 0.5).abs() < 1e-15);
+}
+
+#[test]
+fn derivatives_pick_up_the_chain_factor() {
+    let ctx = degrees();
+    let derive = |f: &str| simplify_with(&derivative_with(&parse(f), "x", &ctx).unwrap(), &ctx).unwrap().to_string();
+    assert_eq!(derive("sin(x)"), "(deg * cos x)");
+    assert_eq!(derive("x * sin(x)"), "((deg * cos x * x) + sin x)");
+    assert_eq!(derive("sinh(x)"), "cosh x");
+    // d/dx arctan(x) in degrees per unit is 180/pi times the radian one
+    assert_eq!(derive("arctan(x)"), format!("({} / ((x ^ 2) + 1))", 180.0 / std::f64::consts::PI));
+}
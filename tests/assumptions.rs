@@ -0,0 +1,70 @@
+use derivative_calculator::assumptions::{Assumptions, SymbolFacts};
+use derivative_calculator::context::Context;
+use derivative_calculator::derivative::derivative_with;
+use derivative_calculator::simplifier::{simplify, simplify_with};
+
+mod common;
+use common::parse;
+
+fn assuming(specs: &[&str]) -> Context {
+    let mut assumptions = Assumptions::default();
+    for spec in specs {
+        assumptions.assume(spec).unwrap();
+    }
+    Context { assumptions, ..Context::default() }
+}
+
+#[test]
+fn assumptions_are_read_from_their_written_forms() {
+    let ctx = assuming(&["x>0", " y != 0 ", "n:integer", "r:real"]);
+    let facts = |name| ctx.assumptions.facts(name);
+    assert_eq!(facts("x"), SymbolFacts { real: true, positive: true, nonzero: true, integer: false });
+    assert_eq!(facts("y"), SymbolFacts { nonzero: true, ..SymbolFacts::default() });
+    assert_eq!(facts("n"), SymbolFacts { real: true, integer: true, ..SymbolFacts::default() });
+    assert_eq!(facts("r"), SymbolFacts { real: true, ..SymbolFacts::default() });
+    assert_eq!(facts("z"), SymbolFacts::default());
+
+    let mut assumptions = Assumptions::default();
+    for spec in ["x", "2>0", "x:big", ">0"] {
+        assert!(assumptions.assume(spec).is_err(), "assume {}", spec);
+    }
+}
+
+#[test]
+fn facts_carry_over_to_compound_expressions() {
+    let ctx = assuming(&["x>0", "y!=0", "y:real", "n:integer"]);
+    let a = &ctx.assumptions;
+    let canonical = |text| simplify(&parse(text)).unwrap();
+    assert!(a.is_positive(&canonical("x^2 + 1")));
+    assert!(a.is_positive(&canonical("y^2")));
+    assert!(a.is_positive(&canonical("exp(y) * sqrt(x)")));
+    assert!(!a.is_positive(&canonical("y")));
+    assert!(!a.is_positive(&canonical("x - 1")));
+    assert!(a.is_nonzero(&canonical("x * y")));
+    assert!(a.is_integer(&canonical("2*n + 1")));
+    assert!(a.is_real(&canonical("log(x)")));
+    assert!(!a.is_real(&canonical("log(y)")));
+}
+
+#[test]
+fn the_simplifier_uses_the_assumptions() {
+    let ctx = assuming(&["x>0"]);
+    for (f, expected) in [("sqrt(x^2)", "x"), ("abs(x)/x", "1"), ("abs(x)", "x")] {
+        assert_eq!(simplify_with(&parse(f), &ctx).unwrap().to_string(), expected, "simplify {}", f);
+        assert_ne!(simplify(&parse(f)).unwrap().to_string(), expected, "simplify {} without assumptions", f);
+    }
+    // Nothing is known about y
+    assert_eq!(simplify_with(&parse("sqrt(y^2)"), &ctx).unwrap().to_string(), "sqrt (y ^ 2)");
+}
+
+#[test]
+fn derivative_rules_drop_the_absolute_value_for_positive_arguments() {
+    let derive = |f: &str, ctx: &Context| simplify_with(&derivative_with(&parse(f), "x", ctx).unwrap(), ctx).unwrap().to_string();
+    let positive = assuming(&["x>0"]);
+    let unknown = Context::default();
+    assert_eq!(derive("abs(x)", &positive), "1");
+    assert_eq!(derive("abs(x)", &unknown), "(x / abs x)");
+    assert_eq!(derive("arcsec(x)", &positive), "(1 / (sqrt ((x ^ 2) - 1) * x))");
+    assert_eq!(derive("arcsec(x)", &unknown), "(1 / (abs x * sqrt ((x ^ 2) - 1)))");
+    assert_eq!(derive("arccsc(x)", &positive), "-(1 / (sqrt ((x ^ 2) - 1) * x))");
+}
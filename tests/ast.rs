@@ -0,0 +1,86 @@
+use std::cmp::Ordering;
+
+use derivative_calculator::ast::Expr;
+use derivative_calculator::simplifier::{normalize, simplify};
+
+mod common;
+use common::parse;
+
+#[test]
+fn unary_minus_parses_as_negation() {
+    assert_eq!(parse("-x"), Expr::Neg(Box::new(Expr::Var("x".to_string()))));
+    assert_eq!(parse("--x"), Expr::Var("x".to_string()));
+    assert_eq!(parse("-2"), Expr::Num(-2.0));
+}
+
+#[test]
+fn negated_operands_print_in_parentheses() {
+    for (text, shown) in [
+        ("-x^2", "((-x) ^ 2)"),
+        ("-(x+1)^2", "((-(x + 1)) ^ 2)"),
+        ("sin(-x)", "sin (-x)"),
+        ("-sin(x)", "-sin x"),
+        ("x - y", "(x - y)"),
+    ] {
+        assert_eq!(parse(text).to_string(), shown, "{}", text);
+    }
+}
+
+#[test]
+fn printed_negations_parse_back_to_the_same_value() {
+    for text in ["-x^2", "-(x+1)^2", "sin(-x)^2", "exp(-x)*cos(-2x)"] {
+        let expr = parse(text);
+        let reparsed = parse(&expr.to_string());
+        assert_eq!(simplify(&reparsed).unwrap(), simplify(&expr).unwrap(), "{} printed as {}", text, expr);
+    }
+}
+
+#[test]
+fn like_terms_collect_across_subtraction() {
+    assert_eq!(simplify(&parse("3x - x - 2x")).unwrap(), Expr::Num(0.0));
+    assert_eq!(simplify(&parse("x - (-x)")).unwrap().to_string(), "(2 * x)");
+}
+
+#[test]
+fn negation_normalizes_to_a_coefficient_of_minus_one() {
+    assert_eq!(normalize(&parse("-x")), normalize(&parse("(-1)*x")));
+    assert_eq!(normalize(&parse("x - y")), normalize(&parse("x + (-1)*y")));
+    fn has_neg(e: &Expr) -> bool {
+        matches!(e, Expr::Neg(_)) || e.children().into_iter().any(has_neg)
+    }
+    for text in ["-x", "-(x + 1)^2", "a - (b - c)", "sin(-x) - -y"] {
+        assert!(!has_neg(&normalize(&parse(text))), "{}", text);
+    }
+}
+
+#[test]
+fn sums_and_products_have_one_canonical_form() {
+    for (a, b) in [("x + y + 1", "1 + y + x"), ("x*y*2", "2*y*x"), ("a*(b + c)", "(c + b)*a")] {
+        let (a, b) = (simplify(&parse(a)).unwrap(), simplify(&parse(b)).unwrap());
+        assert_eq!(a, b);
+        assert_eq!(a.canonical_cmp(&b), Ordering::Equal);
+    }
+    // Nested sums and products are flattened
+    match simplify(&parse("x + (y + z)")).unwrap() {
+        Expr::Sum(terms) => assert_eq!(terms.len(), 3),
+        other => panic!("not a sum: {}", other),
+    }
+    match simplify(&parse("2*x*(3*y)")).unwrap() {
+        Expr::Product(factors) => assert_eq!(factors[0], Expr::Num(6.0)),
+        other => panic!("not a product: {}", other),
+    }
+}
+
+#[test]
+fn canonical_order_puts_numbers_first_and_like_terms_together() {
+    let canonical = |text| simplify(&parse(text)).unwrap();
+    let mut operands: Vec<Expr> = ["x^2", "y", "3", "x", "2*x"].into_iter().map(canonical).collect();
+    operands.sort_by(|a, b| a.canonical_cmp(b));
+    assert_eq!(operands[0], Expr::Num(3.0));
+    let mut at: Vec<usize> = ["x", "2*x", "x^2"]
+        .iter()
+        .map(|text| operands.iter().position(|e| *e == canonical(text)).unwrap())
+        .collect();
+    at.sort();
+    assert_eq!(at[2] - at[0], 2, "{:?}", operands);
+}
@@ -0,0 +1,14 @@
+//! Helpers shared by the integration tests. Each test crate uses only some of them.
+#![allow(dead_code)]
+
+use derivative_calculator::ast::Expr;
+use derivative_calculator::{parser, tokenizer};
+
+/// Parses `text`, panicking on malformed input.
+pub fn parse(text: &str) -> Expr {
+    try_parse(text).unwrap()
+}
+
+pub fn try_parse(text: &str) -> Result<Expr, String> {
+    parser::Parser::new(tokenizer::tokenize(text)?).parse()
+}
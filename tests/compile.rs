@@ -0,0 +1,109 @@
+use std::collections::HashMap;
+
+use derivative_calculator::compile::{CompiledFn, grid};
+use derivative_calculator::context::Context;
+use derivative_calculator::derivative::derivative;
+use derivative_calculator::eval::eval;
+use derivative_calculator::simplifier::simplify;
+
+mod common;
+use common::parse;
+
+const POINTS: [(f64, f64); 5] = [(0.5, 2.0), (1.0, -1.0), (2.5, 0.25), (-0.75, 3.0), (7.0, 0.001)];
+
+fn assert_close(a: f64, b: f64, what: &str) {
+    let agree = (a.is_nan() && b.is_nan()) || a == b || (a - b).abs() <= 1e-12 * a.abs().max(b.abs()).max(1.0);
+    assert!(agree, "{}: {} != {}", what, a, b);
+}
+
+fn values(x: f64, y: f64) -> HashMap<String, f64> {
+    HashMap::from([("x".to_string(), x), ("y".to_string(), y)])
+}
+
+#[test]
+fn compiled_functions_agree_with_eval() {
+    for f in [
+        "x^2 + 3*x*y - y/x",
+        "sin(x)*cos(y) + tan(x*y)",
+        "exp(-x^2) * log(y)",
+        "sqrt(x) + abs(y) - x^y",
+        "arctan(x/y) + sinh(x) - (x+1)^(-3)",
+        "-(x - y)^5",
+        "2^10 * pi + x",
+    ] {
+        let expr = parse(f);
+        let compiled = CompiledFn::compile(&expr, &["x", "y"]).unwrap();
+        for (x, y) in POINTS {
+            assert_close(compiled.call(&[x, y]), eval(&expr, &values(x, y)).unwrap(), &format!("{} at ({}, {})", f, x, y));
+        }
+    }
+}
+
+#[test]
+fn constants_are_folded_and_shared_work_is_done_once() {
+    let constant = CompiledFn::compile(&parse("2^10 * pi + sin(0)"), &["x"]).unwrap();
+    assert_eq!(constant.len(), 1);
+    let once = CompiledFn::compile(&parse("sin(x*y)"), &["x", "y"]).unwrap();
+    let twice = CompiledFn::compile(&parse("sin(x*y) + sin(x*y)^2"), &["x", "y"]).unwrap();
+    // The second copy of sin(x*y) adds no instructions, only the power and the sum
+    assert_eq!(twice.len(), once.len() + 2);
+}
+
+#[test]
+fn compiling_fails_on_unknown_variables_and_functions() {
+    assert!(CompiledFn::compile(&parse("x + z"), &["x", "y"]).is_err());
+    assert!(CompiledFn::compile(&parse("frobnicate(x)"), &["x"]).is_err());
+}
+
+#[test]
+fn derivatives_compile_alongside_the_function() {
+    let f = parse("x^3 * sin(x) + y*x");
+    let compiled = CompiledFn::with_derivative(&f, "x", &["x", "y"], &Context::default()).unwrap();
+    let derivative = simplify(&derivative(&f, "x").unwrap()).unwrap();
+    for (x, y) in POINTS {
+        let [value, slope] = compiled.call_all(&[x, y])[..] else { panic!("expected two outputs") };
+        assert_close(value, eval(&f, &values(x, y)).unwrap(), "f");
+        assert_close(slope, eval(&derivative, &values(x, y)).unwrap(), "f'");
+    }
+    let orders = CompiledFn::with_derivatives(&parse("x^4"), "x", 4, &["x"], &Context::default()).unwrap();
+    assert_eq!(orders.call_all(&[2.0]), vec![16.0, 32.0, 48.0, 48.0, 24.0]);
+}
+
+#[test]
+fn batches_agree_with_calling_point_by_point() {
+    let f = parse("sin(x)*exp(-y) + x^2/y");
+    let compiled = CompiledFn::with_derivative(&f, "x", &["x", "y"], &Context::default()).unwrap();
+    // More points than one chunk, and not a multiple of its size
+    let xs = grid(-3.0, 3.0, 1000);
+    let ys: Vec<f64> = xs.iter().map(|x| 1.5 + x.cos()).collect();
+    let (mut values, mut slopes) = (vec![0.0; xs.len()], vec![0.0; xs.len()]);
+    compiled.eval_batch(&[&xs, &ys], &mut [&mut values, &mut slopes]).unwrap();
+    for i in 0..xs.len() {
+        let point = compiled.call_all(&[xs[i], ys[i]]);
+        assert_eq!([values[i], slopes[i]], [point[0], point[1]], "at ({}, {})", xs[i], ys[i]);
+    }
+}
+
+#[test]
+fn parallel_batches_agree_with_serial_ones() {
+    let compiled = CompiledFn::compile(&parse("x^3 - 2*x + sqrt(abs(x))"), &["x"]).unwrap();
+    let xs = grid(-10.0, 10.0, 100_003);
+    let (mut serial, mut parallel) = (vec![0.0; xs.len()], vec![0.0; xs.len()]);
+    compiled.eval_batch(&[&xs], &mut [&mut serial]).unwrap();
+    compiled.eval_batch_parallel(&[&xs], &mut [&mut parallel]).unwrap();
+    assert_eq!(serial, parallel);
+}
+
+#[test]
+fn batches_check_their_columns_and_buffers() {
+    let compiled = CompiledFn::compile(&parse("x*y"), &["x", "y"]).unwrap();
+    let (xs, ys) = (grid(0.0, 1.0, 10), grid(0.0, 1.0, 9));
+    let mut out = vec![0.0; 10];
+    assert!(compiled.eval_batch(&[&xs], &mut [&mut out]).is_err());
+    assert!(compiled.eval_batch(&[&xs, &ys], &mut [&mut out]).is_err());
+    let mut extra = vec![0.0; 10];
+    assert!(compiled.eval_batch(&[&xs, &xs], &mut [&mut out, &mut extra]).is_err());
+    assert!(compiled.eval_batch(&[&xs, &xs], &mut [&mut out]).is_ok());
+    assert_eq!(grid(0.0, 1.0, 3), vec![0.0, 0.5, 1.0]);
+    assert!(grid(0.0, 1.0, 0).is_empty());
+}
@@ -0,0 +1,64 @@
+use std::collections::HashMap;
+use std::f64::consts::{FRAC_PI_2, PI};
+
+use derivative_calculator::complex::{Complex, eval_complex};
+use derivative_calculator::context::Context;
+use derivative_calculator::derivative::derivative;
+use derivative_calculator::eval::eval;
+use derivative_calculator::simplifier::{simplify, simplify_with};
+
+mod common;
+use common::parse;
+
+fn value(text: &str) -> Complex {
+    eval_complex(&parse(text), &HashMap::new()).unwrap()
+}
+
+fn assert_close(z: Complex, re: f64, im: f64) {
+    assert!((z.re - re).abs() < 1e-12 && (z.im - im).abs() < 1e-12, "{:?} != {} + {}i", z, re, im);
+}
+
+#[test]
+fn functions_take_their_principal_values() {
+    assert_close(value("log(-1)"), 0.0, PI);
+    assert_close(value("sqrt(-4)"), 0.0, 2.0);
+    assert_close(value("i^2"), -1.0, 0.0);
+    assert_close(value("exp(i*pi)"), -1.0, 0.0);
+    assert_close(value("(1+i)*(1-i)"), 2.0, 0.0);
+    // Off the real line, inverse functions follow the conventions of C99 and
+    // Python's cmath
+    assert_close(value("arcsin(2)"), FRAC_PI_2, 1.3169578969248166);
+    assert_close(value("arccos(2)"), 0.0, -1.3169578969248166);
+    assert_close(value("arctan(2*i)"), FRAC_PI_2, 0.5493061443340548);
+    assert_eq!(value("log(-1)").to_string(), "(pi * i)");
+}
+
+#[test]
+fn real_arguments_in_the_domain_give_the_real_values() {
+    let vars = HashMap::from([("x".to_string(), 0.3)]);
+    let complex_vars = HashMap::from([("x".to_string(), Complex::real(0.3))]);
+    for f in ["sin(x)*cosh(x)", "log(x) + sqrt(x)", "arcsin(x) - arctan(x)", "x^x", "artanh(x) / sec(x)"] {
+        let z = eval_complex(&parse(f), &complex_vars).unwrap();
+        let real = eval(&parse(f), &vars).unwrap();
+        assert!((z.re - real).abs() < 1e-12 && z.im == 0.0, "{}: {:?} != {}", f, z, real);
+    }
+    assert!(eval_complex(&parse("x + y"), &complex_vars).is_err());
+}
+
+#[test]
+fn powers_of_i_simplify() {
+    for (f, expected) in [("i^2", "-1"), ("i^3", "-i"), ("i^4", "1"), ("x*i*i", "-x")] {
+        assert_eq!(simplify(&parse(f)).unwrap().to_string(), expected, "simplify {}", f);
+    }
+}
+
+#[test]
+fn complex_mode_replaces_calls_on_numbers_by_their_complex_value() {
+    let ctx = Context { complex: true, ..Context::default() };
+    for (f, expected) in [("sqrt(-4)", "(2 * i)"), ("log(-1)", "(pi * i)")] {
+        assert_eq!(simplify_with(&parse(f), &ctx).unwrap().to_string(), expected, "simplify {}", f);
+        assert_ne!(simplify(&parse(f)).unwrap().to_string(), expected, "simplify {} outside complex mode", f);
+    }
+    let d = simplify(&derivative(&parse("exp(i*x)"), "x").unwrap()).unwrap();
+    assert_eq!(d.to_string(), "(exp (i * x) * i)");
+}
@@ -0,0 +1,29 @@
+use derivative_calculator::ast::Expr;
+use derivative_calculator::cse::cse;
+
+fn var(name: &str) -> Expr {
+    Expr::Var(name.to_string())
+}
+
+fn sin(arg: Expr) -> Expr {
+    Expr::Func("sin".to_string(), Box::new(arg))
+}
+
+#[test]
+fn repeats_with_nan_are_shared() {
+    let repeated = sin(Expr::Sum(vec![var("x"), Expr::Num(f64::NAN)]));
+    let expr = Expr::Product(vec![repeated.clone(), Expr::Func("cos".to_string(), Box::new(repeated))]);
+    let result = cse(&expr);
+    assert_eq!(result.bindings.len(), 1, "{}", result);
+    assert_eq!(result.to_string(), "t1 = sin (x + NaN)\n(t1 * cos t1)");
+}
+
+#[test]
+fn zero_and_negative_zero_are_kept_apart() {
+    let with = |zero: f64| sin(Expr::Sum(vec![var("x"), Expr::Num(zero)]));
+    let expr = Expr::Product(vec![with(0.0), with(-0.0), with(0.0)]);
+    let result = cse(&expr);
+    assert_eq!(result.bindings, vec![("t1".to_string(), with(0.0))]);
+    // Debug output tells -0 from 0, unlike ==
+    assert_eq!(format!("{:?}", result.inline()), format!("{:?}", expr));
+}
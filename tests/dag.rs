@@ -0,0 +1,37 @@
+use derivative_calculator::context::Context;
+use derivative_calculator::dag::Arena;
+use derivative_calculator::derivative::{derivative, derivative_in};
+use derivative_calculator::simplifier::{simplify, simplify_in};
+
+mod common;
+use common::parse;
+
+#[test]
+fn simplifying_in_an_arena_matches_simplifying_the_tree() {
+    let ctx = Context::default();
+    for f in ["sin(x)*cos(x)", "x^2*exp(x)", "log(x)/x", "tan(x)", "arcsin(x)", "x^x", "sqrt(1+x^2)"] {
+        let (mut arena, mut tree) = (Arena::new(), parse(f));
+        let mut id = arena.from_expr(&tree);
+        for order in 1..=3 {
+            let d = derivative_in(&mut arena, id, "x", &ctx).unwrap();
+            id = simplify_in(&mut arena, d, &ctx).unwrap();
+            tree = simplify(&derivative(&tree, "x").unwrap()).unwrap();
+            assert_eq!(arena.to_expr(id), tree, "order {} of {}", order, f);
+        }
+    }
+}
+
+#[test]
+fn shared_nodes_are_simplified_once() {
+    // Each level refers to the last twice, so the tree has 2^40 leaves
+    let mut arena = Arena::new();
+    let (mut id, zero) = (arena.var("x"), arena.num(0.0));
+    for _ in 0..40 {
+        let (sin, cos) = (arena.func("sin", id), arena.func("cos", id));
+        id = arena.sum(vec![sin, zero, cos]);
+    }
+    let nodes = arena.len();
+    let simplified = simplify_in(&mut arena, id, &Context::default()).unwrap();
+    assert_eq!(arena.children(simplified).len(), 2);
+    assert!(arena.len() < 3 * nodes, "{} nodes grew to {}", nodes, arena.len());
+}
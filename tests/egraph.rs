@@ -0,0 +1,74 @@
+use std::time::{Duration, Instant};
+
+use derivative_calculator::ast::Expr;
+use derivative_calculator::context::Context;
+use derivative_calculator::derivative::derivative_with;
+use derivative_calculator::egraph::{self, CostFn, EGraphSettings};
+use derivative_calculator::limits::MAX_DEPTH;
+use derivative_calculator::logarithm::LogMode;
+use derivative_calculator::simplifier::{normalize, simplify_with};
+use derivative_calculator::trig::TrigMode;
+
+mod common;
+use common::parse;
+
+fn with_egraph(ctx: Context) -> Context {
+    Context { egraph: Some(EGraphSettings::default()), ..ctx }
+}
+
+#[test]
+fn egraph_reaches_forms_the_greedy_simplifier_misses() {
+    let ctx = with_egraph(Context { trig: TrigMode::Identities, ..Context::default() });
+    let d = derivative_with(&parse("tan(x)*cos(x)"), "x", &ctx).unwrap();
+    assert_eq!(simplify_with(&d, &ctx).unwrap().to_string(), "cos x");
+}
+
+#[test]
+fn egraph_uses_only_the_enabled_trig_and_log_rules() {
+    let off = EGraphSettings::default();
+    for f in ["1/cos(x)", "log(x) + log(y)", "sin(x)^2 - 1"] {
+        let expr = normalize(&parse(f));
+        assert_eq!(egraph::simplify(&expr, &Context::default(), &off), expr, "{}", f);
+    }
+    let identities = Context { trig: TrigMode::Identities, ..Context::default() };
+    assert_eq!(egraph::simplify(&normalize(&parse("1/cos(x)")), &identities, &off).to_string(), "sec x");
+    let contract = Context { log: LogMode::Contract, ..Context::default() };
+    assert_eq!(egraph::simplify(&normalize(&parse("log(x) + log(y)")), &contract, &off), normalize(&parse("log(x*y)")));
+}
+
+#[test]
+fn cost_functions_pick_different_forms() {
+    let ctx = Context::default();
+    let expr = normalize(&parse("x*y + x*z"));
+    let nodes = EGraphSettings { cost: CostFn::NodeCount, ..EGraphSettings::default() };
+    let ops = EGraphSettings { cost: CostFn::OpCount, ..EGraphSettings::default() };
+    assert_eq!(egraph::simplify(&expr, &ctx, &nodes), normalize(&parse("x*(y + z)")));
+    assert_eq!(egraph::simplify(&expr, &ctx, &ops), normalize(&parse("x*(y + z)")));
+    assert_eq!(CostFn::parse("ops"), Ok(CostFn::OpCount));
+    assert!(CostFn::parse("size").is_err());
+}
+
+#[test]
+fn the_context_time_limit_also_stops_the_egraph() {
+    let mut ctx = Context { trig: TrigMode::Identities, ..Context::default() };
+    ctx.limits.time_limit = Some(Duration::from_millis(50));
+    let settings = EGraphSettings { time_limit: Duration::from_secs(20), node_limit: usize::MAX, iter_limit: usize::MAX, ..EGraphSettings::default() };
+    let expr = normalize(&parse("(a + b)*(c + d)*(f + g)*(h + j)*(k + l)*(m + n)"));
+    let start = Instant::now();
+    egraph::simplify(&expr, &ctx, &settings);
+    assert!(start.elapsed() < Duration::from_secs(5), "took {:?}", start.elapsed());
+}
+
+#[test]
+fn input_at_the_depth_limit_works_on_a_small_stack() {
+    let deep = (1..MAX_DEPTH - 10).fold(parse("x"), |e, _| Expr::Func("sin".to_string(), Box::new(e)));
+    std::thread::Builder::new()
+        .stack_size(2 << 20)
+        .spawn(move || {
+            let settings = EGraphSettings::default();
+            assert_eq!(egraph::simplify(&deep, &Context::default(), &settings), deep);
+        })
+        .unwrap()
+        .join()
+        .unwrap();
+}
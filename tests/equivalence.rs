@@ -0,0 +1,54 @@
+use derivative_calculator::assumptions::Assumptions;
+use derivative_calculator::context::Context;
+use derivative_calculator::equivalence::{equivalent, equivalent_with, Equivalence};
+
+mod common;
+use common::parse;
+
+fn check(a: &str, b: &str) -> Equivalence {
+    equivalent(&parse(a), &parse(b))
+}
+
+#[test]
+fn equal_expressions_are_equal() {
+    for (a, b) in [("2*cos(2*x)", "2cos(2x)"), ("sin(x)^2", "1 - cos(x)^2"), ("(x+1)^2", "x^2 + 2x + 1"), ("x*y + x", "x*(y + 1)")] {
+        assert_eq!(check(a, b), Equivalence::Equal, "{} = {}", a, b);
+    }
+}
+
+#[test]
+fn different_expressions_are_not_equal_at_some_point() {
+    for (a, b) in [("sin(x)", "cos(x)"), ("x^2", "x^3"), ("x + y", "x - y")] {
+        assert!(matches!(check(a, b), Equivalence::NotEqual(_)), "{} != {}", a, b);
+    }
+    let Equivalence::NotEqual(point) = check("sqrt(x^2)", "x") else { panic!("sqrt(x^2) = x") };
+    assert!(point[0].1 < 0.0, "{:?}", point);
+}
+
+#[test]
+fn assumptions_restrict_the_sampled_points() {
+    let mut assumptions = Assumptions::default();
+    assumptions.assume("x>0").unwrap();
+    let ctx = Context { assumptions, ..Context::default() };
+    assert_eq!(equivalent_with(&parse("sqrt(x^2)"), &parse("x"), &ctx), Equivalence::Equal);
+}
+
+#[test]
+fn a_different_domain_is_not_equal_even_when_the_difference_cancels() {
+    assert!(matches!(check("1/x", "1/x + 0*log(x)"), Equivalence::NotEqual(_)));
+}
+
+#[test]
+fn infinite_values_must_agree_too() {
+    // Only one side is infinite
+    assert!(matches!(check("1/0 + x", "x"), Equivalence::NotEqual(_)));
+    // Both are, with opposite signs for negative x
+    assert!(matches!(check("x/0", "abs(x)/0"), Equivalence::NotEqual(_)));
+    // The same infinity everywhere tells nothing
+    assert_eq!(check("x/0", "x/0 + 1"), Equivalence::Unknown);
+}
+
+#[test]
+fn expressions_defined_nowhere_are_unknown() {
+    assert_eq!(check("sqrt(-1 - x^2)", "log(-1 - x^2)"), Equivalence::Unknown);
+}
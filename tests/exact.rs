@@ -0,0 +1,45 @@
+use derivative_calculator::ast::Expr;
+use derivative_calculator::derivative::derivative;
+use derivative_calculator::exact::special_value;
+use derivative_calculator::simplifier::simplify;
+
+mod common;
+use common::parse;
+
+fn simplified(text: &str) -> String {
+    simplify(&parse(text)).unwrap().to_string()
+}
+
+#[test]
+fn notable_angles_fold() {
+    assert_eq!(simplified("sin(pi/6)"), "0.5");
+    assert_eq!(simplified("cos(pi)"), "-1");
+    assert_eq!(simplified("sin(3000*pi + pi/6)"), "0.5");
+    assert_eq!(simplified("arccos(-sqrt(3)/2)"), "(5 * pi / 6)");
+}
+
+#[test]
+fn huge_and_tiny_angles_do_not_fold() {
+    for x in [1e15, -1e15, 1e-13, -1e-13] {
+        for name in ["sin", "cos", "tan"] {
+            assert_eq!(special_value(name, &Expr::Num(x)), None, "{}({})", name, x);
+        }
+    }
+    assert_eq!(special_value("arcsin", &Expr::Num(0.5 + 1e-13)), None);
+}
+
+#[test]
+fn integer_powers_of_square_roots_reduce() {
+    assert_eq!(simplified("2/sqrt(2)"), "sqrt 2");
+    assert_eq!(simplified("sqrt(2)^2"), "2");
+    assert_eq!(simplified("sqrt(3)^3"), "(3 * sqrt 3)");
+    assert_eq!(simplified("4/sqrt(2)^4"), "1");
+
+    // Derivatives at notable points
+    let at = |f: &str, x0: &str| {
+        let d = derivative(&parse(f), "x").unwrap();
+        simplify(&d.substitute("x", &parse(x0))).unwrap().to_string()
+    };
+    assert_eq!(at("tan(x)", "pi/4"), "2");
+    assert_eq!(at("sec(x)", "pi/4"), "sqrt 2");
+}
@@ -0,0 +1,21 @@
+use std::collections::HashMap;
+
+use derivative_calculator::context::Context;
+use derivative_calculator::interval::{bound_derivative, Interval};
+
+mod common;
+use common::parse;
+
+fn bound(f: &str, lo: f64, hi: f64) -> Interval {
+    let domain = HashMap::from([("x".to_string(), Interval::new(lo, hi))]);
+    bound_derivative(&parse(f), "x", &domain, &Context::default()).unwrap()
+}
+
+#[test]
+fn derivative_bounds_leave_out_where_the_function_has_no_values() {
+    // 1/x has values for x < 0, but log(x) does not
+    assert!(bound("log(x)", -2.0, -1.0).is_empty());
+    assert!(bound("sqrt(x) + log(x)", -2.0, -1.0).is_empty());
+    let positive = bound("log(x)", 1.0, 2.0);
+    assert!(positive.contains(0.5) && positive.contains(1.0) && !positive.contains(-1.0));
+}
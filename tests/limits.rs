@@ -0,0 +1,62 @@
+use derivative_calculator::ast::Expr;
+use derivative_calculator::context::Context;
+use derivative_calculator::dag::Arena;
+use derivative_calculator::derivative::derivative_with;
+use derivative_calculator::limits::MAX_DEPTH;
+use derivative_calculator::simplifier::{normalize, simplify_with};
+
+mod common;
+use common::try_parse;
+
+/// `sin(sin(...(x)))`, `depth` levels deep.
+fn nested_sin(depth: usize) -> Expr {
+    (1..depth).fold(Expr::Var("x".to_string()), |e, _| Expr::Func("sin".to_string(), Box::new(e)))
+}
+
+/// Runs `f` on a thread with the 2 MB stack spawned threads get by default.
+fn on_small_stack(f: impl FnOnce() + Send + 'static) {
+    std::thread::Builder::new().stack_size(2 << 20).spawn(f).unwrap().join().unwrap();
+}
+
+#[test]
+fn input_at_the_depth_limit_works_on_a_small_stack() {
+    on_small_stack(|| {
+        let ctx = Context::default();
+        let expr = nested_sin(MAX_DEPTH - 10);
+        let derivative = derivative_with(&expr, "x", &ctx).unwrap();
+        assert!(derivative.size() > expr.size());
+        assert_eq!(simplify_with(&expr, &ctx).unwrap(), normalize(&expr));
+        let mut arena = Arena::new();
+        let id = arena.from_expr(&expr);
+        assert_eq!(arena.to_expr(id), expr);
+        let text = format!("{}x{}", "sin(".repeat(MAX_DEPTH - 10), ")".repeat(MAX_DEPTH - 10));
+        assert!(try_parse(&text).is_ok());
+    });
+}
+
+#[test]
+fn input_past_the_depth_limit_is_an_error() {
+    on_small_stack(|| {
+        let ctx = Context::default();
+        let expr = nested_sin(5 * MAX_DEPTH);
+        assert!(derivative_with(&expr, "x", &ctx).is_err());
+        assert!(simplify_with(&expr, &ctx).is_err());
+        let text = format!("{}x{}", "(".repeat(5 * MAX_DEPTH), ")".repeat(5 * MAX_DEPTH));
+        assert!(try_parse(&text).is_err());
+    });
+}
+
+#[test]
+fn input_with_100k_nodes_works_on_a_small_stack() {
+    on_small_stack(|| {
+        let ctx = Context::default();
+        // 33333 terms of three nodes each
+        let sum = (1..=33_333).map(|k| format!("{}x^{}", k, k)).collect::<Vec<_>>().join(" + ");
+        let expr = try_parse(&sum).unwrap();
+        assert!(expr.size() > 100_000);
+        let derivative = simplify_with(&derivative_with(&expr, "x", &ctx).unwrap(), &ctx).unwrap();
+        assert!(derivative.size() > 100_000);
+        let negated = try_parse(&format!("{}x", "-".repeat(100_001))).unwrap();
+        assert_eq!(simplify_with(&derivative_with(&negated, "x", &ctx).unwrap(), &ctx).unwrap(), Expr::Num(-1.0));
+    });
+}
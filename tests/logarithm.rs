@@ -0,0 +1,52 @@
+use derivative_calculator::assumptions::Assumptions;
+use derivative_calculator::ast::Expr;
+use derivative_calculator::context::Context;
+use derivative_calculator::derivative::derivative_with;
+use derivative_calculator::equivalence::{equivalent, equivalent_with, Equivalence};
+use derivative_calculator::logarithm::LogMode;
+use derivative_calculator::simplifier::simplify_with;
+
+mod common;
+use common::parse;
+
+fn positive_x() -> Context {
+    let mut assumptions = Assumptions::default();
+    assumptions.assume("x>0").unwrap();
+    Context { assumptions, ..Context::default() }
+}
+
+#[test]
+fn exp_of_log_cancels_only_for_positive_arguments() {
+    let f = parse("exp(log(x))");
+    let derive = |ctx: &Context| simplify_with(&derivative_with(&f, "x", ctx).unwrap(), ctx).unwrap();
+    assert_ne!(derive(&Context::default()), Expr::Num(1.0));
+    assert_eq!(derive(&positive_x()), Expr::Num(1.0));
+}
+
+#[test]
+fn check_tells_apart_functions_with_different_domains() {
+    let (f, x) = (parse("exp(log(x))"), parse("x"));
+    assert!(matches!(equivalent(&f, &x), Equivalence::NotEqual(_)));
+    assert_eq!(equivalent_with(&f, &x, &positive_x()), Equivalence::Equal);
+}
+
+#[test]
+fn expand_splits_only_logs_of_positive_arguments() {
+    let expand = |f: &str, ctx: Context| simplify_with(&parse(f), &Context { log: LogMode::Expand, ..ctx }).unwrap().to_string();
+    assert_eq!(expand("log(x*y)", Context::default()), "log (x * y)");
+    assert_eq!(expand("log((-x)*(-y))", Context::default()), "log (x * y)");
+    assert_eq!(expand("log(x^3)", Context::default()), "log (x ^ 3)");
+    let mut assumptions = Assumptions::default();
+    assumptions.assume("x>0").unwrap();
+    assumptions.assume("y>0").unwrap();
+    let positive = Context { assumptions, ..Context::default() };
+    assert_eq!(expand("log(x*y)", positive.clone()), "(log y + log x)");
+    assert_eq!(expand("log(x^3)", positive), "(3 * log x)");
+}
+
+#[test]
+fn contract_merges_sums_of_logs() {
+    let ctx = Context { log: LogMode::Contract, ..Context::default() };
+    assert_eq!(simplify_with(&parse("log(x) + log(y)"), &ctx).unwrap(), simplify_with(&parse("log(x*y)"), &ctx).unwrap());
+    assert_eq!(simplify_with(&parse("2*log(x)"), &ctx).unwrap().to_string(), "log (x ^ 2)");
+}
@@ -0,0 +1,71 @@
+use derivative_calculator::context::Context;
+use derivative_calculator::plot::{Glyphs, TerminalPlot, sample, tangent, y_range};
+
+mod common;
+use common::parse;
+
+#[test]
+fn samples_hold_the_function_and_its_derivative() {
+    let series = sample(&parse("x^2"), "x", -2.0, 2.0, 5, &Context::default()).unwrap();
+    assert_eq!(series.len(), 2);
+    assert_eq!((series[0].label.as_str(), series[1].label.as_str()), ("f", "f'"));
+    assert_eq!(series[0].xs, vec![-2.0, -1.0, 0.0, 1.0, 2.0]);
+    assert_eq!(series[0].ys, vec![4.0, 1.0, 0.0, 1.0, 4.0]);
+    assert_eq!(series[1].ys, vec![-4.0, -2.0, 0.0, 2.0, 4.0]);
+}
+
+#[test]
+fn the_y_range_ignores_poles() {
+    let ctx = Context::default();
+    let parabola = sample(&parse("x^2"), "x", -2.0, 2.0, 5, &ctx).unwrap();
+    assert_eq!(y_range(&parabola), Some((-4.0, 4.0)));
+    // tan x runs off to about ±1e16 next to its poles at ±pi/2
+    let tan = sample(&parse("tan(x)"), "x", -3.0, 3.0, 400, &ctx).unwrap();
+    let (lo, hi) = y_range(&tan[..1]).unwrap();
+    assert!(lo > -100.0 && hi < 100.0 && lo < -5.0 && hi > 5.0, "{} to {}", lo, hi);
+    let undefined = sample(&parse("sqrt(x)"), "x", -2.0, -1.0, 50, &ctx).unwrap();
+    assert_eq!(y_range(&undefined), None);
+}
+
+#[test]
+fn plots_have_labelled_axes_and_a_legend() {
+    let series = sample(&parse("x^2"), "x", -2.0, 2.0, 50, &Context::default()).unwrap();
+    let plot = TerminalPlot { width: 20, height: 6, glyphs: Glyphs::Ascii, color: false };
+    let expected = [
+        " 4 +**        |      ooo",
+        "   | ***      |   oooo* ",
+        "   |    ******oooo**    ",
+        " 0 +------oooo+---------",
+        "   |  oooo    |         ",
+        "-4 +ooo       |         ",
+        "   +--------------------",
+        "   -2                  2",
+        "    * f   o f'",
+    ];
+    assert_eq!(plot.render(&series).unwrap(), expected.join("\n") + "\n");
+
+    let braille = TerminalPlot { width: 20, height: 6, ..TerminalPlot::default() }.render(&series).unwrap();
+    let lines: Vec<&str> = braille.lines().collect();
+    assert_eq!(lines.len(), expected.len());
+    assert!(lines[..6].iter().all(|l| l.chars().skip(4).all(|c| ('\u{2800}'..='\u{28ff}').contains(&c))));
+    assert!(lines[3].starts_with(" 0 ┤"), "{}", braille);
+    assert!(lines[8].contains("━━ f") && lines[8].contains("╍╍ f'"));
+
+    let colored = TerminalPlot { color: true, ..plot }.render(&series).unwrap();
+    assert!(colored.contains("\x1b[36m*\x1b[0m") && colored.contains("\x1b[35mo\x1b[0m"));
+}
+
+#[test]
+fn nothing_is_plotted_without_finite_values() {
+    let series = sample(&parse("sqrt(x)"), "x", -2.0, -1.0, 50, &Context::default()).unwrap();
+    assert!(TerminalPlot::default().render(&series).is_err());
+}
+
+#[test]
+fn tangents_run_across_the_range() {
+    let ctx = Context::default();
+    let line = tangent(&parse("x^2"), "x", 1.0, -2.0, 2.0, &ctx).unwrap().unwrap();
+    assert_eq!(line.label, "tangent at x = 1");
+    assert_eq!((line.xs, line.ys), (vec![-2.0, 2.0], vec![-5.0, 3.0]));
+    assert!(tangent(&parse("log(x)"), "x", -1.0, -2.0, 2.0, &ctx).unwrap().is_none());
+}
@@ -0,0 +1,59 @@
+use derivative_calculator::ast::Expr;
+use derivative_calculator::limits::{Budget, Limits};
+use derivative_calculator::polynomial::{Poly, Rational};
+
+mod common;
+use common::parse;
+
+fn poly(text: &str) -> Poly {
+    Poly::from_expr(&parse(text))
+}
+
+fn x() -> Expr {
+    Expr::Var("x".to_string())
+}
+
+#[test]
+fn gcd_and_division_work() {
+    let limits = Limits::default();
+    let gcd = poly("x^2 - 1").gcd_limited(&poly("x^2 - 2x + 1"), &Budget::start(&limits)).unwrap();
+    assert_eq!(gcd, poly("x - 1"));
+    let (q, r) = poly("x^3 + 1").div_rem(&poly("x + 1"), &x()).unwrap();
+    assert_eq!((q, r), (poly("x^2 - x + 1"), Poly::zero()));
+}
+
+#[test]
+fn non_finite_coefficients_are_an_error() {
+    let limits = Limits::default();
+    for bad in ["x + nan", "x + inf", "x + 1/0", "nan*x^2 + x"] {
+        for other in ["x", "x^2 - 1"] {
+            let (a, b) = (poly(bad), poly(other));
+            assert!(a.gcd_limited(&b, &Budget::start(&limits)).is_err(), "gcd({}, {})", bad, other);
+            assert!(b.gcd_limited(&a, &Budget::start(&limits)).is_err(), "gcd({}, {})", other, bad);
+            assert!(a.div_rem(&b, &x()).is_err(), "({}) / ({})", bad, other);
+            assert!(b.div_rem(&a, &x()).is_err(), "({}) / ({})", other, bad);
+        }
+    }
+}
+
+#[test]
+fn overflowing_coefficients_are_an_error() {
+    // The remainders' coefficients outgrow an i128 long before the gcd is found
+    let limits = Limits::default();
+    let (a, b) = (poly("(x + 1/7)^20"), poly("(x + 1/3)^19"));
+    assert!(a.gcd_limited(&b, &Budget::start(&limits)).is_err());
+    assert!(poly("(x + 1/7)^40").to_expr().numbers_finite());
+    assert!(!poly("(x + 1/7)^50").to_expr().numbers_finite());
+}
+
+#[test]
+fn floats_are_read_as_fractions_only_within_rounding() {
+    assert_eq!(Rational::from_f64(0.1), Some(Rational::new(1, 10)));
+    assert_eq!(Rational::from_f64(0.1 + 0.2), Some(Rational::new(3, 10)));
+    assert_eq!(Rational::from_f64(-2.0 / 3.0), Some(Rational::new(-2, 3)));
+    assert_eq!(Rational::from_f64(1.0 / 300_000.0), Some(Rational::new(1, 300_000)));
+    // Neither close to a fraction nor zero, however small
+    for x in [std::f64::consts::PI / 180.0, 1.0 / 3.0 + 1e-13, 1e-13, -1e-300] {
+        assert_eq!(Rational::from_f64(x), None, "{}", x);
+    }
+}
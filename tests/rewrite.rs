@@ -0,0 +1,60 @@
+use derivative_calculator::assumptions::Assumptions;
+use derivative_calculator::context::Context;
+use derivative_calculator::rewrite::RuleSet;
+use derivative_calculator::simplifier::{normalize, simplify, simplify_with};
+
+mod common;
+use common::parse;
+
+fn assuming(fact: &str) -> Assumptions {
+    let mut assumptions = Assumptions::default();
+    assumptions.assume(fact).unwrap();
+    assumptions
+}
+
+#[test]
+fn rules_rewrite_matching_subexpressions() {
+    let rules = RuleSet::parse("# a comment\n\nsec(a)^2 -> 1 + tan(a)^2\n").unwrap();
+    let rewritten = rules.apply(&normalize(&parse("x * sec(2x)^2")), &Assumptions::default());
+    assert_eq!(rewritten, normalize(&parse("x * (1 + tan(2x)^2)")));
+}
+
+#[test]
+fn sum_patterns_match_some_of_the_terms() {
+    let rules = RuleSet::parse("sin(a)^2 + cos(a)^2 -> 1").unwrap();
+    let rewritten = rules.apply(&normalize(&parse("sin(y)^2 + x + cos(y)^2")), &Assumptions::default());
+    assert_eq!(rewritten, normalize(&parse("x + 1")));
+}
+
+#[test]
+fn conditions_restrict_what_a_rule_matches() {
+    let rules = RuleSet::parse("n*log(a) -> log(a^n) if number(n)\nabs(a) -> a if positive(a)").unwrap();
+    let apply = |text: &str, assumptions: &Assumptions| rules.apply(&normalize(&parse(text)), assumptions);
+    let none = Assumptions::default();
+    assert_eq!(apply("2*log(x)", &none), normalize(&parse("log(x^2)")));
+    assert_eq!(apply("y*log(x)", &none), normalize(&parse("y*log(x)")));
+    assert_eq!(apply("abs(x)", &none), normalize(&parse("abs(x)")));
+    assert_eq!(apply("abs(x)", &assuming("x>0")), parse("x"));
+}
+
+#[test]
+fn malformed_rules_are_reported_with_their_line() {
+    assert!(RuleSet::parse("sin(a) = cos(a)").unwrap_err().contains("missing '->'"));
+    assert!(RuleSet::parse("\nlog(a) -> a if odd(a)").unwrap_err().starts_with("line 2"));
+    assert!(RuleSet::parse("log(a) -> a if positive a").is_err());
+}
+
+#[test]
+fn user_rules_apply_during_simplify() {
+    let mut ctx = Context::default();
+    ctx.rules.add("sec(a)^2 -> 1 + tan(a)^2").unwrap();
+    assert_eq!(simplify_with(&parse("sec(x)^2"), &ctx).unwrap(), simplify(&parse("1 + tan(x)^2")).unwrap());
+}
+
+#[test]
+fn square_roots_squared_cancel_only_for_positive_arguments() {
+    assert_eq!(simplify(&parse("sqrt(x)^2")).unwrap().to_string(), "(sqrt x ^ 2)");
+    assert_eq!(simplify(&parse("sqrt(4)^2")).unwrap(), parse("4"));
+    let ctx = Context { assumptions: assuming("x>0"), ..Context::default() };
+    assert_eq!(simplify_with(&parse("sqrt(x)^2"), &ctx).unwrap(), parse("x"));
+}
@@ -0,0 +1,55 @@
+use derivative_calculator::derivative::derivative;
+use derivative_calculator::simplifier::simplify;
+
+mod common;
+use common::parse;
+
+/// The simplified derivative with respect to `x`, as printed.
+fn derive(text: &str) -> String {
+    simplify(&derivative(&parse(text), "x").unwrap()).unwrap().to_string()
+}
+
+#[test]
+fn constants_have_derivative_zero() {
+    for constant in ["0^0", "sin(pi)^0", "0/0", "2^3"] {
+        assert_eq!(derive(constant), "0", "d/dx {}", constant);
+    }
+}
+
+#[test]
+fn non_finite_numbers_do_not_break_cancelling() {
+    // These used to send the polynomial gcd into endless recursion
+    for f in ["1/x + 0/0", "0^0/(y+9)", "1/x + 1/0", "1/x + nan", "inf/(x+1)", "(x^2 - 1)/(x - 1) + nan"] {
+        assert!(simplify(&parse(f)).is_ok(), "simplify {}", f);
+        assert!(derivative(&parse(f), "x").and_then(|d| simplify(&d)).is_ok(), "d/dx {}", f);
+    }
+    assert_eq!(derive("1/x + 0/0"), "-(1 / (x ^ 2))");
+    assert_eq!(derive("0^0/(y+9)"), "0");
+}
+
+#[test]
+fn equal_bases_merge_into_one_power() {
+    for (f, expected) in [
+        ("x*x", "(x ^ 2)"),
+        ("x^2*x^3", "(x ^ 5)"),
+        ("x^a*x^b", "(x ^ (b + a))"),
+        ("2*x*3*x", "(6 * (x ^ 2))"),
+        ("sin(x)*sin(x)", "(sin x ^ 2)"),
+    ] {
+        assert_eq!(simplify(&parse(f)).unwrap().to_string(), expected, "simplify {}", f);
+    }
+}
+
+#[test]
+fn common_factors_cancel_across_division() {
+    for (f, expected) in [
+        ("x/x", "1"),
+        ("x*y/x", "y"),
+        ("(x*y)/(y*z)", "(x / z)"),
+        ("x^3/x^5", "(1 / (x ^ 2))"),
+        ("x^a/x", "(x ^ (a - 1))"),
+    ] {
+        assert_eq!(simplify(&parse(f)).unwrap().to_string(), expected, "simplify {}", f);
+    }
+    assert_eq!(derive("x*x"), "(2 * x)");
+}
@@ -0,0 +1,62 @@
+use derivative_calculator::context::Context;
+use derivative_calculator::plot::{Series, sample, sample_adaptive, tangent};
+use derivative_calculator::svg::SvgPlot;
+
+mod common;
+use common::parse;
+
+fn tan_with_tangent() -> Vec<Series> {
+    let ctx = Context::default();
+    let mut series = sample(&parse("tan(x)"), "x", -3.0, 3.0, 50, &ctx).unwrap();
+    series.push(tangent(&parse("tan(x)"), "x", 0.0, -3.0, 3.0, &ctx).unwrap().unwrap());
+    series
+}
+
+#[test]
+fn images_have_a_path_and_a_legend_entry_per_series() {
+    let svg = SvgPlot::default().render(&tan_with_tangent()).unwrap();
+    assert!(svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"640\" height=\"400\""));
+    assert!(svg.ends_with("</svg>\n"));
+    assert_eq!(svg.matches("<path ").count(), 3);
+    for label in [">f<", ">f'<", ">tangent at x = 0<"] {
+        assert!(svg.contains(label), "no legend entry {}", label);
+    }
+    // The function solid, the derivative dashed and the tangent dotted
+    assert!(svg.contains(r##"stroke="#1f77b4"/>"##));
+    assert!(svg.contains(r##"stroke="#d62728" stroke-dasharray="6 4"/>"##));
+    assert!(svg.contains(r##"stroke="#2ca02c" stroke-dasharray="2 3"/>"##));
+    // Grid labels on round numbers
+    for tick in [">-3<", ">0<", ">3<", ">-20<", ">40<"] {
+        assert!(svg.contains(tick), "no tick {}", tick);
+    }
+}
+
+#[test]
+fn curves_break_at_poles_and_labels_are_escaped() {
+    let mut series = tan_with_tangent();
+    let svg = SvgPlot::default().render(&series).unwrap();
+    let derivative = svg.lines().find(|l| l.contains("#d62728") && l.starts_with("<path")).unwrap();
+    // sec(x)^2 leaves the plot at each pole, where the path moves instead of drawing
+    assert!(derivative.matches(" M").count() >= 2, "{}", derivative);
+
+    series[0].label = "f<x> & g".to_string();
+    let svg = SvgPlot::default().render(&series).unwrap();
+    assert!(svg.contains(">f&lt;x&gt; &amp; g<"));
+    assert!(SvgPlot::default().render(&sample(&parse("sqrt(x)"), "x", -2.0, -1.0, 10, &Context::default()).unwrap()).is_err());
+}
+
+#[test]
+fn adaptive_sampling_adds_points_only_where_curves_bend_or_end() {
+    let ctx = Context::default();
+    let straight = sample_adaptive(&parse("x^2"), "x", -3.0, 3.0, &ctx).unwrap();
+    let steep = sample_adaptive(&parse("tan(x)"), "x", -3.0, 3.0, &ctx).unwrap();
+    assert_eq!(straight[0].xs.len(), 200);
+    assert!(steep[0].xs.len() > 300 && steep[1].xs.len() > 300);
+    for s in steep.iter().chain(&straight) {
+        assert!(s.xs.windows(2).all(|w| w[0] < w[1]), "{} is not increasing", s.label);
+    }
+    // The edge of the domain of sqrt(x) is found much closer than the 0.03 step
+    let sqrt = sample_adaptive(&parse("sqrt(x)"), "x", -3.0, 3.0, &ctx).unwrap();
+    let first = sqrt[0].ys.iter().position(|y| y.is_finite()).unwrap();
+    assert!(sqrt[0].xs[first] - sqrt[0].xs[first - 1] < 1e-3);
+}
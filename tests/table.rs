@@ -0,0 +1,15 @@
+use derivative_calculator::context::Context;
+use derivative_calculator::table::tabulate;
+
+mod common;
+use common::parse;
+
+#[test]
+fn derivatives_are_blank_where_the_function_is_not_finite() {
+    // Only f' is asked for, but log(x) still has no value for x <= 0
+    let table = tabulate(&parse("log(x)"), "x", -1.0, 1.0, 4, &[1], &Context::default()).unwrap();
+    assert_eq!(table.headers, ["x", "f'"]);
+    let slopes = &table.columns[1];
+    assert!(slopes[..3].iter().all(|v| v.is_nan()), "{:?}", slopes);
+    assert_eq!(slopes[3..], [2.0, 1.0]);
+}
@@ -0,0 +1,55 @@
+use derivative_calculator::angle::AngleMode;
+use derivative_calculator::context::Context;
+use derivative_calculator::tangent::{tangent_lines, TangentLines};
+
+mod common;
+use common::parse;
+
+fn lines(f: &str, x0: &str, ctx: &Context) -> Result<TangentLines, String> {
+    tangent_lines(&parse(f), "x", &parse(x0), ctx)
+}
+
+#[test]
+fn tangent_and_normal_pass_through_the_point() {
+    let l = lines("x^3 - 3x", "2", &Context::default()).unwrap();
+    assert_eq!(l.point, (2.0, 2.0));
+    assert_eq!((l.tangent.slope, l.tangent.intercept), (9.0, -16.0));
+    assert_eq!(l.tangent.to_string(), "y = ((9 * x) - 16)");
+    let normal = l.normal.unwrap();
+    assert_eq!(normal.slope, -1.0 / 9.0);
+    assert!((normal.intercept - (2.0 + 2.0 / 9.0)).abs() < 1e-12);
+}
+
+#[test]
+fn a_horizontal_tangent_has_a_vertical_normal() {
+    let l = lines("x^2 + 1", "0", &Context::default()).unwrap();
+    assert_eq!(l.tangent.to_string(), "y = 1");
+    assert!(l.normal.is_none());
+}
+
+#[test]
+fn lines_stay_exact_at_notable_points() {
+    let l = lines("sin(x)", "pi/6", &Context::default()).unwrap();
+    assert!(l.tangent.expr.to_string().contains("sqrt 3"), "{}", l.tangent);
+    assert!((l.tangent.slope - 3f64.sqrt() / 2.0).abs() < 1e-12);
+}
+
+#[test]
+fn degree_mode_reads_the_point_in_degrees() {
+    let ctx = Context { angle: AngleMode::Degrees, ..Context::default() };
+    let l = lines("sin(x)", "30", &ctx).unwrap();
+    assert!((l.point.1 - 0.5).abs() < 1e-12);
+    assert!((l.tangent.slope - 3f64.sqrt() / 2.0 * std::f64::consts::PI / 180.0).abs() < 1e-12);
+}
+
+#[test]
+fn there_is_no_tangent_where_the_function_is_not_differentiable() {
+    let ctx = Context::default();
+    for (f, x0) in [("abs(x)", "0"), ("abs(x - 1)", "1"), ("sqrt(abs(x))", "0"), ("x*sqrt(x)", "0"), ("1/x", "0")] {
+        assert!(lines(f, x0, &ctx).is_err(), "{} at {}", f, x0);
+    }
+    // A kink elsewhere, or a smooth product with abs, is fine
+    assert_eq!(lines("abs(x)", "1", &ctx).unwrap().tangent.slope, 1.0);
+    assert_eq!(lines("x*abs(x)", "0", &ctx).unwrap().tangent.slope, 0.0);
+    assert!(lines("tan(x)", "1.57", &ctx).is_ok());
+}
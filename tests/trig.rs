@@ -0,0 +1,51 @@
+use std::collections::HashMap;
+
+use derivative_calculator::ast::Expr;
+use derivative_calculator::complex::{eval_complex, Complex};
+use derivative_calculator::context::Context;
+use derivative_calculator::derivative::derivative_with;
+use derivative_calculator::simplifier::simplify_with;
+use derivative_calculator::trig::TrigMode;
+
+mod common;
+use common::parse;
+
+fn derive(text: &str, trig: TrigMode) -> Expr {
+    let ctx = Context { trig, ..Context::default() };
+    simplify_with(&derivative_with(&parse(text), "x", &ctx).unwrap(), &ctx).unwrap()
+}
+
+#[test]
+fn identities_undo_inverse_functions() {
+    for (f, expected) in [
+        ("sin(arcsin(x))", "1"),
+        ("tan(arctan(x))", "1"),
+        ("tanh(artanh(x))", "1"),
+        ("cos(arcsin(x))", "-(x / sqrt (-(x ^ 2) + 1))"),
+    ] {
+        assert_eq!(derive(f, TrigMode::Identities).to_string(), expected, "d/dx {}", f);
+    }
+}
+
+#[test]
+fn exponential_form_covers_circular_functions() {
+    for f in ["sin(x)", "tan(x)", "sec(x)", "sinh(x)"] {
+        let d = derive(f, TrigMode::Exponential);
+        let text = d.to_string();
+        assert!(text.contains("exp") && !text.contains("sin") && !text.contains("cos"), "d/dx {} = {}", f, text);
+
+        // Still the same function: compare with the derivative in the default mode
+        let vars = HashMap::from([("x".to_string(), Complex::real(0.7))]);
+        let value = eval_complex(&d, &vars).unwrap();
+        let expected = eval_complex(&derive(f, TrigMode::Off), &vars).unwrap();
+        assert!((value - expected).abs() < 1e-12, "d/dx {} at 0.7: {} vs {}", f, value, expected);
+    }
+}
+
+#[test]
+fn squares_of_functions_of_inverse_functions_need_no_square_root() {
+    let ctx = Context { trig: TrigMode::Identities, ..Context::default() };
+    for (f, expected) in [("sec(arctan(x))^2", "((x ^ 2) + 1)"), ("cos(arcsin(x))^2", "(-(x ^ 2) + 1)")] {
+        assert_eq!(simplify_with(&parse(f), &ctx).unwrap().to_string(), expected, "{}", f);
+    }
+}